@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+#[derive(Serialize, Deserialize)]
+struct Node<T> {
+    label: T,
+    size: i64,
+    referenced: bool,
+    prev: usize,
+    next: usize,
+}
+
+/// CLOCK, also known as second chance, with a reference bit per object and a hand that sweeps them.
+///
+/// Hits only set the reference bit. To evict, the hand goes around clearing reference bits until it
+/// finds an object without one. New objects take the place behind the hand, so it reaches them last.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Clock<T> where T : Hash + Eq {
+    nodes: Vec<Option<Node<T>>>, // the clock, linked in the direction the hand moves and around
+    free: Vec<usize>, // unused slots of `nodes`
+    index: HashMap<T, usize>, // slot of every cached item
+    hand: Option<usize>, // next object to look at, None when the cache is empty
+    current_used : i64, // current space in cache
+    size : i64, // size of cache
+    event_count: i32,
+    hit_count : i32,
+    events : EventLog<T>
+}
+
+impl <T> Clock<T> where T : Hash + Eq + Clone {
+    fn node(&self, slot: usize) -> &Node<T> {
+        self.nodes[slot].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, slot: usize) -> &mut Node<T> {
+        self.nodes[slot].as_mut().unwrap()
+    }
+
+    fn push(&mut self, label: T, size: i64) {
+        let node = Node {
+            label: label.clone(),
+            size,
+            referenced: false,
+            prev: 0,
+            next: 0
+        };
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = Some(node);
+                slot
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        let (prev, next) = match self.hand {
+            Some(hand) => (self.node(hand).prev, hand),
+            None => {
+                self.hand = Some(slot);
+                (slot, slot)
+            }
+        };
+        let node = self.node_mut(slot);
+        node.prev = prev;
+        node.next = next;
+        self.node_mut(prev).next = slot;
+        self.node_mut(next).prev = slot;
+        self.index.insert(label, slot);
+    }
+
+    fn unlink(&mut self, slot: usize) -> Node<T> {
+        let node = self.nodes[slot].take().unwrap();
+        if node.next == slot {
+            self.hand = None;
+        } else {
+            self.node_mut(node.prev).next = node.next;
+            self.node_mut(node.next).prev = node.prev;
+            if self.hand == Some(slot) {
+                self.hand = Some(node.next);
+            }
+        }
+        self.free.push(slot);
+        self.index.remove(&node.label);
+        self.current_used -= node.size;
+        node
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let mut slot = self.hand.unwrap();
+            while self.node(slot).referenced {
+                self.node_mut(slot).referenced = false;
+                slot = self.node(slot).next;
+            }
+            self.hand = Some(slot);
+            let node = self.unlink(slot);
+            self.events.record(EventKind::Evict, &node.label, node.size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.current_used += file.size;
+        self.evict();
+        self.push(file.label, file.size);
+    }
+}
+
+impl <T> CacheAlgorithm<T> for Clock<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if let Some(&slot) = self.index.get(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.node_mut(slot).referenced = true;
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Clock::<T> {
+            nodes: vec![],
+            free: vec![],
+            index: HashMap::new(),
+            hand: None,
+            current_used: 0,
+            size,
+            event_count: 0,
+            hit_count: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.index.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.index.get(&file.label) {
+            Some(&slot) => {
+                let node = self.node_mut(slot);
+                let old = node.size;
+                node.size = file.size;
+                self.current_used += file.size - old;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.index.get(label) {
+            Some(&slot) => {
+                self.unlink(slot);
+                true
+            }
+            None => false
+        }
+    }
+
+    // A write to a cached object counts as a reference
+    fn admit(&mut self, file: FileRecord<T>) {
+        if let Some(&slot) = self.index.get(&file.label) {
+            self.node_mut(slot).referenced = true;
+            self.resize(&file);
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn referenced_objects_get_a_second_chance() {
+        let mut cache = Clock::<i32>::new(3);
+        for label in 1..=3 {
+            cache.request(get(label, 1));
+        }
+        assert!(cache.request(get(1, 1)));
+        cache.request(get(4, 1));
+        assert!(cache.contains(&1) && !cache.contains(&2));
+        assert!(!cache.node(cache.index[&1]).referenced);
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Bounds of the share of the cache given to cold pages
+const MIN_COLD_PERCENT: i64 = 1;
+const MAX_COLD_PERCENT: i64 = 99;
+
+#[derive(Serialize, Deserialize)]
+struct Node<T> {
+    label: T,
+    size: i64,
+    hot: bool,
+    referenced: bool,
+    test: bool, // cold pages in their test period, non-resident pages always are
+    resident: bool,
+    prev: usize,
+    next: usize,
+}
+
+/// CLOCK-Pro (Jiang et al.), LIRS approximated on a single clock.
+///
+/// Pages are hot or cold. A cold page requested again during its test period, which lasts until
+/// the hot hand passes it, becomes hot. Cold pages keep their test period after eviction, and
+/// requests to them grow the share of the cache given to cold pages. Test periods ending without
+/// a request shrink it.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct ClockPro<T> where T : Hash + Eq {
+    nodes: Vec<Option<Node<T>>>, // the clock, linked from older to newer and around
+    free: Vec<usize>, // unused slots of `nodes`
+    index: HashMap<T, usize>, // slot of every resident and non-resident page
+    head: Option<usize>, // newest page, the hands start from the one after it
+    hand_hot: Option<usize>,
+    hand_cold: Option<usize>,
+    hand_test: Option<usize>,
+    hot_used: i64,
+    cold_used: i64,
+    test_used: i64, // sizes of the non-resident pages
+    cold_target: i64,
+    size : i64, // size of cache
+    event_count: i32,
+    hit_count : i32,
+    events : EventLog<T>
+}
+
+impl <T> ClockPro<T> where T : Hash + Eq + Clone {
+    fn node(&self, slot: usize) -> &Node<T> {
+        self.nodes[slot].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, slot: usize) -> &mut Node<T> {
+        self.nodes[slot].as_mut().unwrap()
+    }
+
+    // Page a hand points at, the oldest one if it has not moved yet
+    fn hand(&self, hand: Option<usize>) -> usize {
+        hand.unwrap_or_else(|| self.node(self.head.unwrap()).next)
+    }
+
+    fn attach(&mut self, slot: usize) {
+        match self.head {
+            Some(head) => {
+                let next = self.node(head).next;
+                let node = self.node_mut(slot);
+                node.prev = head;
+                node.next = next;
+                self.node_mut(head).next = slot;
+                self.node_mut(next).prev = slot;
+            }
+            None => {
+                let node = self.node_mut(slot);
+                node.prev = slot;
+                node.next = slot;
+            }
+        }
+        self.head = Some(slot);
+    }
+
+    // Takes a page off the clock, moving hands that point at it to the next one
+    fn detach(&mut self, slot: usize) {
+        let (prev, next) = (self.node(slot).prev, self.node(slot).next);
+        if next == slot {
+            self.head = None;
+            self.hand_hot = None;
+            self.hand_cold = None;
+            self.hand_test = None;
+            return;
+        }
+        self.node_mut(prev).next = next;
+        self.node_mut(next).prev = prev;
+        if self.head == Some(slot) {
+            self.head = Some(prev);
+        }
+        for hand in [&mut self.hand_hot, &mut self.hand_cold, &mut self.hand_test].iter_mut() {
+            if **hand == Some(slot) {
+                **hand = Some(next);
+            }
+        }
+    }
+
+    fn push(&mut self, label: T, size: i64, hot: bool) {
+        let node = Node {
+            label: label.clone(),
+            size,
+            hot,
+            referenced: false,
+            test: !hot,
+            resident: true,
+            prev: 0,
+            next: 0
+        };
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = Some(node);
+                slot
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.attach(slot);
+        self.index.insert(label, slot);
+        if hot {
+            self.hot_used += size;
+        } else {
+            self.cold_used += size;
+        }
+    }
+
+    fn remove(&mut self, slot: usize) -> Node<T> {
+        self.detach(slot);
+        let node = self.nodes[slot].take().unwrap();
+        self.free.push(slot);
+        self.index.remove(&node.label);
+        node
+    }
+
+    // A test period ended without a request, so cold pages get less of the cache
+    fn drop_ghost(&mut self, slot: usize) {
+        let node = self.remove(slot);
+        self.test_used -= node.size;
+        self.cold_target = (self.cold_target - node.size).max(self.size * MIN_COLD_PERCENT / 100);
+    }
+
+    // Evicts one cold page
+    fn run_cold(&mut self) {
+        loop {
+            if self.cold_used == 0 {
+                self.run_hot();
+            }
+            let slot = self.hand(self.hand_cold);
+            let node = self.node_mut(slot);
+            if node.hot || !node.resident {
+                self.hand_cold = Some(node.next);
+                continue;
+            }
+            if node.referenced {
+                node.referenced = false;
+                if node.test {
+                    node.test = false;
+                    node.hot = true;
+                    let size = node.size;
+                    self.cold_used -= size;
+                    self.hot_used += size;
+                } else {
+                    node.test = true;
+                }
+                self.detach(slot);
+                self.attach(slot);
+                continue;
+            }
+
+            node.resident = false;
+            let (size, test, next) = (node.size, node.test, node.next);
+            self.cold_used -= size;
+            let label = self.node(slot).label.clone();
+            self.events.record(EventKind::Evict, &label, size);
+            if test {
+                self.test_used += size;
+                self.hand_cold = Some(next);
+                // Non-resident pages are remembered for as many bytes as the cache holds
+                while self.test_used > self.size {
+                    self.run_test();
+                }
+            } else {
+                self.remove(slot);
+            }
+            return;
+        }
+    }
+
+    // Turns one hot page cold, ending the test periods of the cold pages it passes
+    fn run_hot(&mut self) {
+        loop {
+            let slot = self.hand(self.hand_hot);
+            self.hand_hot = Some(self.node(slot).next);
+            let node = self.node_mut(slot);
+            if node.hot {
+                if node.referenced {
+                    node.referenced = false;
+                    continue;
+                }
+                node.hot = false;
+                let size = node.size;
+                self.hot_used -= size;
+                self.cold_used += size;
+                return;
+            }
+            if node.test {
+                node.test = false;
+                if !node.resident {
+                    self.drop_ghost(slot);
+                }
+            }
+        }
+    }
+
+    // Forgets one non-resident page, ending the test periods of the cold pages it passes
+    fn run_test(&mut self) {
+        loop {
+            let slot = self.hand(self.hand_test);
+            self.hand_test = Some(self.node(slot).next);
+            let node = self.node_mut(slot);
+            if !node.hot && node.test {
+                node.test = false;
+                if !node.resident {
+                    self.drop_ghost(slot);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn make_room(&mut self, extra: i64) {
+        while self.hot_used + self.cold_used + extra > self.size {
+            self.run_cold();
+        }
+    }
+
+    fn balance(&mut self) {
+        while self.hot_used > self.size - self.cold_target {
+            self.run_hot();
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        // A request to a non-resident page in its test period makes it hot, and cold pages get more of the cache
+        let hot = match self.index.get(&file.label) {
+            Some(&slot) => {
+                let node = self.remove(slot);
+                self.test_used -= node.size;
+                self.cold_target = (self.cold_target + node.size).min(self.size * MAX_COLD_PERCENT / 100);
+                true
+            }
+            None => false
+        };
+        self.make_room(file.size);
+        self.push(file.label, file.size, hot);
+        self.balance();
+    }
+}
+
+impl <T> CacheAlgorithm<T> for ClockPro<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if self.contains(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            let slot = self.index[&file.label];
+            self.node_mut(slot).referenced = true;
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        ClockPro::<T> {
+            nodes: vec![],
+            free: vec![],
+            index: HashMap::new(),
+            head: None,
+            hand_hot: None,
+            hand_cold: None,
+            hand_test: None,
+            hot_used: 0,
+            cold_used: 0,
+            test_used: 0,
+            cold_target: size * MIN_COLD_PERCENT / 100,
+            size,
+            event_count: 0,
+            hit_count: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.hot_used + self.cold_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        match self.index.get(label) {
+            Some(&slot) => self.node(slot).resident,
+            None => false
+        }
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        if !self.contains(&file.label) {
+            return false;
+        }
+        let slot = self.index[&file.label];
+        let node = self.node_mut(slot);
+        let (hot, change) = (node.hot, file.size - node.size);
+        node.size = file.size;
+        if hot {
+            self.hot_used += change;
+        } else {
+            self.cold_used += change;
+        }
+        self.make_room(0);
+        self.balance();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        if !self.contains(label) {
+            return false;
+        }
+        let slot = self.index[label];
+        let node = self.remove(slot);
+        if node.hot {
+            self.hot_used -= node.size;
+        } else {
+            self.cold_used -= node.size;
+        }
+        true
+    }
+
+    // A write to a cached page counts as a reference
+    fn admit(&mut self, file: FileRecord<T>) {
+        if self.contains(&file.label) {
+            let slot = self.index[&file.label];
+            self.node_mut(slot).referenced = true;
+            self.resize(&file);
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn hot_objects_survive_a_scan() {
+        let mut cache = ClockPro::<i32>::new(100);
+        let mut cold = 1000;
+        for _ in 0..10 {
+            for label in 0..20 {
+                cache.request(get(label, 1));
+            }
+            for _ in 0..40 {
+                cache.request(get(cold, 1));
+                cold += 1;
+            }
+        }
+        for _ in 0..1000 {
+            cache.request(get(cold, 1));
+            cold += 1;
+        }
+        assert!((0..20).all(|label| cache.contains(&label)));
+        assert!(cache.used() <= 100);
+    }
+}
@@ -1,25 +1,57 @@
-use std::collections::{ HashSet, VecDeque};
+use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
-use algorithm::CacheAlgorithm;
-use simulator_shared_types::FileRecord;
+use algorithm::{CacheAlgorithm, EventLog};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
 
-pub struct FiFo<T> {
-    queue : VecDeque<FileRecord<T>>, // Double ended queue - basically ring buffer for order items have entered queue
-    cache : HashSet<T>,
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct FiFo<T> where T : Hash + Eq {
+    queue : VecDeque<(T, u64)>, // Double ended queue - basically ring buffer for order items have entered queue
+    cache : HashMap<T, (i64, u64)>, // size and insertion number of cached items
     current_used : i64, // current space in cache
     size : i64, // size of cache
     event_count: i32,
-    hit_count : i32
+    hit_count : i32,
+    insertions : u64,
+    events : EventLog<T>
 }
 
 impl <T> FiFo<T> where T : Hash + Eq + Clone {
     fn in_cache(&self, file : &FileRecord<T>) -> bool {
-        self.cache.contains(&file.label.clone())
+        self.cache.contains_key(&file.label)
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let (label, insertion) = self.queue.pop_back().unwrap();
+            // Invalidated items leave their queue entry behind, only the latest insertion counts
+            if let Some(&(size, current)) = self.cache.get(&label) {
+                if current == insertion {
+                    self.cache.remove(&label);
+                    self.current_used -= size;
+                    self.events.record(EventKind::Evict, &label, size);
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.insertions += 1;
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.cache.insert(file.label.clone(), (file.size, self.insertions));
+        self.current_used += file.size;
+        self.queue.push_front((file.label, self.insertions));
+        self.evict();
     }
 }
 
 impl <T> CacheAlgorithm<T> for FiFo<T> where T : Hash + Eq + Clone{
-    fn simulate(&mut self, file: FileRecord<T>) {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
         if file.size > self.size {
             println!("FILE SIZE: {}", file.size);
             panic!("File larger than cache")
@@ -27,34 +59,72 @@ impl <T> CacheAlgorithm<T> for FiFo<T> where T : Hash + Eq + Clone{
         self.event_count += 1;
         if self.in_cache(&file) {
             self.hit_count += 1;
-            return;
-        }
-        let id = file.label.clone();
-        self.cache.insert(file.label.clone());
-        self.current_used += file.size;
-        self.queue.push_front(file);
-        while self.current_used > self.size {
-            let popped = self.queue.pop_back().unwrap();
-            if popped.label == id {
-                panic!("Popped file we just inserted")
-            }
-            self.cache.remove(&popped.label.clone());
-            self.current_used -= popped.size;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            return true;
         }
+        self.insert(file);
+        false
     }
 
     fn new(size: i64) -> Self {
         FiFo::<T> {
-            queue: VecDeque::<FileRecord<T>>::new(),
+            queue: VecDeque::<(T, u64)>::new(),
             cache: Default::default(),
             current_used: 0,
             size,
             event_count: 0,
-            hit_count: 0
+            hit_count: 0,
+            insertions: 0,
+            events: EventLog::new()
         }
     }
 
     fn stats(&self) -> (i32,i32) {
         (self.event_count, self.hit_count)
     }
-}
\ No newline at end of file
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                self.current_used += file.size - entry.0;
+                entry.0 = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some((size, _)) => {
+                self.current_used -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        if !self.resize(&file) {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    size: i64,
+    insertion: u64, // matches the object's latest queue entry
+    accessed: bool, // requested since it last entered the queue
+}
+
+/// FIFO that gives objects requested since they entered the queue another round instead of
+/// evicting them.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct FifoReinsertion<T> where T : Hash + Eq {
+    queue : VecDeque<(T, u64)>, // newest at the front, stale entries of reinserted and invalidated items stay behind
+    cache : HashMap<T, Entry>,
+    current_used : i64, // current space in cache
+    size : i64, // size of cache
+    event_count: i32,
+    hit_count : i32,
+    insertions : u64,
+    events : EventLog<T>
+}
+
+impl <T> FifoReinsertion<T> where T : Hash + Eq + Clone {
+    fn push(&mut self, label: T) {
+        self.insertions += 1;
+        let entry = self.cache.get_mut(&label).unwrap();
+        entry.insertion = self.insertions;
+        entry.accessed = false;
+        self.queue.push_front((label, self.insertions));
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let (label, insertion) = self.queue.pop_back().unwrap();
+            let accessed = match self.cache.get(&label) {
+                Some(entry) if entry.insertion == insertion => entry.accessed,
+                _ => continue
+            };
+            if accessed {
+                self.push(label);
+            } else {
+                let entry = self.cache.remove(&label).unwrap();
+                self.current_used -= entry.size;
+                self.events.record(EventKind::Evict, &label, entry.size);
+            }
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.cache.insert(file.label.clone(), Entry { size: file.size, insertion: 0, accessed: false });
+        self.current_used += file.size;
+        self.push(file.label);
+        self.evict();
+    }
+}
+
+impl <T> CacheAlgorithm<T> for FifoReinsertion<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if let Some(entry) = self.cache.get_mut(&file.label) {
+            entry.accessed = true;
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        FifoReinsertion::<T> {
+            queue: VecDeque::new(),
+            cache: HashMap::new(),
+            current_used: 0,
+            size,
+            event_count: 0,
+            hit_count: 0,
+            insertions: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                self.current_used += file.size - entry.size;
+                entry.size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some(entry) => {
+                self.current_used -= entry.size;
+                true
+            }
+            None => false
+        }
+    }
+
+    // Writes do not count as requests, like in FIFO
+    fn admit(&mut self, file: FileRecord<T>) {
+        if !self.resize(&file) {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn requested_objects_are_reinserted() {
+        let mut cache = FifoReinsertion::<i32>::new(3);
+        for &label in &[1, 2, 3, 1, 4] {
+            cache.request(get(label, 1));
+        }
+        assert!(cache.contains(&1));
+        assert!(!cache.contains(&2));
+        assert!(!cache.cache[&1].accessed);
+        // 1 went back to the front, so the next evictions take 3 and 4 first
+        cache.request(get(5, 1));
+        assert!(!cache.contains(&3));
+        assert!(cache.contains(&1));
+    }
+}
@@ -1,23 +1,28 @@
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashSet};
+use std::collections::{BinaryHeap, HashMap};
 use std::hash::Hash;
-use algorithm::CacheAlgorithm;
-use simulator_shared_types::FileRecord;
+use algorithm::{CacheAlgorithm, EventLog};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
 
-pub struct GdSize<T>{
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct GdSize<T> where T : Hash + Eq {
     heap: BinaryHeap<SortedFileRecord<T>>,
-    cache: HashSet<T>,
+    cache: HashMap<T, (i64, u64)>, // size and insertion number of cached items
     current_used : i64, // current space in cache
     size : i64, // size of cache
     event_count: i32,
     hit_count : i32,
-    inflation : f64
+    inflation : f64,
+    insertions : u64,
+    events : EventLog<T>
 }
 /*
  New type pattern to implement sorting for shared type.
  */
 
-#[derive(PartialEq, PartialOrd)]
+#[derive(PartialEq, PartialOrd, Serialize, Deserialize)]
 struct NonNan(f64);
 
 impl Eq for NonNan{}
@@ -27,10 +32,11 @@ impl Ord for NonNan{
         self.partial_cmp(other).unwrap()
     }
 }
-#[derive(Eq)]
+#[derive(Eq, Serialize, Deserialize)]
 struct SortedFileRecord<T>{
     record : FileRecord<T>,
-    h_value: NonNan
+    h_value: NonNan,
+    insertion: u64
 }
 
 impl <T> PartialEq<Self> for SortedFileRecord<T> where T : Eq {
@@ -47,50 +53,79 @@ impl <T> PartialOrd<Self> for SortedFileRecord<T> where T : Eq {
 
 impl <T> Ord for SortedFileRecord<T> where T : Eq{
     fn cmp(&self, other: &Self) -> Ordering {
-        other.h_value.cmp(&self.h_value)
+        // Ties go to the item queued first, wherever the heap happens to keep it
+        other.h_value.cmp(&self.h_value).then_with(|| other.insertion.cmp(&self.insertion))
         // other.record.size.cmp(&self.record.size)
     }
 }
 
 impl <T> GdSize<T> where T : Hash + Eq + Clone {
-    fn in_cache(&self, file : &FileRecord<T>) -> bool {
-        self.cache.contains(&file.label.clone())
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let popped = self.heap.pop().unwrap();
+            // Resized and invalidated items leave stale heap entries behind, only the latest counts
+            if let Some(&(size, current)) = self.cache.get(&popped.record.label) {
+                if current == popped.insertion {
+                    // Items left behind are aged by raising L to the H of the victim
+                    self.inflation = popped.h_value.0;
+                    self.cache.remove(&popped.record.label);
+                    self.current_used -= size;
+                    self.events.record(EventKind::Evict, &popped.record.label, size);
+                }
+            }
+        }
     }
-}
 
-impl<T> CacheAlgorithm<T> for GdSize<T> where T : Hash + Eq + Clone{
-    fn simulate(&mut self, file: FileRecord<T>) {
+    fn insert(&mut self, file: FileRecord<T>) {
         if file.size > self.size {
             println!("FILE SIZE: {}", file.size);
             panic!("File larger than cache")
         }
-        self.event_count += 1;
-        if self.in_cache(&file) {
-            self.hit_count += 1;
-            return;
-        }
-
-        let id = file.label.clone();
         self.current_used += file.size;
-        while self.current_used > self.size {
-            let popped = self.heap.pop().unwrap();
-            if popped.record.label == id {
-                panic!("Popped file we just inserted")
-            }
-            self.cache.remove(&popped.record.label.clone());
-            self.current_used -= popped.record.size;
-        }
+        self.evict();
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.push(file);
+    }
 
-        self.cache.insert(file.label.clone());
+    fn push(&mut self, file: FileRecord<T>) {
+        self.insertions += 1;
+        self.cache.insert(file.label.clone(), (file.size, self.insertions));
 
-        let h_value = self.inflation + 1.0/(file.size as f64);
+        // Objects that cost more to fetch again stay longer, every miss costs the same without a cost
+        let cost = if file.cost == 0 { 1.0 } else { file.cost as f64 };
+        let h_value = self.inflation + cost/(file.size as f64);
         let sorted = SortedFileRecord{
             record: file,
-            h_value: NonNan(h_value)
+            h_value: NonNan(h_value),
+            insertion: self.insertions
         };
         self.heap.push(sorted);
 
+        // Every hit and resize leaves a stale entry behind, dropped once they outnumber the cached items
+        if self.heap.len() > 2 * self.cache.len() + 16 {
+            let cache = &self.cache;
+            self.heap.retain(|entry| cache.get(&entry.record.label).is_some_and(|&(_, current)| current == entry.insertion));
+        }
+    }
+}
+
+impl<T> CacheAlgorithm<T> for GdSize<T> where T : Hash + Eq + Clone{
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if let Some(&(size, _)) = self.cache.get(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            // A hit restores H to the current L plus the object's cost per byte
+            self.push(FileRecord { size, ..file });
+            return true;
+        }
 
+        self.insert(file);
+        false
     }
 
 
@@ -102,11 +137,98 @@ impl<T> CacheAlgorithm<T> for GdSize<T> where T : Hash + Eq + Clone{
             size,
             event_count: 0,
             hit_count: 0,
-            inflation: 0.0
+            inflation: 0.0,
+            insertions: 0,
+            events: EventLog::new()
         }
     }
 
     fn stats(&self) -> (i32, i32) {
         (self.event_count, self.hit_count)
     }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        let old_size = match self.cache.get(&file.label) {
+            Some(&(size, _)) => size,
+            None => return false
+        };
+        // H depends on the size, so the item is queued again under its new value
+        self.current_used += file.size - old_size;
+        self.push(file.clone());
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some((size, _)) => {
+                self.current_used -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        if !self.resize(&file) {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn hits_leave_the_heap_bounded() {
+        let mut cache = GdSize::<i32>::new(100);
+        for i in 0..10000 {
+            cache.simulate(get(i % 10, 10));
+            assert!(cache.heap.len() <= 2 * cache.cache.len() + 17);
+        }
+        assert_eq!(cache.stats(), (10000, 9990));
+    }
+
+    #[test]
+    fn ties_evict_the_item_queued_first() {
+        let mut cache = GdSize::<i32>::new(30);
+        for label in [1, 2, 3, 2, 1, 4].iter() {
+            cache.simulate(get(*label, 10));
+        }
+        // Every item has the same H, 3 was queued before the hits on 2 and 1
+        assert!(!cache.contains(&3));
+        assert!(cache.contains(&1) && cache.contains(&2) && cache.contains(&4));
+    }
+
+    #[test]
+    fn small_objects_outlive_large_ones() {
+        let mut cache = GdSize::<i32>::new(30);
+        cache.simulate(get(1, 5));
+        cache.simulate(get(2, 20));
+        cache.simulate(get(3, 10));
+        assert!(cache.contains(&1) && cache.contains(&3));
+        assert!(!cache.contains(&2));
+    }
 }
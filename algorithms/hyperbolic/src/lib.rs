@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog, Params, Rng};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Objects compared per eviction, as in the paper
+const SAMPLES: usize = 64;
+const SEED: u64 = 0;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    size: i64,
+    slot: usize, // position in `labels`
+    requests: u64,
+    inserted: u64,
+}
+
+/// Hyperbolic caching (Blankstein et al.), which evicts the sampled object with the fewest requests
+/// per request since it was cached, per byte.
+///
+/// Priorities change with every request, so instead of keeping objects ordered the policy compares
+/// a random sample of them at each eviction.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Hyperbolic<T> where T : Hash + Eq {
+    labels: Vec<T>, // cached items in no particular order, to sample from
+    cache: HashMap<T, Entry>,
+    current_used : i64, // current space in cache
+    size : i64, // size of cache
+    samples: usize,
+    rng: Rng,
+    event_count: i32,
+    hit_count : i32,
+    time : u64, // reads and writes
+    events : EventLog<T>
+}
+
+impl <T> Hyperbolic<T> where T : Hash + Eq + Clone {
+    fn priority(&self, entry: &Entry) -> f64 {
+        let age = (self.time - entry.inserted + 1) as f64;
+        entry.requests as f64 / (age * entry.size.max(1) as f64)
+    }
+
+    fn remove(&mut self, label: &T) -> Option<Entry> {
+        let entry = self.cache.remove(label)?;
+        self.labels.swap_remove(entry.slot);
+        if let Some(moved) = self.labels.get(entry.slot) {
+            self.cache.get_mut(moved).unwrap().slot = entry.slot;
+        }
+        self.current_used -= entry.size;
+        Some(entry)
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let mut victim: Option<(usize, f64)> = None;
+            for _ in 0..self.samples {
+                let slot = self.rng.below(self.labels.len());
+                let priority = self.priority(&self.cache[&self.labels[slot]]);
+                if victim.is_none_or(|(_, lowest)| priority < lowest) {
+                    victim = Some((slot, priority));
+                }
+            }
+            let label = self.labels[victim.unwrap().0].clone();
+            let entry = self.remove(&label).unwrap();
+            self.events.record(EventKind::Evict, &label, entry.size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.current_used += file.size;
+        self.evict();
+        self.cache.insert(file.label.clone(), Entry {
+            size: file.size,
+            slot: self.labels.len(),
+            requests: 1,
+            inserted: self.time
+        });
+        self.labels.push(file.label);
+    }
+}
+
+impl <T> CacheAlgorithm<T> for Hyperbolic<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        self.time += 1;
+        if let Some(entry) = self.cache.get_mut(&file.label) {
+            entry.requests += 1;
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["samples", "seed"];
+
+    // samples and seed set the objects compared per eviction and the seed they are picked with
+    fn with_params(size: i64, params: &Params) -> Self {
+        Hyperbolic::<T> {
+            labels: vec![],
+            cache: HashMap::new(),
+            current_used: 0,
+            size,
+            samples: params.int("samples", SAMPLES as i64).max(1) as usize,
+            rng: Rng::new(params.int("seed", SEED as i64) as u64),
+            event_count: 0,
+            hit_count: 0,
+            time: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                self.current_used += file.size - entry.size;
+                entry.size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        self.remove(label).is_some()
+    }
+
+    // A write to a cached object counts as a request
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.time += 1;
+        if let Some(entry) = self.cache.get_mut(&file.label) {
+            entry.requests += 1;
+            self.resize(&file);
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    // Two samples per eviction, so the seed decides which objects are compared
+    fn contents(seed: u64) -> Vec<i32> {
+        let mut cache = Hyperbolic::<i32>::new(10);
+        cache.samples = 2;
+        cache.rng = Rng::new(seed);
+        for i in 0..200 {
+            cache.request(get(i * 7 % 30, 1));
+        }
+        (0..30).filter(|label| cache.contains(label)).collect()
+    }
+
+    #[test]
+    fn large_objects_are_evicted_first() {
+        let mut cache = Hyperbolic::<i32>::new(4);
+        cache.request(get(1, 1));
+        cache.request(get(2, 3));
+        // 1 is older, but 2 is requested as often for three times the bytes
+        cache.request(get(3, 1));
+        assert!(cache.contains(&1) && !cache.contains(&2));
+        assert!(cache.contains(&3));
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_match() {
+        assert_eq!(contents(1), contents(1));
+        assert_ne!(contents(1), contents(2));
+    }
+}
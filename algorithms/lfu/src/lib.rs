@@ -1,16 +1,23 @@
 use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 use priority_queue::priority_queue::PriorityQueue;
-use algorithm::CacheAlgorithm;
-use simulator_shared_types::FileRecord;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog};
+use simulator_shared_types::{EventKind, FileRecord};
 
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
 pub struct LFU<T> where T : Hash + Eq{
-    heap: PriorityQueue<FileRecord<T>,Reverse<FileSorting>>,
+    heap: PriorityQueue<T,Reverse<FileSorting>>,
+    sizes: HashMap<T, i64>,
     current_used : i64, // current space in cache
     size : i64, // size of cache
     event_count: u64,
     hit_count : i32,
+    clock : u64, // reads and writes, breaks frequency ties by recency
+    events : EventLog<T>,
 }
 /*
  New type pattern to implement sorting for shared type.
@@ -35,65 +42,129 @@ impl Ord for FileSorting {
     }
 }
 
-#[derive(Eq,PartialEq,Debug,Clone)]
+#[derive(Eq,PartialEq,Debug,Clone,Serialize,Deserialize)]
 struct FileSorting {
     freq : u64,
     last_used: u64
 }
 
+impl<T> LFU<T> where T : Hash + Eq + Clone {
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let popped = self.heap.pop().unwrap();
+            let size = self.sizes.remove(&popped.0).unwrap();
+            self.current_used -= size;
+            self.events.record(EventKind::Evict, &popped.0, size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.current_used += file.size;
+        self.evict();
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.sizes.insert(file.label.clone(), file.size);
+        self.heap.push(file.label, Reverse(FileSorting{
+            freq:  1_u64,
+            last_used: self.clock
+        }));
+    }
+}
 
 impl<T> CacheAlgorithm<T> for LFU<T> where T : Hash + Eq + Clone + Debug{
-    fn simulate(&mut self, file: FileRecord<T>) {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
         if file.size > self.size {
             println!("FILE SIZE: {}", file.size);
             panic!("File larger than cache")
         }
         self.event_count += 1;
-        let size = file.size;
+        self.clock += 1;
 
-        let id = file.label.clone();
-
-        let new_record = match self.heap.get_priority(&file){
+        let new_record = match self.heap.get_priority(&file.label){
             Some(i) => {
                 self.hit_count+=1;
+                self.events.record(EventKind::Hit, &file.label, file.size);
 
                 FileSorting{
                     freq: i.0.freq + 1_u64,
-                    last_used: self.event_count
+                    last_used: self.clock
                 }
             }
             None => {
-                self.current_used += size;
-                while self.current_used > self.size {
-                    let popped = self.heap.pop().unwrap();
-                    if popped.0.label == id {
-                        panic!("Popped file we just inserted")
-                    }
-                    //println!("POPPED: {:?}", popped);
-                    self.current_used -= popped.0.size;
-
-                }
-                FileSorting{
-                    freq:  1_u64,
-                    last_used: self.event_count
-                }
+                self.insert(file);
+                return false;
             }
         };
-        self.heap.push(file,Reverse(new_record));
+        self.heap.push(file.label,Reverse(new_record));
+        true
     }
 
 
     fn new(size: i64) -> Self {
         LFU::<T> {
-            heap: PriorityQueue::<FileRecord<T>,Reverse<FileSorting>>::new(),
+            heap: PriorityQueue::<T,Reverse<FileSorting>>::new(),
+            sizes: HashMap::new(),
             current_used: 0,
             size,
             event_count: 0,
             hit_count: 0,
+            clock: 0,
+            events: EventLog::new(),
         }
     }
 
     fn stats(&self) -> (i32, i32) {
         (self.event_count as i32, self.hit_count)
     }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.sizes.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.sizes.get_mut(&file.label) {
+            Some(size) => {
+                self.current_used += file.size - *size;
+                *size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.sizes.remove(label) {
+            Some(size) => {
+                self.heap.remove(label);
+                self.current_used -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    // Writes do not count as uses, an overwritten item keeps its frequency
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.clock += 1;
+        if !self.resize(&file) {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
 }
@@ -0,0 +1,188 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::Hash;
+use priority_queue::priority_queue::PriorityQueue;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog};
+use simulator_shared_types::{EventKind, FileRecord};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Serialize, Deserialize)]
+struct Priority {
+    key: u64, // frequency plus the cache age when last requested
+    last_used: u64, // breaks ties by recency
+}
+
+/// LFU with dynamic aging (Arlitt et al.).
+///
+/// Objects are ranked by their request count plus the age of the cache at their last request. The
+/// age becomes the rank of every evicted object, so objects that were popular long ago eventually
+/// fall behind newly requested ones.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct LFUDA<T> where T : Hash + Eq {
+    heap: PriorityQueue<T, Reverse<Priority>>,
+    cache: HashMap<T, (i64, u64)>, // size and request count of cached items
+    current_used : i64, // current space in cache
+    size : i64, // size of cache
+    age: u64,
+    event_count: u64,
+    hit_count : i32,
+    clock : u64, // reads and writes, breaks ties by recency
+    events : EventLog<T>,
+}
+
+impl<T> LFUDA<T> where T : Hash + Eq + Clone {
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let (label, Reverse(priority)) = self.heap.pop().unwrap();
+            let (size, _) = self.cache.remove(&label).unwrap();
+            self.current_used -= size;
+            self.age = priority.key;
+            self.events.record(EventKind::Evict, &label, size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.current_used += file.size;
+        self.evict();
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.cache.insert(file.label.clone(), (file.size, 1));
+        self.heap.push(file.label, Reverse(Priority {
+            key: 1 + self.age,
+            last_used: self.clock
+        }));
+    }
+}
+
+impl<T> CacheAlgorithm<T> for LFUDA<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        self.clock += 1;
+        match self.cache.get_mut(&file.label) {
+            Some((_, frequency)) => {
+                self.hit_count += 1;
+                self.events.record(EventKind::Hit, &file.label, file.size);
+                *frequency += 1;
+                let priority = Priority {
+                    key: *frequency + self.age,
+                    last_used: self.clock
+                };
+                self.heap.push(file.label, Reverse(priority));
+                true
+            }
+            None => {
+                self.insert(file);
+                false
+            }
+        }
+    }
+
+    fn new(size: i64) -> Self {
+        LFUDA::<T> {
+            heap: PriorityQueue::new(),
+            cache: HashMap::new(),
+            current_used: 0,
+            size,
+            age: 0,
+            event_count: 0,
+            hit_count: 0,
+            clock: 0,
+            events: EventLog::new(),
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        (self.event_count as i32, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some((size, _)) => {
+                self.current_used += file.size - *size;
+                *size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some((size, _)) => {
+                self.heap.remove(label);
+                self.current_used -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    // Writes do not count as uses, like in LFU
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.clock += 1;
+        if !self.resize(&file) {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn frequent_objects_survive_one_hit_objects() {
+        let mut cache = LFUDA::<i32>::new(2);
+        for _ in 0..3 {
+            cache.request(get(1, 1));
+        }
+        for label in 2..=4 {
+            cache.request(get(label, 1));
+        }
+        assert!(cache.contains(&1));
+        assert_eq!(cache.age, 2);
+    }
+
+    #[test]
+    fn aging_evicts_objects_popular_long_ago() {
+        let mut cache = LFUDA::<i32>::new(2);
+        for _ in 0..3 {
+            cache.request(get(1, 1));
+        }
+        for label in 2..=5 {
+            cache.request(get(label, 1));
+        }
+        assert!(!cache.contains(&1));
+        assert!(cache.contains(&5));
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog, Params, Rng};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Objects compared per eviction, as in the paper
+const SAMPLES: usize = 64;
+const SEED: u64 = 0;
+// Objects are classed by their hit count, up to this many classes
+const CLASSES: usize = 8;
+// Coarsened ages tracked, older objects share the last one
+const AGES: usize = 512;
+// Requests between recomputing hit densities
+const RECONFIGURE_INTERVAL: u64 = 1 << 16;
+// Share of past hits and evictions kept at each reconfiguration
+const DECAY: f64 = 0.9;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    size: i64,
+    slot: usize, // position in `labels`
+    hits: u64,
+    last: u64, // time of the last request
+}
+
+// Hits and evictions at each age of the objects of one class, and the hit density they give
+#[derive(Serialize, Deserialize)]
+struct Class {
+    hits: Vec<f64>,
+    evictions: Vec<f64>,
+    density: Vec<f64>,
+}
+
+impl Class {
+    fn new() -> Self {
+        Class {
+            hits: vec![0.0; AGES],
+            evictions: vec![0.0; AGES],
+            // Younger objects are worth more until there is data
+            density: (0..AGES).map(|age| 1.0 / (age + 1) as f64).collect(),
+        }
+    }
+
+    // Expected hits over expected remaining lifetime, for objects that reached each age
+    fn reconfigure(&mut self) {
+        let (mut hits, mut events, mut lifetime) = (0.0, 0.0, 0.0);
+        for age in (0..AGES).rev() {
+            hits += self.hits[age];
+            events += self.hits[age] + self.evictions[age];
+            lifetime += events;
+            self.density[age] = if lifetime > 1e-5 { hits / lifetime } else { 0.0 };
+        }
+        for age in 0..AGES {
+            self.hits[age] *= DECAY;
+            self.evictions[age] *= DECAY;
+        }
+    }
+
+    // Merges pairs of ages, for twice the coarsening. The last age still holds the older objects.
+    fn coarsen(&mut self) {
+        for counts in [&mut self.hits, &mut self.evictions].iter_mut() {
+            for age in 0..AGES / 2 {
+                counts[age] = counts[2 * age] + if 2 * age + 1 < AGES - 1 { counts[2 * age + 1] } else { 0.0 };
+            }
+            for age in AGES / 2..AGES - 1 {
+                counts[age] = 0.0;
+            }
+        }
+    }
+}
+
+/// Least Hit Density (Beckmann et al.), which evicts the sampled object expected to bring the
+/// fewest hits per byte and per request it stays cached.
+///
+/// Hit densities are learned from how many objects of each class get hit or evicted at each age,
+/// recomputed every `RECONFIGURE_INTERVAL` requests. Ages are coarsened until few objects outlive
+/// the tracked ages.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Lhd<T> where T : Hash + Eq {
+    labels: Vec<T>, // cached items in no particular order, to sample from
+    cache: HashMap<T, Entry>,
+    classes: Vec<Class>,
+    coarsening: u64, // requests per tracked age
+    current_used : i64, // current space in cache
+    size : i64, // size of cache
+    samples: usize,
+    rng: Rng,
+    event_count: i32,
+    hit_count : i32,
+    time : u64, // reads and writes
+    events : EventLog<T>
+}
+
+impl <T> Lhd<T> where T : Hash + Eq + Clone {
+    fn class_of(entry: &Entry) -> usize {
+        (entry.hits as usize).min(CLASSES - 1)
+    }
+
+    fn age_of(&self, entry: &Entry) -> usize {
+        (((self.time - entry.last) / self.coarsening) as usize).min(AGES - 1)
+    }
+
+    fn density(&self, entry: &Entry) -> f64 {
+        self.classes[Self::class_of(entry)].density[self.age_of(entry)] / entry.size.max(1) as f64
+    }
+
+    fn remove(&mut self, label: &T) -> Option<Entry> {
+        let entry = self.cache.remove(label)?;
+        self.labels.swap_remove(entry.slot);
+        if let Some(moved) = self.labels.get(entry.slot) {
+            self.cache.get_mut(moved).unwrap().slot = entry.slot;
+        }
+        self.current_used -= entry.size;
+        Some(entry)
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let mut victim: Option<(usize, f64)> = None;
+            for _ in 0..self.samples {
+                let slot = self.rng.below(self.labels.len());
+                let density = self.density(&self.cache[&self.labels[slot]]);
+                if victim.is_none_or(|(_, lowest)| density < lowest) {
+                    victim = Some((slot, density));
+                }
+            }
+            let label = self.labels[victim.unwrap().0].clone();
+            let age = self.age_of(&self.cache[&label]);
+            let entry = self.remove(&label).unwrap();
+            self.classes[Self::class_of(&entry)].evictions[age] += 1.0;
+            self.events.record(EventKind::Evict, &label, entry.size);
+        }
+    }
+
+    fn hit(&mut self, label: &T) {
+        let age = self.age_of(&self.cache[label]);
+        let time = self.time;
+        let entry = self.cache.get_mut(label).unwrap();
+        self.classes[Self::class_of(entry)].hits[age] += 1.0;
+        entry.hits += 1;
+        entry.last = time;
+    }
+
+    fn tick(&mut self) {
+        self.time += 1;
+        if !self.time.is_multiple_of(RECONFIGURE_INTERVAL) {
+            return;
+        }
+        // Coarser ages when more than 1% of hits and evictions happen past the tracked ones
+        let (mut last, mut total) = (0.0, 0.0);
+        for class in self.classes.iter() {
+            last += class.hits[AGES - 1] + class.evictions[AGES - 1];
+            total += class.hits.iter().sum::<f64>() + class.evictions.iter().sum::<f64>();
+        }
+        if last > total / 100.0 {
+            self.coarsening *= 2;
+            self.classes.iter_mut().for_each(Class::coarsen);
+        }
+        self.classes.iter_mut().for_each(Class::reconfigure);
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.current_used += file.size;
+        self.evict();
+        self.cache.insert(file.label.clone(), Entry {
+            size: file.size,
+            slot: self.labels.len(),
+            hits: 0,
+            last: self.time
+        });
+        self.labels.push(file.label);
+    }
+}
+
+impl <T> CacheAlgorithm<T> for Lhd<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        self.tick();
+        if self.cache.contains_key(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.hit(&file.label);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["samples", "seed"];
+
+    // samples sets the eviction sample size, seed the generator drawing it
+    fn with_params(size: i64, params: &Params) -> Self {
+        Lhd::<T> {
+            labels: vec![],
+            cache: HashMap::new(),
+            classes: (0..CLASSES).map(|_| Class::new()).collect(),
+            coarsening: 1,
+            current_used: 0,
+            size,
+            samples: params.int("samples", SAMPLES as i64).max(1) as usize,
+            rng: Rng::new(params.int("seed", SEED as i64) as u64),
+            event_count: 0,
+            hit_count: 0,
+            time: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                self.current_used += file.size - entry.size;
+                entry.size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        self.remove(label).is_some()
+    }
+
+    // A write to a cached object counts as a hit
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.tick();
+        if self.cache.contains_key(&file.label) {
+            self.hit(&file.label);
+            self.resize(&file);
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    // Two samples per eviction, so the seed decides which objects are compared
+    fn contents(seed: u64) -> Vec<i32> {
+        let mut cache = Lhd::<i32>::new(10);
+        cache.samples = 2;
+        cache.rng = Rng::new(seed);
+        for i in 0..200 {
+            cache.request(get(i * 7 % 30, 1));
+        }
+        (0..30).filter(|label| cache.contains(label)).collect()
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_match() {
+        assert_eq!(contents(1), contents(1));
+        assert_ne!(contents(1), contents(2));
+    }
+}
@@ -0,0 +1,311 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::Hash;
+use priority_queue::priority_queue::PriorityQueue;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog, Params};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Share of the cache holding HIR blocks, as in the paper
+const HIR_RATIO: f64 = 0.01;
+
+#[derive(Serialize, Deserialize)]
+struct Block {
+    size: i64,
+    lir: bool,
+    resident: bool, // non-resident blocks are HIR blocks kept in the stack after eviction
+}
+
+/// LIRS (Jiang and Zhang), which ranks objects by reuse distance rather than recency.
+///
+/// Objects with a short reuse distance are LIR and hold most of the cache. The rest are HIR and
+/// cycle through a small queue, so a scan only ever displaces HIR objects. The recency stack keeps
+/// HIR objects, including recently evicted ones, that were requested after its oldest LIR object.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Lirs<T> where T : Hash + Eq {
+    stack: PriorityQueue<T, Reverse<u64>>, // S, oldest on top
+    queue: PriorityQueue<T, Reverse<u64>>, // resident HIR blocks, next to evict on top
+    ghosts: PriorityQueue<T, Reverse<u64>>, // non-resident blocks in S, oldest on top
+    blocks: HashMap<T, Block>,
+    lir_used: i64,
+    hir_used: i64,
+    ghost_used: i64, // sizes of the non-resident blocks
+    size : i64, // size of cache
+    lir_size: i64,
+    event_count: u64,
+    hit_count : i32,
+    clock : u64, // reads and writes, used as recency
+    events : EventLog<T>,
+}
+
+impl<T> Lirs<T> where T : Hash + Eq + Clone {
+    // Drops HIR blocks from the bottom of the stack, they have no LIR block older than them left
+    fn prune(&mut self) {
+        while let Some((label, _)) = self.stack.peek() {
+            if self.blocks[label].lir {
+                break;
+            }
+            let (label, _) = self.stack.pop().unwrap();
+            if !self.blocks[&label].resident {
+                self.forget(&label);
+            }
+        }
+    }
+
+    fn forget(&mut self, label: &T) {
+        let block = self.blocks.remove(label).unwrap();
+        self.ghosts.remove(label);
+        self.stack.remove(label);
+        self.ghost_used -= block.size;
+    }
+
+    // The oldest LIR block becomes a resident HIR block
+    fn demote(&mut self) {
+        self.prune();
+        let (label, _) = self.stack.pop().unwrap();
+        let block = self.blocks.get_mut(&label).unwrap();
+        block.lir = false;
+        self.lir_used -= block.size;
+        self.hir_used += block.size;
+        self.clock += 1;
+        self.queue.push(label, Reverse(self.clock));
+        self.prune();
+    }
+
+    fn evict(&mut self) {
+        let (label, _) = self.queue.pop().unwrap();
+        let block = self.blocks.get_mut(&label).unwrap();
+        block.resident = false;
+        let size = block.size;
+        self.hir_used -= size;
+        self.events.record(EventKind::Evict, &label, size);
+
+        match self.stack.get_priority(&label).copied() {
+            Some(recency) => {
+                self.ghost_used += size;
+                self.ghosts.push(label, recency);
+                // Non-resident blocks are remembered for as many bytes as the cache holds
+                while self.ghost_used > self.size {
+                    let (label, _) = self.ghosts.peek().unwrap();
+                    let label = label.clone();
+                    self.forget(&label);
+                }
+                self.prune();
+            }
+            None => { self.blocks.remove(&label); }
+        }
+    }
+
+    // Frees space for `extra` more bytes, demoting LIR blocks when no HIR block is left to evict
+    fn make_room(&mut self, extra: i64) {
+        while self.lir_used + self.hir_used + extra > self.size {
+            if self.queue.is_empty() {
+                self.demote();
+            } else {
+                self.evict();
+            }
+        }
+    }
+
+    fn balance(&mut self) {
+        while self.lir_used > self.lir_size {
+            self.demote();
+        }
+        self.make_room(0);
+    }
+
+    // Applies a request to the object without counting it. Returns whether it was cached.
+    fn access(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.clock += 1;
+        let recency = Reverse(self.clock);
+        let in_stack = self.stack.get(&file.label).is_some();
+
+        match self.blocks.get_mut(&file.label) {
+            Some(block) if block.resident && block.lir => {
+                self.stack.push(file.label, recency);
+                self.prune();
+                return true;
+            }
+            Some(block) if block.resident => {
+                // A HIR block requested while still in the stack has a shorter reuse distance than the oldest LIR block
+                if in_stack {
+                    block.lir = true;
+                    self.hir_used -= block.size;
+                    self.lir_used += block.size;
+                    self.queue.remove(&file.label);
+                    self.stack.push(file.label, recency);
+                    self.balance();
+                } else {
+                    self.stack.push(file.label.clone(), recency);
+                    self.queue.push(file.label, recency);
+                }
+                return true;
+            }
+            _ => {}
+        }
+
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.make_room(file.size);
+        // Making room may have forgotten a non-resident block
+        match self.blocks.get_mut(&file.label) {
+            Some(block) => {
+                self.ghost_used -= block.size;
+                block.resident = true;
+                block.lir = true;
+                block.size = file.size;
+                self.ghosts.remove(&file.label);
+                self.lir_used += file.size;
+                self.stack.push(file.label, recency);
+            }
+            None => {
+                // LIR blocks fill the cache first
+                let lir = self.lir_used + file.size <= self.lir_size;
+                if lir {
+                    self.lir_used += file.size;
+                } else {
+                    self.hir_used += file.size;
+                    self.queue.push(file.label.clone(), recency);
+                }
+                self.blocks.insert(file.label.clone(), Block { size: file.size, lir, resident: true });
+                self.stack.push(file.label, recency);
+            }
+        }
+        self.balance();
+        false
+    }
+}
+
+impl<T> CacheAlgorithm<T> for Lirs<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        self.event_count += 1;
+        if self.contains(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+        }
+        self.access(file)
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["hir_ratio"];
+
+    fn check_params(params: &Params) -> Result<(), String> {
+        params.check(Self::PARAMS)?;
+        params.check_range("hir_ratio", 0.0..=1.0)
+    }
+
+    // hir_ratio sets the share of HIR blocks
+    fn with_params(size: i64, params: &Params) -> Self {
+        Lirs::<T> {
+            stack: PriorityQueue::new(),
+            queue: PriorityQueue::new(),
+            ghosts: PriorityQueue::new(),
+            blocks: HashMap::new(),
+            lir_used: 0,
+            hir_used: 0,
+            ghost_used: 0,
+            size,
+            lir_size: size - (size as f64 * params.float("hir_ratio", HIR_RATIO)) as i64,
+            event_count: 0,
+            hit_count: 0,
+            clock: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        (self.event_count as i32, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.lir_used + self.hir_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.blocks.get(label).is_some_and(|block| block.resident)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.blocks.get_mut(&file.label) {
+            Some(block) if block.resident => {
+                let change = file.size - block.size;
+                block.size = file.size;
+                if block.lir {
+                    self.lir_used += change;
+                } else {
+                    self.hir_used += change;
+                }
+            }
+            _ => return false
+        }
+        self.balance();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        if !self.contains(label) {
+            return false;
+        }
+        let block = self.blocks.remove(label).unwrap();
+        if block.lir {
+            self.lir_used -= block.size;
+        } else {
+            self.hir_used -= block.size;
+        }
+        self.stack.remove(label);
+        self.queue.remove(label);
+        self.prune();
+        true
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.resize(&file);
+        self.access(file);
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn hot_objects_survive_a_scan() {
+        let mut cache = Lirs::<i32>::new(100);
+        let mut cold = 1000;
+        for _ in 0..10 {
+            for label in 0..20 {
+                cache.request(get(label, 1));
+            }
+            for _ in 0..40 {
+                cache.request(get(cold, 1));
+                cold += 1;
+            }
+        }
+        for _ in 0..1000 {
+            cache.request(get(cold, 1));
+            cold += 1;
+        }
+        assert!((0..20).all(|label| cache.contains(&label)));
+        assert!(cache.used() <= 100);
+    }
+}
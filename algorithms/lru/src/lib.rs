@@ -1,55 +1,129 @@
 use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::hash::Hash;
 use priority_queue::priority_queue::PriorityQueue;
-use algorithm::CacheAlgorithm;
-use simulator_shared_types::FileRecord;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog};
+use simulator_shared_types::{EventKind, FileRecord};
 
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
 pub struct LRU<T> where T : Hash + Eq {
-    heap: PriorityQueue<FileRecord<T>, Reverse<u64>>,
+    heap: PriorityQueue<T, Reverse<u64>>,
+    sizes: HashMap<T, i64>,
     current_used : i64, // current space in cache
     size : i64, // size of cache
     event_count:u64,
     hit_count : i32,
+    clock : u64, // reads and writes, used as recency
+    events : EventLog<T>,
 }
 
-impl<T> CacheAlgorithm<T> for LRU<T> where T : Hash + Eq + Clone{
-    fn simulate(&mut self, file: FileRecord<T>) {
+impl<T> LRU<T> where T : Hash + Eq + Clone {
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let popped = self.heap.pop().unwrap();
+            let size = self.sizes.remove(&popped.0).unwrap();
+            self.current_used -= size;
+            self.events.record(EventKind::Evict, &popped.0, size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
         if file.size > self.size {
             println!("FILE SIZE: {}", file.size);
             panic!("File larger than cache")
         }
-        self.event_count += 1;
-        if let Some(_) = self.heap.change_priority(&file, Reverse(self.event_count)) {
-            self.hit_count += 1;
-            return;
-        }
-        let id = file.label.clone();
         self.current_used += file.size;
+        self.evict();
 
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.sizes.insert(file.label.clone(), file.size);
+        self.heap.push(file.label, Reverse(self.clock)); // use clock as recency
+    }
+}
 
-        while self.current_used > self.size {
-            let popped = self.heap.pop().unwrap();
-            if popped.0.label == id {
-                panic!("Popped file we just inserted")
-            }
-            self.current_used -= popped.0.size;
+impl<T> CacheAlgorithm<T> for LRU<T> where T : Hash + Eq + Clone{
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
         }
-
-        self.heap.push(file, Reverse(self.event_count)); // use event count as
+        self.event_count += 1;
+        self.clock += 1;
+        if self.heap.change_priority(&file.label, Reverse(self.clock)).is_some() {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            return true;
+        }
+        self.insert(file);
+        false
     }
 
 
     fn new(size: i64) -> Self {
         LRU::<T> {
-            heap: PriorityQueue::<FileRecord<T>, Reverse<u64>>::new(),
+            heap: PriorityQueue::<T, Reverse<u64>>::new(),
+            sizes: HashMap::new(),
             current_used: 0,
             size,
             event_count: 0,
-            hit_count: 0
+            hit_count: 0,
+            clock: 0,
+            events: EventLog::new()
         }
     }
 
     fn stats(&self) -> (i32, i32) {
         (self.event_count as i32, self.hit_count)
     }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.sizes.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.sizes.get_mut(&file.label) {
+            Some(size) => {
+                self.current_used += file.size - *size;
+                *size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.sizes.remove(label) {
+            Some(size) => {
+                self.heap.remove(label);
+                self.current_used -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.clock += 1;
+        if self.resize(&file) {
+            self.heap.change_priority(&file.label, Reverse(self.clock));
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
 }
@@ -0,0 +1,296 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use priority_queue::priority_queue::PriorityQueue;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog, Params};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Number of references looked back at, LRU-2 as in most of the paper
+const K: usize = 2;
+// Requests after a reference during which further references to the object are correlated
+const CORRELATED_PERIOD: u64 = 0;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    size: i64,
+    history: Vec<u64>, // times of the last K uncorrelated references, newest first, 0 when missing
+    last: u64, // time of the last reference, correlated or not
+}
+
+/// LRU-K (O'Neil et al.), which evicts the object whose K-th most recent reference is the oldest.
+///
+/// Objects referenced fewer than K times go first, least recent first. References within the
+/// correlated period of the previous one only extend it. Evicted objects keep their history for
+/// a while, so an object that returns soon is not treated as new.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct LRUK<T> where T : Hash + Eq {
+    heap: PriorityQueue<T, Reverse<(u64, u64)>>, // K-th and most recent uncorrelated reference
+    cache: HashMap<T, Entry>,
+    retained: VecDeque<(T, u64)>, // newest at the front, stale entries of returned items stay behind
+    histories: HashMap<T, (i64, Vec<u64>, u64)>, // size, history and retention number of evicted items
+    current_used : i64, // current space in cache
+    retained_used: i64, // sizes of the objects with a retained history
+    size : i64, // size of cache
+    k: usize,
+    correlated_period: u64,
+    event_count: u64,
+    hit_count : i32,
+    time : u64, // reads and writes
+    retentions : u64,
+    events : EventLog<T>,
+}
+
+impl<T> LRUK<T> where T : Hash + Eq + Clone {
+    fn priority(&self, history: &[u64]) -> Reverse<(u64, u64)> {
+        Reverse((history[self.k - 1], history[0]))
+    }
+
+    fn reference(&mut self, label: &T) {
+        let time = self.time;
+        let period = self.correlated_period;
+        let entry = self.cache.get_mut(label).unwrap();
+        if time - entry.last > period {
+            // Shift the history by the length of the correlated period that just ended
+            let correlated = entry.last - entry.history[0];
+            for i in (1..entry.history.len()).rev() {
+                entry.history[i] = if entry.history[i - 1] == 0 { 0 } else { entry.history[i - 1] + correlated };
+            }
+            entry.history[0] = time;
+            let priority = Reverse((entry.history[self.k - 1], entry.history[0]));
+            self.heap.change_priority(label, priority);
+        }
+        self.cache.get_mut(label).unwrap().last = time;
+    }
+
+    // The oldest K-th reference among objects past their correlated period, any object when none is
+    fn victim(&mut self) -> T {
+        let mut held = vec![];
+        let victim = loop {
+            match self.heap.pop() {
+                Some((label, priority)) => {
+                    if self.time - self.cache[&label].last > self.correlated_period {
+                        break label;
+                    }
+                    held.push((label, priority));
+                }
+                None => break held.remove(0).0
+            }
+        };
+        for (label, priority) in held {
+            self.heap.push(label, priority);
+        }
+        victim
+    }
+
+    fn retain(&mut self, label: T, size: i64, history: Vec<u64>) {
+        self.retentions += 1;
+        self.retained_used += size;
+        self.histories.insert(label.clone(), (size, history, self.retentions));
+        self.retained.push_front((label, self.retentions));
+        // Histories are retained for about as many objects as the cache holds
+        while self.retained_used > self.size {
+            let (label, retention) = self.retained.pop_back().unwrap();
+            let current = match self.histories.get(&label) {
+                Some(&(_, _, current)) => current,
+                None => continue
+            };
+            if current == retention {
+                let (size, _, _) = self.histories.remove(&label).unwrap();
+                self.retained_used -= size;
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let label = self.victim();
+            let entry = self.cache.remove(&label).unwrap();
+            self.current_used -= entry.size;
+            self.events.record(EventKind::Evict, &label, entry.size);
+            self.retain(label, entry.size, entry.history);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.current_used += file.size;
+        self.evict();
+        self.events.record(EventKind::Insert, &file.label, file.size);
+
+        let mut history = match self.histories.remove(&file.label) {
+            Some((size, history, _)) => {
+                self.retained_used -= size;
+                history
+            }
+            None => vec![0; self.k]
+        };
+        history.rotate_right(1);
+        history[0] = self.time;
+        let priority = self.priority(&history);
+        self.cache.insert(file.label.clone(), Entry { size: file.size, history, last: self.time });
+        self.heap.push(file.label, priority);
+    }
+}
+
+impl<T> CacheAlgorithm<T> for LRUK<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        self.time += 1;
+        if self.cache.contains_key(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.reference(&file.label);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["k", "correlated_period"];
+
+    // k and correlated_period set the references looked back at and the correlated period
+    fn with_params(size: i64, params: &Params) -> Self {
+        LRUK::<T> {
+            heap: PriorityQueue::new(),
+            cache: HashMap::new(),
+            retained: VecDeque::new(),
+            histories: HashMap::new(),
+            current_used: 0,
+            retained_used: 0,
+            size,
+            k: params.int("k", K as i64).max(1) as usize,
+            correlated_period: params.int("correlated_period", CORRELATED_PERIOD as i64).max(0) as u64,
+            event_count: 0,
+            hit_count: 0,
+            time: 0,
+            retentions: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        (self.event_count as i32, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                self.current_used += file.size - entry.size;
+                entry.size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some(entry) => {
+                self.heap.remove(label);
+                self.current_used -= entry.size;
+                true
+            }
+            None => false
+        }
+    }
+
+    // A write to a cached object counts as a reference
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.time += 1;
+        if self.resize(&file) {
+            if self.cache.contains_key(&file.label) {
+                self.reference(&file.label);
+            }
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn objects_with_fewer_than_k_references_go_first() {
+        let mut cache = LRUK::<i32>::new(3);
+        for &label in &[1, 1, 2, 2, 3, 4] {
+            cache.request(get(label, 1));
+        }
+        assert!(cache.contains(&1));
+        assert!(cache.contains(&2));
+        assert!(!cache.contains(&3));
+    }
+
+    #[test]
+    fn the_oldest_kth_reference_goes_first() {
+        let mut cache = LRUK::<i32>::new(3);
+        for &label in &[1, 2, 3, 3, 2, 1, 4] {
+            cache.request(get(label, 1));
+        }
+        assert!(!cache.contains(&1));
+        assert!(cache.contains(&2));
+        assert!(cache.contains(&3));
+    }
+
+    #[test]
+    fn k_of_one_is_lru() {
+        let mut cache = LRUK::<i32>::new(3);
+        cache.k = 1;
+        for &label in &[1, 1, 2, 2, 3, 4] {
+            cache.request(get(label, 1));
+        }
+        assert!(!cache.contains(&1));
+        assert!(cache.contains(&3));
+    }
+
+    #[test]
+    fn correlated_references_do_not_extend_the_history() {
+        let mut cache = LRUK::<i32>::new(3);
+        cache.request(get(1, 1));
+        cache.request(get(1, 1));
+        assert_eq!(cache.cache[&1].history, vec![2, 1]);
+
+        let mut cache = LRUK::<i32>::new(3);
+        cache.correlated_period = 1;
+        cache.request(get(1, 1));
+        cache.request(get(1, 1));
+        assert_eq!(cache.cache[&1].history, vec![1, 0]);
+        assert_eq!(cache.cache[&1].last, 2);
+    }
+}
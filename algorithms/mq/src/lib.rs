@@ -0,0 +1,269 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use priority_queue::priority_queue::PriorityQueue;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog, Params};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Number of LRU queues, as in the paper
+const QUEUES: usize = 8;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    size: i64,
+    frequency: u64,
+    queue: usize,
+    expire: u64, // request after which the object drops to the queue below
+}
+
+/// Multi-Queue (Zhou et al.), LRU queues for exponentially growing request counts.
+///
+/// Objects requested `f` times live in queue `log2(f)`, and drop one queue when they go unrequested
+/// for longer than a lifetime. Evictions come from the least recent object of the lowest queue,
+/// and the ghost queue keeps evicted objects' request counts in case they return. Meant for
+/// second level caches, whose requests are the misses of a cache in front.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct MQ<T> where T : Hash + Eq {
+    queues: Vec<PriorityQueue<T, Reverse<u64>>>,
+    cache: HashMap<T, Entry>,
+    out: VecDeque<(T, u64)>, // newest at the front, stale entries of returned items stay behind
+    ghosts: HashMap<T, (i64, u64, u64)>, // size, request count and insertion number of keys in `out`
+    current_used : i64, // current space in cache
+    ghost_used: i64, // sizes of the objects the ghost keys stand for
+    size : i64, // size of cache
+    event_count: u64,
+    hit_count : i32,
+    time : u64, // reads and writes
+    clock : u64, // queue entries, used as recency and insertion numbers
+    events : EventLog<T>,
+}
+
+impl<T> MQ<T> where T : Hash + Eq + Clone {
+    fn queue_of(&self, frequency: u64) -> usize {
+        ((63 - frequency.leading_zeros()) as usize).min(self.queues.len() - 1)
+    }
+
+    // About one turnover of the cache, the paper's suggestion when the trace is not known in advance
+    fn lifetime(&self) -> u64 {
+        self.cache.len() as u64
+    }
+
+    fn place(&mut self, label: T, size: i64, frequency: u64) {
+        let queue = self.queue_of(frequency);
+        let expire = self.time + self.lifetime();
+        self.clock += 1;
+        self.queues[queue].push(label.clone(), Reverse(self.clock));
+        self.cache.insert(label, Entry { size, frequency, queue, expire });
+    }
+
+    fn touch(&mut self, label: &T) {
+        self.time += 1;
+        let entry = self.cache.remove(label).unwrap();
+        self.queues[entry.queue].remove(label);
+        self.place(label.clone(), entry.size, entry.frequency + 1);
+        self.adjust();
+    }
+
+    // Objects that outlived their lifetime at the bottom of each queue drop one queue
+    fn adjust(&mut self) {
+        for queue in 1..self.queues.len() {
+            let expired = match self.queues[queue].peek() {
+                Some((label, _)) => self.cache[label].expire < self.time,
+                None => false
+            };
+            if expired {
+                let (label, _) = self.queues[queue].pop().unwrap();
+                let expire = self.time + self.lifetime();
+                let entry = self.cache.get_mut(&label).unwrap();
+                entry.queue = queue - 1;
+                entry.expire = expire;
+                self.clock += 1;
+                self.queues[queue - 1].push(label, Reverse(self.clock));
+            }
+        }
+    }
+
+    fn remember(&mut self, label: T, size: i64, frequency: u64) {
+        self.clock += 1;
+        self.ghost_used += size;
+        self.ghosts.insert(label.clone(), (size, frequency, self.clock));
+        self.out.push_front((label, self.clock));
+        // The ghost queue remembers about as much as the cache holds
+        while self.ghost_used > self.size {
+            let (label, insertion) = self.out.pop_back().unwrap();
+            if let Some(&(size, _, current)) = self.ghosts.get(&label) {
+                if current == insertion {
+                    self.ghosts.remove(&label);
+                    self.ghost_used -= size;
+                }
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let queue = self.queues.iter().position(|queue| !queue.is_empty()).unwrap();
+            let (label, _) = self.queues[queue].pop().unwrap();
+            let entry = self.cache.remove(&label).unwrap();
+            self.current_used -= entry.size;
+            self.events.record(EventKind::Evict, &label, entry.size);
+            self.remember(label, entry.size, entry.frequency);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.time += 1;
+        let frequency = match self.ghosts.remove(&file.label) {
+            Some((size, frequency, _)) => {
+                self.ghost_used -= size;
+                frequency + 1
+            }
+            None => 1
+        };
+        self.current_used += file.size;
+        self.evict();
+        self.place(file.label, file.size, frequency);
+        self.adjust();
+    }
+}
+
+impl<T> CacheAlgorithm<T> for MQ<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if self.cache.contains_key(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.touch(&file.label);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["queues"];
+
+    // queues sets the number of LRU queues
+    fn with_params(size: i64, params: &Params) -> Self {
+        MQ::<T> {
+            queues: (0..params.int("queues", QUEUES as i64).max(1)).map(|_| PriorityQueue::new()).collect(),
+            cache: HashMap::new(),
+            out: VecDeque::new(),
+            ghosts: HashMap::new(),
+            current_used: 0,
+            ghost_used: 0,
+            size,
+            event_count: 0,
+            hit_count: 0,
+            time: 0,
+            clock: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        (self.event_count as i32, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                self.current_used += file.size - entry.size;
+                entry.size = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some(entry) => {
+                self.queues[entry.queue].remove(label);
+                self.current_used -= entry.size;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        if self.resize(&file) {
+            if self.cache.contains_key(&file.label) {
+                self.touch(&file.label);
+            }
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn frequent_objects_outlive_the_lowest_queue() {
+        let mut cache = MQ::<i32>::new(3);
+        for _ in 0..4 {
+            cache.request(get(1, 1));
+        }
+        for label in 2..=4 {
+            cache.request(get(label, 1));
+        }
+        assert!(cache.contains(&1));
+        assert!(!cache.contains(&2));
+        assert!(cache.contains(&3));
+        assert!(cache.contains(&4));
+    }
+
+    #[test]
+    fn returning_objects_keep_their_request_count() {
+        let mut cache = MQ::<i32>::new(3);
+        for label in 1..=4 {
+            cache.request(get(label, 1));
+        }
+        assert!(!cache.contains(&1));
+        assert!(cache.ghosts.contains_key(&1));
+        cache.request(get(1, 1));
+        assert_eq!(cache.cache[&1].frequency, 2);
+        assert_eq!(cache.cache[&1].queue, 1);
+        assert!(!cache.ghosts.contains_key(&1));
+    }
+}
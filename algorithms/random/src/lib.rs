@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog, Params, Rng};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+const SEED: u64 = 0;
+
+/// Evicts cached objects picked uniformly at random, from a seeded generator so runs are repeatable.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Random<T> where T : Hash + Eq {
+    labels: Vec<T>, // cached items in no particular order, to pick from
+    cache: HashMap<T, (i64, usize)>, // size and position in `labels` of cached items
+    current_used : i64, // current space in cache
+    size : i64, // size of cache
+    rng: Rng,
+    event_count: i32,
+    hit_count : i32,
+    events : EventLog<T>
+}
+
+impl <T> Random<T> where T : Hash + Eq + Clone {
+    fn remove(&mut self, label: &T) -> Option<i64> {
+        let (size, slot) = self.cache.remove(label)?;
+        self.labels.swap_remove(slot);
+        if let Some(moved) = self.labels.get(slot) {
+            self.cache.get_mut(moved).unwrap().1 = slot;
+        }
+        self.current_used -= size;
+        Some(size)
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let slot = self.rng.below(self.labels.len());
+            let label = self.labels[slot].clone();
+            let size = self.remove(&label).unwrap();
+            self.events.record(EventKind::Evict, &label, size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.current_used += file.size;
+        self.evict();
+        self.cache.insert(file.label.clone(), (file.size, self.labels.len()));
+        self.labels.push(file.label);
+    }
+}
+
+impl <T> CacheAlgorithm<T> for Random<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if self.cache.contains_key(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["seed"];
+
+    // seed sets the seed victims are picked with
+    fn with_params(size: i64, params: &Params) -> Self {
+        Random::<T> {
+            labels: vec![],
+            cache: HashMap::new(),
+            current_used: 0,
+            size,
+            rng: Rng::new(params.int("seed", SEED as i64) as u64),
+            event_count: 0,
+            hit_count: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                self.current_used += file.size - entry.0;
+                entry.0 = file.size;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        self.remove(label).is_some()
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        if !self.resize(&file) {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    fn contents(seed: u64) -> Vec<i32> {
+        let mut cache = Random::<i32>::new(10);
+        cache.rng = Rng::new(seed);
+        for i in 0..200 {
+            cache.request(get(i * 7 % 30, 1));
+        }
+        (0..30).filter(|label| cache.contains(label)).collect()
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_match() {
+        assert_eq!(contents(1), contents(1));
+        assert_ne!(contents(1), contents(2));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog, Params};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Share of the cache given to the small queue, as in the paper
+const SMALL_RATIO: f64 = 0.1;
+// Frequencies saturate here, two bits per object
+const MAX_FREQ: u8 = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum Queue {
+    Small,
+    Main,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    size: i64,
+    freq: u8,
+    queue: Queue,
+    insertion: u64, // matches the object's latest entry in its queue
+}
+
+/// S3-FIFO (Yang et al.), three FIFO queues.
+///
+/// New objects enter a small queue, and only those requested again while in it move on to the
+/// main queue. The rest leave their key in a ghost queue, so an object that returns soon after
+/// goes straight to main. Main reinserts objects that were requested since they last reached its tail.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct S3Fifo<T> where T : Hash + Eq {
+    small: VecDeque<(T, u64)>, // newest at the front, stale entries of moved and invalidated items stay behind
+    main: VecDeque<(T, u64)>,
+    ghost: VecDeque<(T, u64)>,
+    cache: HashMap<T, Entry>,
+    ghosts: HashMap<T, (i64, u64)>, // size and insertion number of keys in the ghost queue
+    small_used: i64,
+    main_used: i64,
+    ghost_used: i64, // sizes of the objects the ghost keys stand for
+    size : i64, // size of cache
+    small_size: i64,
+    event_count: i32,
+    hit_count : i32,
+    insertions : u64,
+    events : EventLog<T>
+}
+
+impl <T> S3Fifo<T> where T : Hash + Eq + Clone {
+    fn queue(&mut self, queue: Queue) -> &mut VecDeque<(T, u64)> {
+        match queue {
+            Queue::Small => &mut self.small,
+            Queue::Main => &mut self.main,
+        }
+    }
+
+    fn used_of(&mut self, queue: Queue) -> &mut i64 {
+        match queue {
+            Queue::Small => &mut self.small_used,
+            Queue::Main => &mut self.main_used,
+        }
+    }
+
+    fn push(&mut self, label: T, size: i64, freq: u8, queue: Queue) {
+        self.insertions += 1;
+        let insertion = self.insertions;
+        *self.used_of(queue) += size;
+        self.cache.insert(label.clone(), Entry { size, freq, queue, insertion });
+        self.queue(queue).push_front((label, insertion));
+    }
+
+    // Oldest live entry of a queue, skipping the stale ones
+    fn pop(&mut self, queue: Queue) -> (T, Entry) {
+        loop {
+            let (label, insertion) = self.queue(queue).pop_back().unwrap();
+            let live = match self.cache.get(&label) {
+                Some(entry) => entry.queue == queue && entry.insertion == insertion,
+                None => false
+            };
+            if live {
+                let entry = self.cache.remove(&label).unwrap();
+                *self.used_of(queue) -= entry.size;
+                return (label, entry);
+            }
+        }
+    }
+
+    fn remember(&mut self, label: T, size: i64) {
+        self.insertions += 1;
+        self.ghost_used += size;
+        self.ghosts.insert(label.clone(), (size, self.insertions));
+        self.ghost.push_front((label, self.insertions));
+        // The ghost queue remembers about as much as main holds
+        while self.ghost_used > self.size - self.small_size {
+            let (label, insertion) = self.ghost.pop_back().unwrap();
+            if let Some(&(size, current)) = self.ghosts.get(&label) {
+                if current == insertion {
+                    self.ghosts.remove(&label);
+                    self.ghost_used -= size;
+                }
+            }
+        }
+    }
+
+    fn forget(&mut self, label: &T) -> bool {
+        match self.ghosts.remove(label) {
+            Some((size, _)) => {
+                self.ghost_used -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.small_used + self.main_used > self.size {
+            if self.small_used > self.small_size || self.main_used == 0 {
+                self.evict_small();
+            } else {
+                self.evict_main();
+            }
+        }
+    }
+
+    fn evict_small(&mut self) {
+        let (label, entry) = self.pop(Queue::Small);
+        if entry.freq > 0 {
+            self.push(label, entry.size, 0, Queue::Main);
+        } else {
+            self.events.record(EventKind::Evict, &label, entry.size);
+            self.remember(label, entry.size);
+        }
+    }
+
+    fn evict_main(&mut self) {
+        let (label, entry) = self.pop(Queue::Main);
+        if entry.freq > 0 {
+            self.push(label, entry.size, entry.freq - 1, Queue::Main);
+        } else {
+            self.events.record(EventKind::Evict, &label, entry.size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        let queue = if self.forget(&file.label) { Queue::Main } else { Queue::Small };
+        self.push(file.label, file.size, 0, queue);
+        self.evict();
+    }
+}
+
+impl <T> CacheAlgorithm<T> for S3Fifo<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if let Some(entry) = self.cache.get_mut(&file.label) {
+            entry.freq = (entry.freq + 1).min(MAX_FREQ);
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["small_ratio"];
+
+    fn check_params(params: &Params) -> Result<(), String> {
+        params.check(Self::PARAMS)?;
+        params.check_range("small_ratio", 0.0..=1.0)
+    }
+
+    // small_ratio sets the share of the small queue
+    fn with_params(size: i64, params: &Params) -> Self {
+        S3Fifo::<T> {
+            small: VecDeque::new(),
+            main: VecDeque::new(),
+            ghost: VecDeque::new(),
+            cache: HashMap::new(),
+            ghosts: HashMap::new(),
+            small_used: 0,
+            main_used: 0,
+            ghost_used: 0,
+            size,
+            small_size: (size as f64 * params.float("small_ratio", SMALL_RATIO)) as i64,
+            event_count: 0,
+            hit_count: 0,
+            insertions: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.small_used + self.main_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        let (queue, change) = match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                let change = file.size - entry.size;
+                entry.size = file.size;
+                (entry.queue, change)
+            }
+            None => return false
+        };
+        *self.used_of(queue) += change;
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some(entry) => {
+                *self.used_of(entry.queue) -= entry.size;
+                true
+            }
+            None => false
+        }
+    }
+
+    // A write to a cached object counts as a request to it
+    fn admit(&mut self, file: FileRecord<T>) {
+        if let Some(entry) = self.cache.get_mut(&file.label) {
+            entry.freq = (entry.freq + 1).min(MAX_FREQ);
+            self.resize(&file);
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn one_hit_objects_leave_a_ghost_that_returns_to_main() {
+        let mut cache = S3Fifo::<i32>::new(10);
+        for label in 0..=10 {
+            cache.request(get(label, 1));
+        }
+        // 0 was never requested again while in small, so only its key is left
+        assert!(!cache.contains(&0));
+        assert!(cache.ghosts.contains_key(&0));
+
+        assert!(!cache.request(get(0, 1)));
+        assert_eq!(cache.cache[&0].queue, Queue::Main);
+        assert!(!cache.ghosts.contains_key(&0));
+    }
+
+    #[test]
+    fn objects_hit_in_small_move_to_main() {
+        let mut cache = S3Fifo::<i32>::new(10);
+        cache.request(get(0, 1));
+        assert!(cache.request(get(0, 1)));
+        for label in 1..=10 {
+            cache.request(get(label, 1));
+        }
+        assert_eq!(cache.cache[&0].queue, Queue::Main);
+        assert!(!cache.ghosts.contains_key(&0));
+    }
+}
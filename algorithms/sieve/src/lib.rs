@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use algorithm::{CacheAlgorithm, EventLog};
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{EventKind, FileRecord};
+
+#[derive(Serialize, Deserialize)]
+struct Node<T> {
+    label: T,
+    size: i64,
+    visited: bool,
+    newer: Option<usize>,
+    older: Option<usize>,
+}
+
+/// SIEVE (Zhang et al.), a FIFO queue with a visited bit per object and a hand that sweeps it.
+///
+/// Hits only set the visited bit. To evict, the hand moves from the oldest object towards the
+/// newest, clearing visited bits until it finds an object that was not visited, and stays there.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Sieve<T> where T : Hash + Eq {
+    nodes: Vec<Option<Node<T>>>, // queue entries, linked from newest to oldest
+    free: Vec<usize>, // unused slots of `nodes`
+    index: HashMap<T, usize>, // slot of every cached item
+    newest: Option<usize>,
+    oldest: Option<usize>,
+    hand: Option<usize>, // next object to look at, None starts from the oldest
+    current_used : i64, // current space in cache
+    size : i64, // size of cache
+    event_count: i32,
+    hit_count : i32,
+    events : EventLog<T>
+}
+
+impl <T> Sieve<T> where T : Hash + Eq + Clone {
+    fn node(&self, slot: usize) -> &Node<T> {
+        self.nodes[slot].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, slot: usize) -> &mut Node<T> {
+        self.nodes[slot].as_mut().unwrap()
+    }
+
+    fn push(&mut self, label: T, size: i64) {
+        let node = Node {
+            label: label.clone(),
+            size,
+            visited: false,
+            newer: None,
+            older: self.newest
+        };
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = Some(node);
+                slot
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        match self.newest {
+            Some(newest) => self.node_mut(newest).newer = Some(slot),
+            None => self.oldest = Some(slot),
+        }
+        self.newest = Some(slot);
+        self.index.insert(label, slot);
+    }
+
+    fn unlink(&mut self, slot: usize) -> Node<T> {
+        let node = self.nodes[slot].take().unwrap();
+        match node.newer {
+            Some(newer) => self.node_mut(newer).older = node.older,
+            None => self.newest = node.older,
+        }
+        match node.older {
+            Some(older) => self.node_mut(older).newer = node.newer,
+            None => self.oldest = node.newer,
+        }
+        if self.hand == Some(slot) {
+            self.hand = node.newer;
+        }
+        self.free.push(slot);
+        self.index.remove(&node.label);
+        self.current_used -= node.size;
+        node
+    }
+
+    fn evict(&mut self) {
+        while self.current_used > self.size {
+            let mut slot = self.hand.or(self.oldest).unwrap();
+            while self.node(slot).visited {
+                self.node_mut(slot).visited = false;
+                slot = self.node(slot).newer.or(self.oldest).unwrap();
+            }
+            let node = self.unlink(slot);
+            self.hand = node.newer;
+            self.events.record(EventKind::Evict, &node.label, node.size);
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.current_used += file.size;
+        self.evict();
+        self.push(file.label, file.size);
+    }
+}
+
+impl <T> CacheAlgorithm<T> for Sieve<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if let Some(&slot) = self.index.get(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.node_mut(slot).visited = true;
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Sieve::<T> {
+            nodes: vec![],
+            free: vec![],
+            index: HashMap::new(),
+            newest: None,
+            oldest: None,
+            hand: None,
+            current_used: 0,
+            size,
+            event_count: 0,
+            hit_count: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32,i32) {
+        (self.event_count, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.current_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.index.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        match self.index.get(&file.label) {
+            Some(&slot) => {
+                let node = self.node_mut(slot);
+                let old = node.size;
+                node.size = file.size;
+                self.current_used += file.size - old;
+            }
+            None => return false
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.index.get(label) {
+            Some(&slot) => {
+                self.unlink(slot);
+                true
+            }
+            None => false
+        }
+    }
+
+    // A write to a cached object counts as a visit
+    fn admit(&mut self, file: FileRecord<T>) {
+        if let Some(&slot) = self.index.get(&file.label) {
+            self.node_mut(slot).visited = true;
+            self.resize(&file);
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn visited_objects_survive_the_hand() {
+        let mut cache = Sieve::<i32>::new(3);
+        for label in 1..=3 {
+            cache.request(get(label, 1));
+        }
+        assert!(cache.request(get(1, 1)));
+        // The hand clears 1 and evicts 2, then carries on from 3
+        cache.request(get(4, 1));
+        assert!(cache.contains(&1) && !cache.contains(&2));
+        cache.request(get(5, 1));
+        assert!(cache.contains(&1) && !cache.contains(&3));
+        assert!(cache.contains(&4) && cache.contains(&5));
+    }
+}
@@ -0,0 +1,238 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::Hash;
+use priority_queue::priority_queue::PriorityQueue;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog, Params};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Share of the cache given to the protected segment
+const PROTECTED_RATIO: f64 = 0.8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum Segment {
+    Probation,
+    Protected,
+}
+
+/// Segmented LRU (Karedla et al.), a probationary and a protected LRU segment.
+///
+/// New objects enter probation and move to protected when requested again. Objects falling off
+/// protected go back to the most recent end of probation, and evictions come from probation.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Slru<T> where T : Hash + Eq {
+    probation: PriorityQueue<T, Reverse<u64>>,
+    protected: PriorityQueue<T, Reverse<u64>>,
+    cache: HashMap<T, (i64, Segment)>, // size and segment of cached items
+    probation_used: i64,
+    protected_used: i64,
+    size : i64, // size of cache
+    protected_size: i64,
+    event_count: u64,
+    hit_count : i32,
+    clock : u64, // reads and writes, used as recency
+    events : EventLog<T>,
+}
+
+impl<T> Slru<T> where T : Hash + Eq + Clone {
+    fn queue(&mut self, segment: Segment) -> &mut PriorityQueue<T, Reverse<u64>> {
+        match segment {
+            Segment::Probation => &mut self.probation,
+            Segment::Protected => &mut self.protected,
+        }
+    }
+
+    fn used_of(&mut self, segment: Segment) -> &mut i64 {
+        match segment {
+            Segment::Probation => &mut self.probation_used,
+            Segment::Protected => &mut self.protected_used,
+        }
+    }
+
+    // Moves a cached object to the most recent end of `segment`
+    fn place(&mut self, label: T, size: i64, from: Option<Segment>, to: Segment) {
+        self.clock += 1;
+        if let Some(from) = from {
+            self.queue(from).remove(&label);
+            *self.used_of(from) -= size;
+        }
+        *self.used_of(to) += size;
+        self.cache.insert(label.clone(), (size, to));
+        let clock = self.clock;
+        self.queue(to).push(label, Reverse(clock));
+    }
+
+    fn touch(&mut self, label: &T) {
+        let (size, segment) = self.cache[label];
+        self.place(label.clone(), size, Some(segment), Segment::Protected);
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.place(file.label, file.size, None, Segment::Probation);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        // Protected overflows into probation
+        while self.protected_used > self.protected_size {
+            let (label, _) = self.protected.pop().unwrap();
+            let (size, _) = self.cache[&label];
+            self.protected_used -= size;
+            self.place(label, size, None, Segment::Probation);
+        }
+
+        while self.probation_used + self.protected_used > self.size {
+            let (label, _) = self.probation.pop().or_else(|| self.protected.pop()).unwrap();
+            let (size, segment) = self.cache.remove(&label).unwrap();
+            *self.used_of(segment) -= size;
+            self.events.record(EventKind::Evict, &label, size);
+        }
+    }
+}
+
+impl<T> CacheAlgorithm<T> for Slru<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if self.cache.contains_key(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.touch(&file.label);
+            self.evict();
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["protected_ratio"];
+
+    fn check_params(params: &Params) -> Result<(), String> {
+        params.check(Self::PARAMS)?;
+        params.check_range("protected_ratio", 0.0..=1.0)
+    }
+
+    // protected_ratio sets the share of the protected segment
+    fn with_params(size: i64, params: &Params) -> Self {
+        Slru::<T> {
+            probation: PriorityQueue::new(),
+            protected: PriorityQueue::new(),
+            cache: HashMap::new(),
+            probation_used: 0,
+            protected_used: 0,
+            size,
+            protected_size: (size as f64 * params.float("protected_ratio", PROTECTED_RATIO)) as i64,
+            event_count: 0,
+            hit_count: 0,
+            clock: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        (self.event_count as i32, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.probation_used + self.protected_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        let (segment, change) = match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                let change = file.size - entry.0;
+                entry.0 = file.size;
+                (entry.1, change)
+            }
+            None => return false
+        };
+        *self.used_of(segment) += change;
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some((size, segment)) => {
+                self.queue(segment).remove(label);
+                *self.used_of(segment) -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        if self.resize(&file) {
+            if self.cache.contains_key(&file.label) {
+                self.touch(&file.label);
+                self.evict();
+            }
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn protected_objects_survive_a_scan() {
+        let mut cache = Slru::<i32>::new(100);
+        for _ in 0..2 {
+            for label in 0..20 {
+                cache.request(get(label, 1));
+            }
+        }
+        for label in 1000..2000 {
+            cache.request(get(label, 1));
+        }
+        assert!((0..20).all(|label| cache.contains(&label)));
+        assert!(cache.used() <= 100);
+    }
+
+    #[test]
+    fn promotions_stay_within_the_protected_segment() {
+        let mut cache = Slru::<i32>::new(100);
+        cache.protected_size = 50;
+        for _ in 0..2 {
+            for label in 0..80 {
+                cache.request(get(label, 1));
+            }
+        }
+        assert_eq!(cache.protected_used, 50);
+        assert_eq!(cache.used(), 80);
+    }
+}
@@ -0,0 +1,278 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use priority_queue::priority_queue::PriorityQueue;
+use serde::{Deserialize, Serialize};
+use algorithm::{CacheAlgorithm, EventLog, Params};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Shares of the cache for A1in and for the objects remembered by A1out, as in the paper
+const IN_RATIO: f64 = 0.25;
+const OUT_RATIO: f64 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum Queue {
+    In,
+    Main,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    size: i64,
+    queue: Queue,
+    insertion: u64, // matches the object's entry in A1in
+}
+
+/// Full 2Q (Johnson and Shasha), a FIFO for new objects in front of an LRU.
+///
+/// New objects enter the A1in FIFO, and requests to them there are ignored. Objects leaving A1in
+/// leave their key in the A1out ghost FIFO, and only those requested again while remembered there
+/// enter the Am LRU.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct TwoQ<T> where T : Hash + Eq {
+    a1in: VecDeque<(T, u64)>, // newest at the front, stale entries of invalidated items stay behind
+    a1out: VecDeque<(T, u64)>,
+    am: PriorityQueue<T, Reverse<u64>>,
+    cache: HashMap<T, Entry>,
+    ghosts: HashMap<T, (i64, u64)>, // size and insertion number of keys in A1out
+    in_used: i64,
+    main_used: i64,
+    ghost_used: i64, // sizes of the objects the ghost keys stand for
+    size : i64, // size of cache
+    in_size: i64,
+    out_size: i64,
+    event_count: u64,
+    hit_count : i32,
+    clock : u64, // reads, writes and insertions, used as recency and insertion numbers
+    events : EventLog<T>,
+}
+
+impl<T> TwoQ<T> where T : Hash + Eq + Clone {
+    fn remember(&mut self, label: T, size: i64) {
+        self.clock += 1;
+        self.ghost_used += size;
+        self.ghosts.insert(label.clone(), (size, self.clock));
+        self.a1out.push_front((label, self.clock));
+        while self.ghost_used > self.out_size {
+            let (label, insertion) = self.a1out.pop_back().unwrap();
+            if let Some(&(size, current)) = self.ghosts.get(&label) {
+                if current == insertion {
+                    self.ghosts.remove(&label);
+                    self.ghost_used -= size;
+                }
+            }
+        }
+    }
+
+    fn forget(&mut self, label: &T) -> bool {
+        match self.ghosts.remove(label) {
+            Some((size, _)) => {
+                self.ghost_used -= size;
+                true
+            }
+            None => false
+        }
+    }
+
+    // Oldest live object of A1in, skipping the stale entries
+    fn pop_in(&mut self) -> (T, i64) {
+        loop {
+            let (label, insertion) = self.a1in.pop_back().unwrap();
+            let live = match self.cache.get(&label) {
+                Some(entry) => entry.queue == Queue::In && entry.insertion == insertion,
+                None => false
+            };
+            if live {
+                let entry = self.cache.remove(&label).unwrap();
+                self.in_used -= entry.size;
+                return (label, entry.size);
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.in_used + self.main_used > self.size {
+            if self.in_used > self.in_size || self.main_used == 0 {
+                let (label, size) = self.pop_in();
+                self.events.record(EventKind::Evict, &label, size);
+                self.remember(label, size);
+            } else {
+                let (label, _) = self.am.pop().unwrap();
+                let entry = self.cache.remove(&label).unwrap();
+                self.main_used -= entry.size;
+                self.events.record(EventKind::Evict, &label, entry.size);
+            }
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.clock += 1;
+        if self.forget(&file.label) {
+            self.main_used += file.size;
+            self.am.push(file.label.clone(), Reverse(self.clock));
+            self.cache.insert(file.label, Entry { size: file.size, queue: Queue::Main, insertion: self.clock });
+        } else {
+            self.in_used += file.size;
+            self.a1in.push_front((file.label.clone(), self.clock));
+            self.cache.insert(file.label, Entry { size: file.size, queue: Queue::In, insertion: self.clock });
+        }
+        self.evict();
+    }
+
+    // Only requests to objects in Am count towards recency
+    fn touch(&mut self, label: &T) {
+        self.clock += 1;
+        self.am.change_priority(label, Reverse(self.clock));
+    }
+}
+
+impl<T> CacheAlgorithm<T> for TwoQ<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        if self.cache.contains_key(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.touch(&file.label);
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["in_ratio", "out_ratio"];
+
+    fn check_params(params: &Params) -> Result<(), String> {
+        params.check(Self::PARAMS)?;
+        params.check_range("in_ratio", 0.0..=1.0)?;
+        params.check_range("out_ratio", 0.0..=f64::INFINITY)
+    }
+
+    // in_ratio and out_ratio set the shares of A1in and A1out
+    fn with_params(size: i64, params: &Params) -> Self {
+        TwoQ::<T> {
+            a1in: VecDeque::new(),
+            a1out: VecDeque::new(),
+            am: PriorityQueue::new(),
+            cache: HashMap::new(),
+            ghosts: HashMap::new(),
+            in_used: 0,
+            main_used: 0,
+            ghost_used: 0,
+            size,
+            in_size: (size as f64 * params.float("in_ratio", IN_RATIO)) as i64,
+            out_size: (size as f64 * params.float("out_ratio", OUT_RATIO)) as i64,
+            event_count: 0,
+            hit_count: 0,
+            clock: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        (self.event_count as i32, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.in_used + self.main_used
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        let (queue, change) = match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                let change = file.size - entry.size;
+                entry.size = file.size;
+                (entry.queue, change)
+            }
+            None => return false
+        };
+        match queue {
+            Queue::In => self.in_used += change,
+            Queue::Main => self.main_used += change,
+        }
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        match self.cache.remove(label) {
+            Some(entry) => {
+                match entry.queue {
+                    Queue::In => self.in_used -= entry.size,
+                    Queue::Main => {
+                        self.am.remove(label);
+                        self.main_used -= entry.size;
+                    }
+                }
+                true
+            }
+            None => false
+        }
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        if self.resize(&file) {
+            if self.cache.contains_key(&file.label) {
+                self.touch(&file.label);
+            }
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn hot_objects_survive_a_scan() {
+        let mut cache = TwoQ::<i32>::new(100);
+        let mut cold = 1000;
+        for _ in 0..10 {
+            for label in 0..20 {
+                cache.request(get(label, 1));
+            }
+            for _ in 0..40 {
+                cache.request(get(cold, 1));
+                cold += 1;
+            }
+        }
+        for _ in 0..1000 {
+            cache.request(get(cold, 1));
+            cold += 1;
+        }
+        assert!((0..20).all(|label| cache.contains(&label)));
+        assert!(cache.used() <= 100);
+    }
+}
@@ -0,0 +1,292 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::Hash;
+use priority_queue::priority_queue::PriorityQueue;
+use serde::{Deserialize, Serialize};
+use admission::Sketch;
+use algorithm::{CacheAlgorithm, EventLog, Params};
+use simulator_shared_types::{EventKind, FileRecord};
+
+// Shares of the cache, as in Caffeine
+const WINDOW_RATIO: f64 = 0.01;
+const PROTECTED_RATIO: f64 = 0.8; // of the main region
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum Segment {
+    Window,
+    Probation,
+    Protected,
+}
+
+/// W-TinyLFU (Einziger et al.), the policy of Caffeine and Ristretto.
+///
+/// New objects enter a small LRU window. Objects leaving the window only enter the segmented LRU
+/// main region when the frequency sketch estimates them more popular than the main region's victim.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct WTinyLFU<T> where T : Hash + Eq {
+    window: PriorityQueue<T, Reverse<u64>>,
+    probation: PriorityQueue<T, Reverse<u64>>,
+    protected: PriorityQueue<T, Reverse<u64>>,
+    cache: HashMap<T, (i64, Segment)>, // size and segment of cached items
+    sketch: Sketch,
+    window_used: i64,
+    probation_used: i64,
+    protected_used: i64,
+    size : i64, // size of cache
+    window_size: i64,
+    protected_size: i64,
+    event_count: u64,
+    hit_count : i32,
+    clock : u64, // reads and writes, used as recency
+    events : EventLog<T>,
+}
+
+impl<T> WTinyLFU<T> where T : Hash + Eq + Clone {
+    fn queue(&mut self, segment: Segment) -> &mut PriorityQueue<T, Reverse<u64>> {
+        match segment {
+            Segment::Window => &mut self.window,
+            Segment::Probation => &mut self.probation,
+            Segment::Protected => &mut self.protected,
+        }
+    }
+
+    fn used_of(&mut self, segment: Segment) -> &mut i64 {
+        match segment {
+            Segment::Window => &mut self.window_used,
+            Segment::Probation => &mut self.probation_used,
+            Segment::Protected => &mut self.protected_used,
+        }
+    }
+
+    fn main_used(&self) -> i64 {
+        self.probation_used + self.protected_used
+    }
+
+    // Moves a cached object to the most recent end of `segment`
+    fn place(&mut self, label: T, size: i64, from: Option<Segment>, to: Segment) {
+        self.clock += 1;
+        if let Some(from) = from {
+            self.queue(from).remove(&label);
+            *self.used_of(from) -= size;
+        }
+        *self.used_of(to) += size;
+        self.cache.insert(label.clone(), (size, to));
+        let clock = self.clock;
+        self.queue(to).push(label, Reverse(clock));
+    }
+
+    fn remove(&mut self, label: &T) -> Option<i64> {
+        let (size, segment) = self.cache.remove(label)?;
+        self.queue(segment).remove(label);
+        *self.used_of(segment) -= size;
+        Some(size)
+    }
+
+    fn touch(&mut self, label: &T) {
+        let (size, segment) = self.cache[label];
+        match segment {
+            // A hit in probation earns the object a place in protected
+            Segment::Probation => self.place(label.clone(), size, Some(segment), Segment::Protected),
+            _ => self.place(label.clone(), size, Some(segment), segment),
+        }
+    }
+
+    fn insert(&mut self, file: FileRecord<T>) {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.events.record(EventKind::Insert, &file.label, file.size);
+        self.place(file.label, file.size, None, Segment::Window);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        // Objects leaving the window compete for a place in the main region
+        while self.window_used > self.window_size {
+            let (candidate, _) = self.window.pop().unwrap();
+            let (size, _) = self.cache[&candidate];
+            self.window_used -= size;
+            self.probation_used += size;
+            self.cache.insert(candidate.clone(), (size, Segment::Probation));
+            if self.make_room(&candidate, size) {
+                self.clock += 1;
+                self.probation.push(candidate, Reverse(self.clock));
+            } else {
+                self.probation_used -= size;
+                self.cache.remove(&candidate);
+                self.events.record(EventKind::Evict, &candidate, size);
+            }
+        }
+
+        // Protected overflows into probation
+        while self.protected_used > self.protected_size {
+            let (label, _) = self.protected.pop().unwrap();
+            let (size, _) = self.cache[&label];
+            self.protected_used -= size;
+            self.place(label, size, None, Segment::Probation);
+        }
+
+        // Resized objects can leave the main region over its size with no candidate waiting
+        while self.main_used() > self.size - self.window_size {
+            let label = self.victim().unwrap();
+            let size = self.remove(&label).unwrap();
+            self.events.record(EventKind::Evict, &label, size);
+        }
+    }
+
+    // Evicts main region victims less popular than the candidate until it fits, counting the
+    // candidate as already in probation. Returns whether it fits.
+    fn make_room(&mut self, candidate: &T, size: i64) -> bool {
+        let main_size = self.size - self.window_size;
+        if size > main_size {
+            return false;
+        }
+        let frequency = self.sketch.estimate(algorithm::hash64(candidate));
+        while self.main_used() > main_size {
+            let victim = self.victim().unwrap();
+            if frequency <= self.sketch.estimate(algorithm::hash64(&victim)) {
+                return false;
+            }
+            let size = self.remove(&victim).unwrap();
+            self.events.record(EventKind::Evict, &victim, size);
+        }
+        true
+    }
+
+    // Least recent object of probation, or of protected when probation is empty
+    fn victim(&self) -> Option<T> {
+        self.probation.peek().or_else(|| self.protected.peek()).map(|(label, _)| label.clone())
+    }
+}
+
+impl<T> CacheAlgorithm<T> for WTinyLFU<T> where T : Hash + Eq + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if file.size > self.size {
+            println!("FILE SIZE: {}", file.size);
+            panic!("File larger than cache")
+        }
+        self.event_count += 1;
+        self.sketch.increment(algorithm::hash64(&file.label));
+        if self.cache.contains_key(&file.label) {
+            self.hit_count += 1;
+            self.events.record(EventKind::Hit, &file.label, file.size);
+            self.touch(&file.label);
+            self.evict();
+            return true;
+        }
+        self.insert(file);
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = &["window_ratio", "protected_ratio", "sketch_width"];
+
+    fn check_params(params: &Params) -> Result<(), String> {
+        params.check(Self::PARAMS)?;
+        params.check_range("window_ratio", 0.0..=1.0)?;
+        params.check_range("protected_ratio", 0.0..=1.0)
+    }
+
+    // window_ratio, protected_ratio and sketch_width set the shares of the segments and the counters per sketch row
+    fn with_params(size: i64, params: &Params) -> Self {
+        let window_size = ((size as f64 * params.float("window_ratio", WINDOW_RATIO)) as i64).max(1);
+        WTinyLFU::<T> {
+            window: PriorityQueue::new(),
+            probation: PriorityQueue::new(),
+            protected: PriorityQueue::new(),
+            cache: HashMap::new(),
+            sketch: Sketch::new(params.int("sketch_width", admission::SKETCH_WIDTH as i64).max(1) as usize),
+            window_used: 0,
+            probation_used: 0,
+            protected_used: 0,
+            size,
+            window_size,
+            protected_size: ((size - window_size) as f64 * params.float("protected_ratio", PROTECTED_RATIO)) as i64,
+            event_count: 0,
+            hit_count: 0,
+            clock: 0,
+            events: EventLog::new()
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        (self.event_count as i32, self.hit_count)
+    }
+
+    fn reset_stats(&mut self) {
+        self.event_count = 0;
+        self.hit_count = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.window_used + self.main_used()
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.cache.contains_key(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        let segment = match self.cache.get_mut(&file.label) {
+            Some(entry) => {
+                let old = entry.0;
+                entry.0 = file.size;
+                (entry.1, file.size - old)
+            }
+            None => return false
+        };
+        *self.used_of(segment.0) += segment.1;
+        self.evict();
+        true
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        self.remove(label).is_some()
+    }
+
+    fn admit(&mut self, file: FileRecord<T>) {
+        self.sketch.increment(algorithm::hash64(&file.label));
+        if self.resize(&file) {
+            if self.cache.contains_key(&file.label) {
+                self.touch(&file.label);
+                self.evict();
+            }
+        } else {
+            self.insert(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        &mut self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(label: i32, size: i64) -> FileRecord<i32> {
+        FileRecord { label, size, ..Default::default() }
+    }
+
+    #[test]
+    fn popular_objects_survive_a_scan() {
+        let mut cache = WTinyLFU::<i32>::new(100);
+        for _ in 0..5 {
+            for label in 0..50 {
+                cache.request(get(label, 1));
+            }
+        }
+        // Objects seen once lose the frequency contest against the main region's victims
+        for label in 1000..2000 {
+            cache.request(get(label, 1));
+        }
+        assert!((0..50).all(|label| cache.contains(&label)));
+        assert!(cache.used() <= 100);
+    }
+}
@@ -29,4 +29,13 @@ pub fn write_bytes_to_wasm_memory(bytes: &[u8], memory: &Memory, ptr: usize, len
             mem_array[ptr + i as usize] = bytes[i as usize];
         }
     }
-}
\ No newline at end of file
+}
+pub fn read_bincode_from_wasm_memory<T : serde::de::DeserializeOwned>(memory: &Memory, ptr: usize, len: usize) -> T {
+    bincode::deserialize(&read_bytes_from_wasm_memory(memory, ptr, len)).expect("Failed to deserialize type")
+}
+
+pub fn read_bytes_from_wasm_memory(memory: &Memory, ptr: usize, len: usize) -> Vec<u8> {
+    unsafe {
+        memory.data_unchecked()[ptr..ptr + len].to_vec()
+    }
+}
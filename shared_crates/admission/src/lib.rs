@@ -0,0 +1,371 @@
+//! Admission filters that decide whether a missed object is worth caching at all, in front of any
+//! `CacheAlgorithm`.
+//!
+//! Cached objects always pass, so the wrapped policy alone decides what to evict and admission and
+//! eviction can be studied apart. Every host and guest policy is wrapped,
+//! `Admission::All` passes every object through.
+
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use algorithm::{hash64, mix64, CacheAlgorithm, EventLog, Params, Rng};
+use simulator_shared_types::{EventKind, FileRecord};
+
+/// Which objects a policy caches when they miss or are written.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Admission {
+    All,
+    TinyLfu { threshold: u8 }, // estimated recent frequency reaches the threshold, see `Sketch`
+    SecondHit, // requested before, as remembered by a bloom filter
+    Size { max: i64 }, // no larger than `max` bytes
+    AdaptSize { c: f64 }, // with probability e^(-size/c), as in AdaptSize with a fixed c
+}
+
+impl FromStr for Admission {
+    type Err = String;
+
+    // all, tinylfu[:THRESHOLD], second-hit, size:BYTES or adaptsize:C
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.find(':') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None)
+        };
+        let invalid = || format!("Invalid admission parameter: {}", s);
+        match (name, param) {
+            ("all", None) => Ok(Admission::All),
+            ("tinylfu", None) => Ok(Admission::TinyLfu { threshold: 2 }),
+            ("tinylfu", Some(threshold)) => match u8::from_str(threshold) {
+                Ok(threshold) if threshold <= MAX_COUNT => Ok(Admission::TinyLfu { threshold }),
+                _ => Err(invalid())
+            },
+            ("second-hit", None) => Ok(Admission::SecondHit),
+            ("size", Some(max)) => i64::from_str(max).map(|max| Admission::Size { max }).map_err(|_| invalid()),
+            ("adaptsize", Some(c)) => match f64::from_str(c) {
+                Ok(c) if c > 0.0 => Ok(Admission::AdaptSize { c }),
+                _ => Err(invalid())
+            },
+            _ => Err(format!("Unknown admission: {}", s))
+        }
+    }
+}
+
+/// A policy behind an admission filter. Objects the filter turns away are recorded as bypasses and
+/// count as read misses.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "A : Serialize", deserialize = "A : Deserialize<'de>"))]
+pub struct Admitted<A, T> {
+    policy: A,
+    filter: Filter,
+    bypassed: u64, // reads turned away since the statistics were last reset
+    phantom: PhantomData<T>,
+}
+
+impl <A, T> Admitted<A, T> {
+    /// Replaces the filter, forgetting what the previous one had seen.
+    pub fn set_admission(&mut self, admission: Admission) {
+        self.filter = Filter::new(admission);
+    }
+
+    pub fn policy(&self) -> &A {
+        &self.policy
+    }
+}
+
+impl <A, T> Admitted<A, T> where A : CacheAlgorithm<T>, T : Hash + Clone {
+    // Cached objects always pass, the filter only counts their request
+    fn passes(&mut self, file: &FileRecord<T>) -> bool {
+        if let Filter::All = self.filter {
+            return true;
+        }
+        let admitted = self.filter.admits(file);
+        if !admitted && !self.policy.contains(&file.label) {
+            self.policy.events().record(EventKind::Bypass, &file.label, file.size);
+            return false;
+        }
+        true
+    }
+}
+
+impl <A, T> CacheAlgorithm<T> for Admitted<A, T> where A : CacheAlgorithm<T>, T : Hash + Clone {
+    fn simulate(&mut self, file: FileRecord<T>) -> bool {
+        if self.passes(&file) {
+            return self.policy.simulate(file);
+        }
+        self.bypassed += 1;
+        false
+    }
+
+    fn new(size: i64) -> Self {
+        Self::with_params(size, &Params::new())
+    }
+
+    const PARAMS: &'static [&'static str] = A::PARAMS;
+
+    fn check_params(params: &Params) -> Result<(), String> {
+        A::check_params(params)
+    }
+
+    fn with_params(size: i64, params: &Params) -> Self {
+        Admitted {
+            policy: A::with_params(size, params),
+            filter: Filter::All,
+            bypassed: 0,
+            phantom: PhantomData
+        }
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        let (requests, hits) = self.policy.stats();
+        (requests + self.bypassed as i32, hits)
+    }
+
+    fn reset_stats(&mut self) {
+        self.policy.reset_stats();
+        self.bypassed = 0;
+    }
+
+    fn used(&self) -> i64 {
+        self.policy.used()
+    }
+
+    fn contains(&self, label: &T) -> bool {
+        self.policy.contains(label)
+    }
+
+    fn resize(&mut self, file: &FileRecord<T>) -> bool {
+        self.policy.resize(file)
+    }
+
+    fn invalidate(&mut self, label: &T) -> bool {
+        self.policy.invalidate(label)
+    }
+
+    // Writes go through the filter like misses, a turned away write leaves the object uncached
+    fn admit(&mut self, file: FileRecord<T>) {
+        if self.passes(&file) {
+            self.policy.admit(file);
+        }
+    }
+
+    fn events(&mut self) -> &mut EventLog<T> {
+        self.policy.events()
+    }
+}
+
+// Counters saturate at 15, as in the 4-bit counters of TinyLFU
+const MAX_COUNT: u8 = 15;
+const SKETCH_ROWS: usize = 4;
+/// Counters per row of the admission filter's sketch.
+pub const SKETCH_WIDTH: usize = 1 << 16;
+const BLOOM_BITS: usize = 1 << 20;
+const BLOOM_HASHES: u64 = 3;
+
+#[derive(Serialize, Deserialize)]
+enum Filter {
+    All,
+    TinyLfu { sketch: Sketch, threshold: u8 },
+    SecondHit { bloom: Bloom },
+    Size { max: i64 },
+    AdaptSize { c: f64, rng: Rng },
+}
+
+impl Filter {
+    fn new(admission: Admission) -> Self {
+        match admission {
+            Admission::All => Filter::All,
+            Admission::TinyLfu { threshold } => Filter::TinyLfu { sketch: Sketch::new(SKETCH_WIDTH), threshold },
+            Admission::SecondHit => Filter::SecondHit { bloom: Bloom::new() },
+            Admission::Size { max } => Filter::Size { max },
+            Admission::AdaptSize { c } => Filter::AdaptSize { c, rng: Rng::new(0) },
+        }
+    }
+
+    // Counts a request to the object and returns whether it may be cached
+    fn admits<T>(&mut self, file: &FileRecord<T>) -> bool where T : Hash {
+        match self {
+            Filter::All => true,
+            Filter::TinyLfu { sketch, threshold } => sketch.increment(hash64(&file.label)) >= *threshold,
+            Filter::SecondHit { bloom } => bloom.insert(hash64(&file.label)),
+            Filter::Size { max } => file.size <= *max,
+            Filter::AdaptSize { c, rng } => rng.uniform() < (-(file.size as f64) / *c).exp(),
+        }
+    }
+}
+
+/// Count-min sketch of recent request frequencies, with 4-bit counters as in TinyLFU.
+///
+/// Counters are halved once the sketch has counted ten times its width, so old popularity fades.
+#[derive(Serialize, Deserialize)]
+pub struct Sketch {
+    counters: Vec<u8>, // SKETCH_ROWS rows of `width` counters
+    width: usize,
+    additions: usize,
+}
+
+impl Sketch {
+    pub fn new(width: usize) -> Self {
+        Sketch {
+            counters: vec![0; SKETCH_ROWS * width],
+            width,
+            additions: 0
+        }
+    }
+
+    /// Estimated frequency of the object with this `hash`.
+    pub fn estimate(&self, hash: u64) -> u8 {
+        slots(hash, SKETCH_ROWS as u64, self.width).enumerate()
+            .map(|(row, slot)| self.counters[row * self.width + slot])
+            .min()
+            .unwrap()
+    }
+
+    /// Counts the object and returns its estimated frequency, including this request.
+    pub fn increment(&mut self, hash: u64) -> u8 {
+        let estimate = self.estimate(hash);
+        // Conservative update, only the counters at the minimum grow
+        for (row, slot) in slots(hash, SKETCH_ROWS as u64, self.width).enumerate() {
+            let counter = &mut self.counters[row * self.width + slot];
+            if *counter == estimate && *counter < MAX_COUNT {
+                *counter += 1;
+            }
+        }
+
+        self.additions += 1;
+        if self.additions >= 10 * self.width {
+            for counter in self.counters.iter_mut() {
+                *counter /= 2;
+            }
+            self.additions /= 2;
+        }
+        (estimate + 1).min(MAX_COUNT)
+    }
+}
+
+/// Bloom filter of recently requested objects, cleared once it holds an eighth of its bits worth of objects.
+#[derive(Serialize, Deserialize)]
+struct Bloom {
+    bits: Vec<u64>,
+    insertions: usize,
+}
+
+impl Bloom {
+    fn new() -> Self {
+        Bloom {
+            bits: vec![0; BLOOM_BITS / 64],
+            insertions: 0
+        }
+    }
+
+    // Adds the object and returns whether it was already there
+    fn insert(&mut self, hash: u64) -> bool {
+        let mut present = true;
+        for slot in slots(hash, BLOOM_HASHES, BLOOM_BITS) {
+            let (word, bit) = (slot / 64, 1u64 << (slot % 64));
+            present &= self.bits[word] & bit != 0;
+            self.bits[word] |= bit;
+        }
+        if !present {
+            self.insertions += 1;
+            if self.insertions >= BLOOM_BITS / 8 {
+                self.bits.iter_mut().for_each(|word| *word = 0);
+                self.insertions = 0;
+            }
+        }
+        present
+    }
+}
+
+// `count` slots below `width` from one hash, by double hashing
+fn slots(hash: u64, count: u64, width: usize) -> impl Iterator<Item = usize> {
+    let step = mix64(hash) | 1;
+    (0..count).map(move |i| (hash.wrapping_add(i.wrapping_mul(step)) % width as u64) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use lru::LRU;
+    use super::*;
+
+    fn admitted(size: i64, admission: Admission) -> Admitted<LRU<i32>, i32> {
+        let mut cache = Admitted::<LRU<i32>, i32>::new(size);
+        cache.set_admission(admission);
+        cache.events().enable();
+        cache
+    }
+
+    fn reads(cache: &mut Admitted<LRU<i32>, i32>, requests: &[(i32, i64)]) -> Vec<bool> {
+        requests.iter().map(|&(label, size)| cache.simulate(FileRecord { label, size, ..Default::default() })).collect()
+    }
+
+    #[test]
+    fn parses_admissions_and_their_parameters() {
+        assert_eq!(Admission::from_str("tinylfu"), Ok(Admission::TinyLfu { threshold: 2 }));
+        assert_eq!(Admission::from_str("tinylfu:5"), Ok(Admission::TinyLfu { threshold: 5 }));
+        assert_eq!(Admission::from_str("size:100"), Ok(Admission::Size { max: 100 }));
+        assert!(Admission::from_str("tinylfu:16").is_err());
+        assert!(Admission::from_str("adaptsize:0").is_err());
+        assert!(Admission::from_str("size").is_err());
+    }
+
+    #[test]
+    fn sketch_counts_saturate_and_age() {
+        let mut sketch = Sketch::new(4);
+        assert_eq!(sketch.estimate(7), 0);
+        assert_eq!((sketch.increment(7), sketch.increment(7)), (1, 2));
+        for _ in 0..20 {
+            sketch.increment(7);
+        }
+        assert_eq!(sketch.estimate(7), MAX_COUNT);
+        // The 40th addition is ten times the width and halves every counter
+        for _ in 0..18 {
+            sketch.increment(7);
+        }
+        assert_eq!(sketch.estimate(7), MAX_COUNT / 2);
+    }
+
+    #[test]
+    fn bloom_remembers_inserted_objects() {
+        let mut bloom = Bloom::new();
+        assert!(!bloom.insert(hash64(&1)));
+        assert!(bloom.insert(hash64(&1)));
+        assert!(!bloom.insert(hash64(&2)));
+    }
+
+    #[test]
+    fn tinylfu_turns_away_objects_below_the_threshold() {
+        let mut cache = admitted(100, Admission::TinyLfu { threshold: 2 });
+        assert_eq!(reads(&mut cache, &[(1, 10), (1, 10), (1, 10)]), vec![false, false, true]);
+        assert_eq!(cache.stats(), (3, 1));
+        let kinds: Vec<EventKind> = cache.events().drain().iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, vec![EventKind::Bypass, EventKind::Insert, EventKind::Hit]);
+    }
+
+    #[test]
+    fn second_hit_caches_objects_seen_before() {
+        let mut cache = admitted(100, Admission::SecondHit);
+        assert_eq!(reads(&mut cache, &[(1, 10), (2, 10), (1, 10), (1, 10), (2, 10)]), vec![false, false, false, true, false]);
+        assert!(cache.contains(&1) && cache.contains(&2));
+    }
+
+    #[test]
+    fn size_admission_turns_away_large_objects() {
+        let mut cache = admitted(100, Admission::Size { max: 20 });
+        assert_eq!(reads(&mut cache, &[(1, 30), (1, 30), (2, 20), (2, 20)]), vec![false, false, false, true]);
+        assert!(!cache.contains(&1));
+        cache.admit(FileRecord { label: 3, size: 30, ..Default::default() });
+        assert!(!cache.contains(&3));
+        assert_eq!(cache.used(), 20);
+    }
+
+    #[test]
+    fn cached_objects_always_pass() {
+        let mut cache = admitted(100, Admission::All);
+        reads(&mut cache, &[(1, 30)]);
+        cache.set_admission(Admission::Size { max: 20 });
+        assert_eq!(reads(&mut cache, &[(1, 30)]), vec![true]);
+        cache.reset_stats();
+        reads(&mut cache, &[(2, 30)]);
+        assert_eq!(cache.stats(), (1, 0));
+    }
+}
@@ -1,7 +1,359 @@
-use simulator_shared_types::FileRecord;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use simulator_shared_types::{Event, EventKind, FileRecord, Operation};
 
 pub trait CacheAlgorithm<T>{
-    fn simulate(&mut self, file: FileRecord<T>);
+    /// Counts a read of the object, inserting it on a miss. Returns whether it hit.
+    fn simulate(&mut self, file: FileRecord<T>) -> bool;
     fn new(size: i64) -> Self;
+    /// Names of the parameters `with_params` reads, see `Params::check`.
+    const PARAMS: &'static [&'static str] = &[];
+    /// Builds the policy with tunable parameters, see `Params`. Policies without any use `new`.
+    fn with_params(size: i64, params: &Params) -> Self where Self : Sized {
+        let _ = params;
+        Self::new(size)
+    }
+    /// Fails on parameters `with_params` does not take or cannot work with. Policies with bounded
+    /// parameters, such as the share of a segment, check their values on top of the names.
+    fn check_params(params: &Params) -> Result<(), String> where Self : Sized {
+        params.check(Self::PARAMS)
+    }
     fn stats(&self) -> (i32,i32);
-}
\ No newline at end of file
+    /// Zeroes the request and hit counts, leaving the cache contents alone.
+    fn reset_stats(&mut self);
+    /// Bytes currently cached.
+    fn used(&self) -> i64;
+    /// Whether an object is cached, without counting a request.
+    fn contains(&self, label: &T) -> bool;
+    /// Changes the size of a cached object in place, evicting others if it grew.
+    /// Returns whether the object was cached.
+    fn resize(&mut self, file: &FileRecord<T>) -> bool;
+    /// Drops an object from the cache without counting a request. Returns whether it was cached.
+    fn invalidate(&mut self, label: &T) -> bool;
+    /// Places an object in the cache without counting a request, resizing it if it is already cached.
+    fn admit(&mut self, file: FileRecord<T>);
+    /// Events recorded by the policy, see `EventLog`.
+    fn events(&mut self) -> &mut EventLog<T>;
+
+    /// Whether the cache has filled up, which is when it first had to evict.
+    fn filled(&mut self) -> bool {
+        self.events().evictions() > 0
+    }
+
+    /// Serializes the full state of the policy, statistics included.
+    fn snapshot(&self) -> Vec<u8> where Self : Serialize {
+        bincode::serialize(self).expect("Failed to serialize policy state")
+    }
+
+    /// Rebuilds a policy from a `snapshot`. Events are not part of the state, recording starts disabled.
+    fn restore(state: &[u8]) -> Self where Self : DeserializeOwned {
+        bincode::deserialize(state).expect("Failed to deserialize policy state")
+    }
+
+    /// Applies a request according to its operation. Returns whether it was a read that hit.
+    ///
+    /// Writes are allocated in the cache, callers simulating write-around send them as deletes instead.
+    fn request(&mut self, file: FileRecord<T>) -> bool {
+        self.events().set_time(file.time);
+        match file.op {
+            Operation::Get => return self.simulate(file),
+            Operation::Set => self.admit(file),
+            Operation::Update => { self.resize(&file); }
+            Operation::Delete => { self.invalidate(&file.label); }
+        }
+        false
+    }
+}
+
+/// Buffer of the events a policy has recorded since it was last drained.
+///
+/// Nothing is recorded until `enable` is called, so runs that do not look at events pay one branch per event.
+/// Evictions are always counted, and are the only part kept in a snapshot.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct EventLog<T> {
+    #[serde(skip)]
+    events: Option<Vec<Event<T>>>,
+    #[serde(skip)]
+    time: u64, // time of the request being handled
+    evictions: u64,
+}
+
+impl<T> EventLog<T> {
+    pub fn new() -> Self {
+        EventLog {
+            events: None,
+            time: 0,
+            evictions: 0
+        }
+    }
+
+    pub fn enable(&mut self) {
+        if self.events.is_none() {
+            self.events = Some(vec![]);
+        }
+    }
+
+    pub fn set_time(&mut self, time: u64) {
+        self.time = time;
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Takes every event recorded so far, in the order they happened.
+    pub fn drain(&mut self) -> Vec<Event<T>> {
+        match self.events.as_mut() {
+            Some(events) => std::mem::take(events),
+            None => vec![]
+        }
+    }
+}
+
+impl<T> EventLog<T> where T : Clone {
+    pub fn record(&mut self, kind: EventKind, label: &T, size: i64) {
+        if kind == EventKind::Evict {
+            self.evictions += 1;
+        }
+        if let Some(events) = self.events.as_mut() {
+            events.push(Event {
+                kind,
+                label: label.clone(),
+                size,
+                time: self.time
+            });
+        }
+    }
+}
+
+impl<T> Default for EventLog<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// splitmix64 generator for policies that sample or evict at random, seeded so runs are repeatable.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        mix64(self.0)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform below `n`, which must not be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// 64-bit FNV-1a hash of a key with a splitmix64 finalizer, stable across runs and the same on the
+/// host and in 32-bit guests. Used for sketches, sampling and routing.
+pub fn hash64<T>(label: &T) -> u64 where T : Hash {
+    let mut hasher = Fnv1a(0xcbf29ce484222325);
+    label.hash(&mut hasher);
+    mix64(hasher.finish())
+}
+
+/// splitmix64 finalizer, spreads a hash evenly over the low bits.
+pub fn mix64(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+struct Fnv1a(u64);
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // Lengths of byte string keys are hashed as usize, widened so guests hash them like the host
+    fn write_usize(&mut self, i: usize) {
+        self.write(&(i as u64).to_le_bytes());
+    }
+}
+
+/// A tunable parameter of a policy, such as a segment ratio or a seed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Param {
+    Int(i64),
+    Float(f64),
+}
+
+impl FromStr for Param {
+    type Err = String;
+
+    // An integer unless the value only parses as a float
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match (i64::from_str(s), f64::from_str(s)) {
+            (Ok(value), _) => Ok(Param::Int(value)),
+            (_, Ok(value)) => Ok(Param::Float(value)),
+            _ => Err(format!("Invalid parameter value: {}", s))
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Param::Int(value) => write!(f, "{}", value),
+            Param::Float(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Named parameters a policy is built with, passed to every policy of a run.
+///
+/// Policies read the names they know with a default and ignore the rest, so an empty map gives
+/// every policy its usual configuration. Callers catch misspelled names with `check`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Params(BTreeMap<String, Param>);
+
+impl Params {
+    pub fn new() -> Self {
+        Params(BTreeMap::new())
+    }
+
+    pub fn set(&mut self, name: &str, value: Param) {
+        self.0.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Param> {
+        self.0.get(name).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The parameters among `names`, the others left out.
+    pub fn only(&self, names: &[&str]) -> Params {
+        Params(self.0.iter().filter(|(name, _)| names.contains(&name.as_str())).map(|(name, value)| (name.clone(), *value)).collect())
+    }
+
+    /// Fails on the first name that is not in `accepted`, usually a policy's `CacheAlgorithm::PARAMS`.
+    pub fn check(&self, accepted: &[&str]) -> Result<(), String> {
+        match self.0.keys().find(|name| !accepted.contains(&name.as_str())) {
+            Some(name) if accepted.is_empty() => Err(format!("Unknown policy parameter {}, the policy takes none", name)),
+            Some(name) => Err(format!("Unknown policy parameter {}, expected one of {}", name, accepted.join(", "))),
+            None => Ok(())
+        }
+    }
+
+    /// Fails when the parameter is set outside `range`, an unset parameter takes the policy's default.
+    pub fn check_range(&self, name: &str, range: RangeInclusive<f64>) -> Result<(), String> {
+        match self.get(name) {
+            Some(_) if !range.contains(&self.float(name, 0.0)) => {
+                Err(format!("Policy parameter {} must be between {} and {}, not {}", name, range.start(), range.end(), self.float(name, 0.0)))
+            }
+            _ => Ok(())
+        }
+    }
+
+    /// The parameter as an integer, floats are truncated.
+    pub fn int(&self, name: &str, default: i64) -> i64 {
+        match self.get(name) {
+            Some(Param::Int(value)) => value,
+            Some(Param::Float(value)) => value as i64,
+            None => default
+        }
+    }
+
+    pub fn float(&self, name: &str, default: f64) -> f64 {
+        match self.get(name) {
+            Some(Param::Int(value)) => value as f64,
+            Some(Param::Float(value)) => value,
+            None => default
+        }
+    }
+}
+
+impl FromStr for Params {
+    type Err = String;
+
+    // NAME=VALUE separated by commas
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = Params::new();
+        for param in s.split(',').filter(|param| !param.is_empty()) {
+            let invalid = || format!("Invalid policy parameter: {}", param);
+            let (name, value) = match param.find('=') {
+                Some(i) => (param[..i].trim(), param[i + 1..].trim()),
+                None => return Err(invalid())
+            };
+            if name.is_empty() {
+                return Err(invalid());
+            }
+            params.set(name, Param::from_str(value).map_err(|_| invalid())?);
+        }
+        Ok(params)
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self.0.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        write!(f, "{}", params.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_set_parameters_against_their_range() {
+        let params = Params::from_str("ratio=1.5,share=0.5").unwrap();
+        assert!(params.check_range("ratio", 0.0..=1.0).is_err());
+        assert!(params.check_range("share", 0.0..=1.0).is_ok());
+        assert!(params.check_range("unset", 0.0..=1.0).is_ok());
+        assert!(Params::from_str("ratio=-1").unwrap().check_range("ratio", 0.0..=f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        // Sketches, samples and routes must not change between runs, builds or guests
+        assert_eq!(hash64(&42u64), 16239707572940805204);
+        assert_eq!(hash64(&b"object".to_vec()), 11014597603519035685);
+        assert_eq!(mix64(0), 0);
+    }
+
+    #[test]
+    fn usize_hashes_like_u64() {
+        assert_eq!(hash64(&7usize), hash64(&7u64));
+        assert_eq!(hash64(&(3usize, 4usize)), hash64(&(3u64, 4u64)));
+    }
+
+    #[test]
+    fn low_bits_are_spread_evenly() {
+        let mut buckets = [0; 16];
+        for label in 0..16000u64 {
+            buckets[(hash64(&label) % 16) as usize] += 1;
+        }
+        assert!(buckets.iter().all(|&count| (800..1200).contains(&count)), "{:?}", buckets);
+    }
+}
@@ -0,0 +1,112 @@
+// Checks every policy against the parts of the CacheAlgorithm contract they all share. What sets a
+// policy apart is tested in its own crate.
+use algorithm::{CacheAlgorithm, Param, Params};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use simulator_shared_types::FileRecord;
+
+fn get(label: i32, size: i64) -> FileRecord<i32> {
+    FileRecord { label, size, ..Default::default() }
+}
+
+fn accounts_bytes_across_resize_and_invalidate<A>() where A : CacheAlgorithm<i32> {
+    let mut cache = A::new(100);
+    cache.request(get(1, 10));
+    cache.request(get(2, 20));
+    assert_eq!(cache.used(), 30);
+    assert!(cache.resize(&get(1, 15)));
+    assert_eq!(cache.used(), 35);
+    assert!(cache.invalidate(&2));
+    assert!(!cache.invalidate(&2));
+    assert!(!cache.resize(&get(2, 5)));
+    assert_eq!(cache.used(), 15);
+}
+
+fn stays_within_its_size<A>() where A : CacheAlgorithm<i32> {
+    let mut cache = A::new(50);
+    for i in 0..1000 {
+        cache.request(get(i * 13 % 97, 1 + (i % 5) as i64));
+        assert!(cache.used() <= 50);
+    }
+    assert!(cache.filled());
+}
+
+fn admits_without_counting_a_request<A>() where A : CacheAlgorithm<i32> {
+    let mut cache = A::new(100);
+    cache.admit(get(1, 10));
+    assert!(cache.contains(&1));
+    assert_eq!(cache.stats(), (0, 0));
+    assert!(cache.request(get(1, 10)));
+    assert_eq!(cache.stats(), (1, 1));
+}
+
+fn restores_from_a_snapshot<A>() where A : CacheAlgorithm<i32> + Serialize + DeserializeOwned {
+    let mut cache = A::new(50);
+    for i in 0..500 {
+        cache.request(get(i * 13 % 97, 1));
+    }
+    let mut restored = A::restore(&cache.snapshot());
+    assert_eq!(restored.stats(), cache.stats());
+    for i in 0..500 {
+        let file = get(i * 7 % 89, 1);
+        assert_eq!(restored.request(file), cache.request(file));
+    }
+}
+
+// Values a policy accepts must not break it, out of range ones are for `check_params` to turn away
+fn works_with_every_accepted_parameter<A>() where A : CacheAlgorithm<i32> {
+    let mut unknown = Params::new();
+    unknown.set("unknown", Param::Int(1));
+    assert!(A::check_params(&unknown).is_err());
+    for name in A::PARAMS {
+        for value in [-0.5, 0.0, 0.5, 1.0, 1.5, 4.0].iter() {
+            let mut params = Params::new();
+            params.set(name, Param::Float(*value));
+            if A::check_params(&params).is_err() {
+                continue;
+            }
+            let mut cache = A::with_params(50, &params);
+            for i in 0..1000 {
+                cache.request(get(i * 13 % 97, 1 + (i % 5) as i64));
+                assert!(cache.used() <= 50, "{}={} overfills the cache", name, value);
+            }
+        }
+    }
+}
+
+macro_rules! check_policies {
+    ($($name:ident: $policy:ty),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                accounts_bytes_across_resize_and_invalidate::<$policy>();
+                stays_within_its_size::<$policy>();
+                admits_without_counting_a_request::<$policy>();
+                restores_from_a_snapshot::<$policy>();
+                works_with_every_accepted_parameter::<$policy>();
+            }
+        )*
+    }
+}
+
+check_policies! {
+    fifo: fifo::FiFo<i32>,
+    gdsize: gdsize::GdSize<i32>,
+    lfu: lfu::LFU<i32>,
+    lru: lru::LRU<i32>,
+    wtinylfu: wtinylfu::WTinyLFU<i32>,
+    s3fifo: s3fifo::S3Fifo<i32>,
+    sieve: sieve::Sieve<i32>,
+    lirs: lirs::Lirs<i32>,
+    clockpro: clockpro::ClockPro<i32>,
+    clock: clock::Clock<i32>,
+    slru: slru::Slru<i32>,
+    twoq: twoq::TwoQ<i32>,
+    mq: mq::MQ<i32>,
+    lfuda: lfuda::LFUDA<i32>,
+    lruk: lruk::LRUK<i32>,
+    hyperbolic: hyperbolic::Hyperbolic<i32>,
+    lhd: lhd::Lhd<i32>,
+    random: random::Random<i32>,
+    fifo_reinsertion: fifo_reinsertion::FifoReinsertion<i32>,
+}
@@ -0,0 +1,319 @@
+//! Exports shared by the WASM policy modules, one macro per way of passing requests to the guest.
+//!
+//! Each module is its own sandbox, so the statics below exist once per module.
+//!
+//! The key type of the exported policy is picked at build time, `i32` by default, `u64` with the
+//! `u64_keys` feature and byte strings with `byte_keys`. Byte strings only fit the bincode encoding.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use simulator_shared_types::{Event, WireKey};
+#[cfg(not(feature = "byte_keys"))]
+use simulator_shared_types::{FileRecord, WireRecord};
+
+pub use admission;
+pub use algorithm;
+pub use once_cell;
+pub use packed_i32;
+pub use simulator_shared_types;
+
+#[cfg(all(feature = "u64_keys", feature = "byte_keys"))]
+compile_error!("Only one of the u64_keys and byte_keys features can be enabled");
+
+/// Object key of the exported policy.
+#[cfg(not(any(feature = "u64_keys", feature = "byte_keys")))]
+pub type Key = i32;
+
+/// Object key of the exported policy.
+#[cfg(feature = "u64_keys")]
+pub type Key = u64;
+
+/// Object key of the exported policy.
+#[cfg(feature = "byte_keys")]
+pub type Key = Vec<u8>;
+
+/// Keys that fit the fixed width pair and bytemuck encodings.
+pub trait FixedWidthKey : WireKey {}
+
+impl FixedWidthKey for i32 {}
+
+impl FixedWidthKey for u64 {}
+
+static BUFFERS : once_cell::sync::Lazy<std::sync::Mutex<Vec<Box<[u8]>>>> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(vec![]));
+
+/// Hands out a buffer for the host to write a serialized value into, packed as `(ptr, len)`.
+pub fn alloc_buffer(size: i32) -> i64 {
+    let buffer = Vec::<u8>::with_capacity(size as usize).into_boxed_slice();
+    let ptr = buffer.as_ptr() as i32;
+    BUFFERS.lock().unwrap().push(buffer);
+    packed_i32::join_i32_to_i64(ptr, size )
+}
+
+/// Deserializes the value the host wrote into the last buffer from `alloc_buffer`.
+pub fn read_buffer<V : DeserializeOwned>(ptr: i32, buffer_size: i32) -> V {
+    let slice = unsafe {
+        // ptr as *const _ casts the i32 ptr to an actual pointer
+        std::slice::from_raw_parts(ptr as *const _, buffer_size as usize)
+        // from_raw_parts turns this data into a byte array we can use safely.
+    };
+    // Pop the buffer, but keep the memory allocated by assigning it to a variable.
+    let _buffer_would_be_dropped  = BUFFERS.lock().unwrap().pop();
+
+    // deserialize the slice.
+    bincode::deserialize(slice).expect("Deserialization error")
+}
+
+static OUTPUT : once_cell::sync::Lazy<std::sync::Mutex<Vec<u8>>> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(vec![]));
+
+static STATE : once_cell::sync::Lazy<std::sync::Mutex<Vec<u8>>> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(vec![]));
+
+/// Keeps bytes for the host to read, packed as `(ptr, len)`. Only valid until the next call.
+pub fn write_output(bytes: Vec<u8>) -> i64 {
+    let mut buffer = OUTPUT.lock().unwrap();
+    *buffer = bytes;
+    packed_i32::join_i32_to_i64(buffer.as_ptr() as i32, buffer.len() as i32)
+}
+
+/// Serializes drained events with bincode for the host to read, see `write_output`.
+///
+/// Events are always passed this way, whatever the encoding of requests.
+pub fn write_events<K : Serialize>(events: &[Event<K>]) -> i64 {
+    write_output(bincode::serialize(events).expect("Serialization error"))
+}
+
+/// Hands out a buffer of `size` bytes for the host to write a policy snapshot, admission filter or parameters into, packed as `(ptr, len)`.
+pub fn alloc_state(size: i32) -> i64 {
+    let mut buffer = STATE.lock().unwrap();
+    *buffer = vec![0; size as usize];
+    packed_i32::join_i32_to_i64(buffer.as_ptr() as i32, size)
+}
+
+/// Takes the snapshot the host wrote into the buffer from `alloc_state`.
+pub fn take_state() -> Vec<u8> {
+    std::mem::take(&mut *STATE.lock().unwrap())
+}
+
+/// Takes the admission filter the host wrote into the buffer from `alloc_state`, serialized with bincode.
+pub fn take_admission() -> admission::Admission {
+    bincode::deserialize(&take_state()).expect("Deserialization error")
+}
+
+/// Takes the policy parameters the host wrote into the buffer from `alloc_state`, serialized with
+/// bincode. The defaults when the host wrote nothing.
+pub fn take_params() -> algorithm::Params {
+    let state = take_state();
+    if state.is_empty() {
+        return algorithm::Params::new();
+    }
+    bincode::deserialize(&state).expect("Deserialization error")
+}
+
+// Aligned so bytemuck can view it as a record in place
+#[cfg(not(feature = "byte_keys"))]
+#[repr(C, align(8))]
+struct RecordBuffer([u8; std::mem::size_of::<WireRecord>()]);
+
+#[cfg(not(feature = "byte_keys"))]
+static RECORD : once_cell::sync::Lazy<std::sync::Mutex<RecordBuffer>> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(RecordBuffer([0; std::mem::size_of::<WireRecord>()])));
+
+/// Address of the single record sized buffer the host writes every request into.
+#[cfg(not(feature = "byte_keys"))]
+pub fn record_buffer() -> i32 {
+    // The buffer lives inside the static, so its address stays valid after the lock is released
+    RECORD.lock().unwrap().0.as_ptr() as i32
+}
+
+/// Reads the record the host last wrote into `record_buffer`.
+#[cfg(not(feature = "byte_keys"))]
+pub fn read_record() -> FileRecord<Key> {
+    // Dont need the ptr/buffersize, data can only be in one spot
+    let wire = *bytemuck::from_bytes::<WireRecord>(&RECORD.lock().unwrap().0);
+    std::convert::TryFrom::try_from(wire).expect("Invalid record")
+}
+
+/// Fails the build of a fixed width module when the key type does not fit the encoding.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! assert_fixed_width_key {
+    () => {
+        const _: fn() = || {
+            fn fixed_width<K : $crate::FixedWidthKey>() {}
+            fixed_width::<$crate::Key>();
+        };
+    };
+}
+
+/// Defines the `POLICY` static every export works on, the policy behind an admission filter.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! policy_static {
+    ($policy:ident) => {
+        type Policy = $crate::admission::Admitted<$policy<$crate::Key>, $crate::Key>;
+
+        static POLICY : $crate::once_cell::sync::Lazy<std::sync::Mutex<Option<Policy>>> = $crate::once_cell::sync::Lazy::new(||{
+            std::sync::Mutex::new(None)
+        });
+
+        // Builds the policy with the parameters the host wrote into the `alloc_state` buffer, if any
+        #[no_mangle]
+        pub fn init(size: i64){
+            *POLICY.lock().unwrap() = Some(<Policy as $crate::algorithm::CacheAlgorithm<$crate::Key>>::with_params(size, &$crate::take_params()));
+        }
+
+        // Sets the admission filter the host wrote into the `alloc_state` buffer
+        #[no_mangle]
+        pub fn set_admission() {
+            POLICY.lock().unwrap().as_mut().unwrap().set_admission($crate::take_admission());
+        }
+
+        #[no_mangle]
+        pub fn stats() -> i64 {
+            let stats = $crate::algorithm::CacheAlgorithm::stats(POLICY.lock().unwrap().as_ref().unwrap());
+            $crate::packed_i32::join_i32_to_i64(stats.0, stats.1)
+        }
+
+        #[no_mangle]
+        pub fn reset_stats() {
+            $crate::algorithm::CacheAlgorithm::reset_stats(POLICY.lock().unwrap().as_mut().unwrap())
+        }
+
+        #[no_mangle]
+        pub fn used() -> i64 {
+            $crate::algorithm::CacheAlgorithm::used(POLICY.lock().unwrap().as_ref().unwrap())
+        }
+
+        #[no_mangle]
+        pub fn filled() -> i32 {
+            $crate::algorithm::CacheAlgorithm::filled(POLICY.lock().unwrap().as_mut().unwrap()) as i32
+        }
+
+        #[no_mangle]
+        pub fn enable_events() {
+            $crate::algorithm::CacheAlgorithm::events(POLICY.lock().unwrap().as_mut().unwrap()).enable()
+        }
+
+        #[no_mangle]
+        pub fn drain_events() -> i64 {
+            let events = $crate::algorithm::CacheAlgorithm::events(POLICY.lock().unwrap().as_mut().unwrap()).drain();
+            $crate::write_events(&events)
+        }
+
+        #[no_mangle]
+        pub fn snapshot() -> i64 {
+            let state = $crate::algorithm::CacheAlgorithm::snapshot(POLICY.lock().unwrap().as_ref().unwrap());
+            $crate::write_output(state)
+        }
+
+        #[no_mangle]
+        pub fn alloc_state(size: i32) -> i64 {
+            $crate::alloc_state(size)
+        }
+
+        // Replaces the policy with the snapshot the host wrote into the `alloc_state` buffer
+        #[no_mangle]
+        pub fn restore() {
+            let state = $crate::take_state();
+            *POLICY.lock().unwrap() = Some(<Policy as $crate::algorithm::CacheAlgorithm<$crate::Key>>::restore(&state));
+        }
+    };
+}
+
+/// Exports a policy that takes requests as `label, size, op, time, ttl, cost` arguments.
+#[macro_export]
+macro_rules! pair_policy {
+    ($policy:ident) => {
+        $crate::policy_static!($policy);
+        $crate::assert_fixed_width_key!();
+
+        #[no_mangle]
+        pub fn send(label: $crate::Key, size : i64, op : i32, time : i64, ttl : i64, cost : i64) -> i32 {
+            $crate::algorithm::CacheAlgorithm::request(POLICY.lock().unwrap().as_mut().unwrap(), $crate::simulator_shared_types::FileRecord::<$crate::Key>{
+                label,
+                size,
+                op: <$crate::simulator_shared_types::Operation as std::convert::TryFrom<i32>>::try_from(op).expect("Invalid operation"),
+                time: time as u64,
+                ttl: ttl as u64,
+                cost: cost as u64
+            }) as i32
+        }
+
+        #[no_mangle]
+        pub fn resize(label: $crate::Key, size : i64) -> i32 {
+            $crate::algorithm::CacheAlgorithm::resize(POLICY.lock().unwrap().as_mut().unwrap(), &$crate::simulator_shared_types::FileRecord::<$crate::Key>{
+                label,
+                size,
+                op: $crate::simulator_shared_types::Operation::Update,
+                ..Default::default()
+            }) as i32
+        }
+
+        #[no_mangle]
+        pub fn invalidate(label: $crate::Key) -> i32 {
+            $crate::algorithm::CacheAlgorithm::invalidate(POLICY.lock().unwrap().as_mut().unwrap(), &label) as i32
+        }
+    };
+}
+
+/// Exports a policy that takes requests serialized with bincode into buffers handed out by `alloc`.
+#[macro_export]
+macro_rules! bincode_policy {
+    ($policy:ident) => {
+        $crate::policy_static!($policy);
+
+        #[no_mangle]
+        pub fn alloc(size: i32) -> i64 {
+            $crate::alloc_buffer(size)
+        }
+
+        #[no_mangle]
+        pub fn send(ptr: i32, buffer_size: i32) -> i32 {
+            let x : $crate::simulator_shared_types::FileRecord<$crate::Key> = $crate::read_buffer(ptr, buffer_size);
+            $crate::algorithm::CacheAlgorithm::request(POLICY.lock().unwrap().as_mut().unwrap(), x) as i32
+        }
+
+        #[no_mangle]
+        pub fn resize(ptr: i32, buffer_size: i32) -> i32 {
+            let x : $crate::simulator_shared_types::FileRecord<$crate::Key> = $crate::read_buffer(ptr, buffer_size);
+            $crate::algorithm::CacheAlgorithm::resize(POLICY.lock().unwrap().as_mut().unwrap(), &x) as i32
+        }
+
+        #[no_mangle]
+        pub fn invalidate(ptr: i32, buffer_size: i32) -> i32 {
+            let label : $crate::Key = $crate::read_buffer(ptr, buffer_size);
+            $crate::algorithm::CacheAlgorithm::invalidate(POLICY.lock().unwrap().as_mut().unwrap(), &label) as i32
+        }
+    };
+}
+
+/// Exports a policy that reads requests as raw bytes from one fixed buffer, see `record_buffer`.
+#[macro_export]
+macro_rules! bytemuck_policy {
+    ($policy:ident) => {
+        $crate::policy_static!($policy);
+        $crate::assert_fixed_width_key!();
+
+        #[no_mangle]
+        pub fn alloc(size: i32) -> i64 {
+            $crate::packed_i32::join_i32_to_i64($crate::record_buffer(), size)
+        }
+
+        #[no_mangle]
+        pub fn send(_ptr: i32, _buffer_size: i32) -> i32 {
+            let x = $crate::read_record();
+            $crate::algorithm::CacheAlgorithm::request(POLICY.lock().unwrap().as_mut().unwrap(), x) as i32
+        }
+
+        #[no_mangle]
+        pub fn resize(_ptr: i32, _buffer_size: i32) -> i32 {
+            let x = $crate::read_record();
+            $crate::algorithm::CacheAlgorithm::resize(POLICY.lock().unwrap().as_mut().unwrap(), &x) as i32
+        }
+
+        // Only the label of the record is used
+        #[no_mangle]
+        pub fn invalidate(_ptr: i32, _buffer_size: i32) -> i32 {
+            let x = $crate::read_record();
+            $crate::algorithm::CacheAlgorithm::invalidate(POLICY.lock().unwrap().as_mut().unwrap(), &x.label) as i32
+        }
+    };
+}
@@ -0,0 +1,3 @@
+use clock::Clock;
+
+policy_exports::bincode_policy!(Clock);
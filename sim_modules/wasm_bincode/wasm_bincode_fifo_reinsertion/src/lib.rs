@@ -0,0 +1,3 @@
+use fifo_reinsertion::FifoReinsertion;
+
+policy_exports::bincode_policy!(FifoReinsertion);
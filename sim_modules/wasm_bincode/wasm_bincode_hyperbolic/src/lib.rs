@@ -0,0 +1,3 @@
+use hyperbolic::Hyperbolic;
+
+policy_exports::bincode_policy!(Hyperbolic);
@@ -0,0 +1,3 @@
+use lirs::Lirs;
+
+policy_exports::bincode_policy!(Lirs);
@@ -0,0 +1,3 @@
+use lruk::LRUK;
+
+policy_exports::bincode_policy!(LRUK);
@@ -0,0 +1,3 @@
+use wtinylfu::WTinyLFU;
+
+policy_exports::bincode_policy!(WTinyLFU);
@@ -0,0 +1,3 @@
+use clockpro::ClockPro;
+
+policy_exports::bytemuck_policy!(ClockPro);
@@ -0,0 +1,3 @@
+use fifo_reinsertion::FifoReinsertion;
+
+policy_exports::bytemuck_policy!(FifoReinsertion);
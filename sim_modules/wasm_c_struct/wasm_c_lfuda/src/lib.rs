@@ -0,0 +1,3 @@
+use lfuda::LFUDA;
+
+policy_exports::bytemuck_policy!(LFUDA);
@@ -0,0 +1,3 @@
+use mq::MQ;
+
+policy_exports::bytemuck_policy!(MQ);
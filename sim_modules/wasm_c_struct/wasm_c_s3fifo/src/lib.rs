@@ -0,0 +1,3 @@
+use s3fifo::S3Fifo;
+
+policy_exports::bytemuck_policy!(S3Fifo);
@@ -0,0 +1,3 @@
+use sieve::Sieve;
+
+policy_exports::bytemuck_policy!(Sieve);
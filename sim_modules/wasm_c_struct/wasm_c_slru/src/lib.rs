@@ -0,0 +1,3 @@
+use slru::Slru;
+
+policy_exports::bytemuck_policy!(Slru);
@@ -0,0 +1,3 @@
+use twoq::TwoQ;
+
+policy_exports::bytemuck_policy!(TwoQ);
@@ -0,0 +1,3 @@
+use wtinylfu::WTinyLFU;
+
+policy_exports::bytemuck_policy!(WTinyLFU);
@@ -0,0 +1,3 @@
+use clock::Clock;
+
+policy_exports::pair_policy!(Clock);
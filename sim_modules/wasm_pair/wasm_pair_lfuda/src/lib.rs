@@ -0,0 +1,3 @@
+use lfuda::LFUDA;
+
+policy_exports::pair_policy!(LFUDA);
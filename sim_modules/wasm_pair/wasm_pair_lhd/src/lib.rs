@@ -0,0 +1,3 @@
+use lhd::Lhd;
+
+policy_exports::pair_policy!(Lhd);
@@ -0,0 +1,3 @@
+use lirs::Lirs;
+
+policy_exports::pair_policy!(Lirs);
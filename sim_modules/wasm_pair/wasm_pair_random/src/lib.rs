@@ -0,0 +1,3 @@
+use random::Random;
+
+policy_exports::pair_policy!(Random);
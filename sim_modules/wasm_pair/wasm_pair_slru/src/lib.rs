@@ -0,0 +1,3 @@
+use slru::Slru;
+
+policy_exports::pair_policy!(Slru);
@@ -0,0 +1,3 @@
+use wtinylfu::WTinyLFU;
+
+policy_exports::pair_policy!(WTinyLFU);
@@ -13,6 +13,21 @@ fn main(){
         "sim_modules/wasm_bincode/wasm_bincode_gdsize","sim_modules/wasm_c_struct/wasm_c_gdsize", "sim_modules/wasm_pair/wasm_pair_gdsize",
         "sim_modules/wasm_bincode/wasm_bincode_lfu","sim_modules/wasm_c_struct/wasm_c_lfu", "sim_modules/wasm_pair/wasm_pair_lfu",
         "sim_modules/wasm_bincode/wasm_bincode_lru","sim_modules/wasm_c_struct/wasm_c_lru", "sim_modules/wasm_pair/wasm_pair_lru",
+        "sim_modules/wasm_bincode/wasm_bincode_wtinylfu","sim_modules/wasm_c_struct/wasm_c_wtinylfu", "sim_modules/wasm_pair/wasm_pair_wtinylfu",
+        "sim_modules/wasm_bincode/wasm_bincode_s3fifo","sim_modules/wasm_c_struct/wasm_c_s3fifo", "sim_modules/wasm_pair/wasm_pair_s3fifo",
+        "sim_modules/wasm_bincode/wasm_bincode_sieve","sim_modules/wasm_c_struct/wasm_c_sieve", "sim_modules/wasm_pair/wasm_pair_sieve",
+        "sim_modules/wasm_bincode/wasm_bincode_lirs","sim_modules/wasm_c_struct/wasm_c_lirs", "sim_modules/wasm_pair/wasm_pair_lirs",
+        "sim_modules/wasm_bincode/wasm_bincode_clockpro","sim_modules/wasm_c_struct/wasm_c_clockpro", "sim_modules/wasm_pair/wasm_pair_clockpro",
+        "sim_modules/wasm_bincode/wasm_bincode_clock","sim_modules/wasm_c_struct/wasm_c_clock", "sim_modules/wasm_pair/wasm_pair_clock",
+        "sim_modules/wasm_bincode/wasm_bincode_slru","sim_modules/wasm_c_struct/wasm_c_slru", "sim_modules/wasm_pair/wasm_pair_slru",
+        "sim_modules/wasm_bincode/wasm_bincode_twoq","sim_modules/wasm_c_struct/wasm_c_twoq", "sim_modules/wasm_pair/wasm_pair_twoq",
+        "sim_modules/wasm_bincode/wasm_bincode_mq","sim_modules/wasm_c_struct/wasm_c_mq", "sim_modules/wasm_pair/wasm_pair_mq",
+        "sim_modules/wasm_bincode/wasm_bincode_lfuda","sim_modules/wasm_c_struct/wasm_c_lfuda", "sim_modules/wasm_pair/wasm_pair_lfuda",
+        "sim_modules/wasm_bincode/wasm_bincode_lruk","sim_modules/wasm_c_struct/wasm_c_lruk", "sim_modules/wasm_pair/wasm_pair_lruk",
+        "sim_modules/wasm_bincode/wasm_bincode_hyperbolic","sim_modules/wasm_c_struct/wasm_c_hyperbolic", "sim_modules/wasm_pair/wasm_pair_hyperbolic",
+        "sim_modules/wasm_bincode/wasm_bincode_lhd","sim_modules/wasm_c_struct/wasm_c_lhd", "sim_modules/wasm_pair/wasm_pair_lhd",
+        "sim_modules/wasm_bincode/wasm_bincode_random","sim_modules/wasm_c_struct/wasm_c_random", "sim_modules/wasm_pair/wasm_pair_random",
+        "sim_modules/wasm_bincode/wasm_bincode_fifo_reinsertion","sim_modules/wasm_c_struct/wasm_c_fifo_reinsertion", "sim_modules/wasm_pair/wasm_pair_fifo_reinsertion",
     ];
 
     for module in &modules {
@@ -22,13 +37,27 @@ fn main(){
 
 
     for module in modules {
-        let _result = Command::new("cargo")
-            .args(&["build",format!("--target={}",WASM_UNKNOWN).as_str(),"--target-dir=../../../modules","--release"])
-            .current_dir(format!("../{}",module))
-            .status().unwrap();
+        // Default i32 keys go straight in modules/, other key types in a directory named after their feature
+        let mut builds = vec![("../../../modules", None), ("../../../modules/u64_keys", Some("u64_keys"))];
+        if module.contains("wasm_bincode") {
+            // Byte string keys only fit the bincode encoding
+            builds.push(("../../../modules/byte_keys", Some("byte_keys")));
+        }
+
+        for (target_dir, feature) in builds {
+            let mut args = vec!["build".to_string(), format!("--target={}",WASM_UNKNOWN), format!("--target-dir={}",target_dir), "--release".to_string()];
+            if let Some(feature) = feature {
+                args.push(format!("--features={}", feature));
+            }
+
+            let _result = Command::new("cargo")
+                .args(&args)
+                .current_dir(format!("../{}",module))
+                .status().unwrap();
 
-        if _result.code().unwrap() != 0 {
-            panic!("Compilation error for module: {}", module)
+            if _result.code().unwrap() != 0 {
+                panic!("Compilation error for module: {} ({})", module, feature.unwrap_or("i32 keys"))
+            }
         }
 
     }
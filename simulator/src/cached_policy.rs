@@ -1,7 +1,12 @@
 use wasmer::{Function, Instance, Memory, Module, Val, imports};
 use ouroboros::self_referencing;
-use simulator_shared_types::FileRecord;
-use crate::policy::PolicyModule;
+use admission::Admission;
+use algorithm::Params;
+use simulator_shared_types::{Event, FileRecord, Operation, WireRecord};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::keys::FixedWidthKey;
+use crate::policy::{self, PolicyModule};
 
 #[self_referencing]
 pub struct WasmCachedBincodePolicyModule{
@@ -16,6 +21,10 @@ pub struct WasmCachedBincodePolicyModule{
     init: &'this Function,
     #[borrows(module)]
     stats: &'this Function,
+    #[borrows(module)]
+    resize: &'this Function,
+    #[borrows(module)]
+    invalidate: &'this Function,
 }
 
 #[self_referencing]
@@ -27,6 +36,10 @@ pub struct WasmCachedPairPolicyModule{
     init: &'this Function,
     #[borrows(module)]
     stats: &'this Function,
+    #[borrows(module)]
+    resize: &'this Function,
+    #[borrows(module)]
+    invalidate: &'this Function,
 }
 
 #[self_referencing]
@@ -44,6 +57,10 @@ pub struct WasmCachedBytemuckPolicyModule{
     init: &'this Function,
     #[borrows(module)]
     stats: &'this Function,
+    #[borrows(module)]
+    resize: &'this Function,
+    #[borrows(module)]
+    invalidate: &'this Function,
 }
 
 impl WasmCachedPairPolicyModule {
@@ -61,17 +78,25 @@ impl WasmCachedPairPolicyModule {
             send_builder: |module: &Instance| module.exports.get_function("send").unwrap(),
             init_builder: |module: &Instance| module.exports.get_function("init").unwrap(),
             stats_builder: |module: &Instance| module.exports.get_function("stats").unwrap(),
+            resize_builder: |module: &Instance| module.exports.get_function("resize").unwrap(),
+            invalidate_builder: |module: &Instance| module.exports.get_function("invalidate").unwrap(),
         };
         out.build()
     }
 }
 
-impl PolicyModule<i32> for WasmCachedPairPolicyModule {
-    fn initialize(&mut self, cache_size: i64) {
+impl<K> PolicyModule<K> for WasmCachedPairPolicyModule where K : FixedWidthKey {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+        policy::write_guest_params(self.borrow_module(), params);
         self.borrow_init().call(&[Val::I64(cache_size)]).unwrap();
     }
-    fn send_request(&mut self, request : FileRecord<i32>){
-        self.borrow_send().call(&[Val::I32(request.label),Val::I64(request.size)]).unwrap();
+
+    fn set_admission(&mut self, admission: Admission) {
+        policy::set_guest_admission(self.borrow_module(), admission)
+    }
+    fn send_request(&mut self, request: FileRecord<K>) -> bool {
+        let result = self.borrow_send().call(&[request.label.to_val(),Val::I64(request.size),Val::I32(request.op as i32),Val::I64(request.time as i64),Val::I64(request.ttl as i64),Val::I64(request.cost as i64)]).unwrap();
+        result[0].unwrap_i32() != 0
     }
 
     fn stats(&self) -> (i32, i32) {
@@ -79,6 +104,44 @@ impl PolicyModule<i32> for WasmCachedPairPolicyModule {
         let packed = result[0].i64().unwrap();
         packed_i32::split_i64_to_i32(packed)
     }
+
+    fn resize(&mut self, file: FileRecord<K>) -> bool {
+        let result = self.borrow_resize().call(&[file.label.to_val(),Val::I64(file.size)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn invalidate(&mut self, label: K) -> bool {
+        let result = self.borrow_invalidate().call(&[label.to_val()]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn reset_stats(&mut self) {
+        policy::reset_guest_stats(self.borrow_module())
+    }
+
+    fn used(&self) -> i64 {
+        policy::guest_used(self.borrow_module())
+    }
+
+    fn filled(&mut self) -> bool {
+        policy::guest_filled(self.borrow_module())
+    }
+
+    fn enable_events(&mut self) {
+        policy::enable_guest_events(self.borrow_module())
+    }
+
+    fn events(&mut self) -> Vec<Event<K>> {
+        policy::drain_guest_events(self.borrow_module())
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        policy::snapshot_guest(self.borrow_module())
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        policy::restore_guest(self.borrow_module(), state)
+    }
 }
 
 impl WasmCachedBincodePolicyModule {
@@ -88,6 +151,14 @@ impl WasmCachedBincodePolicyModule {
         packed_i32::split_i64_to_i32(merged)
     }
 
+    // Copies a value into a fresh guest buffer, returning where it was written
+    fn write<V : serde::Serialize>(&self, value: &V) -> (i32,i32) {
+        let buffer_size = bincode::serialized_size(value).expect("Could not calculate buffer size") as i32;
+        let (ptr, len) = self.alloc(buffer_size);
+        runtime::write_bincode_to_wasm_memory(value, self.borrow_mem(), ptr as usize, len as usize);
+        (ptr, len)
+    }
+
     pub fn from_module(module : Module) -> Self {
         let import_objects = imports!{};
         // Create new sandbox
@@ -108,7 +179,9 @@ impl WasmCachedBincodePolicyModule {
             alloc_builder: |module: &Instance| module.exports.get_function("alloc").unwrap(),
             send_builder: |module: &Instance| module.exports.get_function("send").unwrap(),
             init_builder: |module: &Instance| module.exports.get_function("init").unwrap(),
-            stats_builder: |module: &Instance| module.exports.get_function("stats").unwrap()
+            stats_builder: |module: &Instance| module.exports.get_function("stats").unwrap(),
+            resize_builder: |module: &Instance| module.exports.get_function("resize").unwrap(),
+            invalidate_builder: |module: &Instance| module.exports.get_function("invalidate").unwrap()
         };
 
         out.build()
@@ -116,11 +189,16 @@ impl WasmCachedBincodePolicyModule {
     }
 }
 
-impl PolicyModule<i32> for WasmCachedBincodePolicyModule {
-    fn initialize(&mut self, cache_size: i64) {
+impl<K> PolicyModule<K> for WasmCachedBincodePolicyModule where K : Serialize + DeserializeOwned {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+        policy::write_guest_params(self.borrow_module(), params);
         self.borrow_init().call(&[Val::I64(cache_size)]).unwrap();
     }
-    fn send_request(&mut self, request: FileRecord<i32>) {
+
+    fn set_admission(&mut self, admission: Admission) {
+        policy::set_guest_admission(self.borrow_module(), admission)
+    }
+    fn send_request(&mut self, request: FileRecord<K>) -> bool {
         let buffer_size = bincode::serialized_size(&request).expect("Could not calculate buffer size") as i32;
 
         let (ptr, len) = self.alloc(buffer_size);
@@ -137,7 +215,8 @@ impl PolicyModule<i32> for WasmCachedBincodePolicyModule {
         //         mem_array[ptr as usize + i as usize] = serialized_array[i as usize];
         //     }
         // }
-        self.borrow_send().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        let result = self.borrow_send().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        result[0].unwrap_i32() != 0
     }
 
     fn stats(&self) -> (i32, i32) {
@@ -145,10 +224,50 @@ impl PolicyModule<i32> for WasmCachedBincodePolicyModule {
         let packed = result[0].i64().unwrap();
         packed_i32::split_i64_to_i32(packed)
     }
+
+    fn resize(&mut self, file: FileRecord<K>) -> bool {
+        let (ptr, len) = self.write(&file);
+        let result = self.borrow_resize().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn invalidate(&mut self, label: K) -> bool {
+        let (ptr, len) = self.write(&label);
+        let result = self.borrow_invalidate().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn reset_stats(&mut self) {
+        policy::reset_guest_stats(self.borrow_module())
+    }
+
+    fn used(&self) -> i64 {
+        policy::guest_used(self.borrow_module())
+    }
+
+    fn filled(&mut self) -> bool {
+        policy::guest_filled(self.borrow_module())
+    }
+
+    fn enable_events(&mut self) {
+        policy::enable_guest_events(self.borrow_module())
+    }
+
+    fn events(&mut self) -> Vec<Event<K>> {
+        policy::drain_guest_events(self.borrow_module())
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        policy::snapshot_guest(self.borrow_module())
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        policy::restore_guest(self.borrow_module(), state)
+    }
 }
 
 impl WasmCachedBytemuckPolicyModule {
-    pub fn from_module(module : Module) -> Self {
+    pub fn from_module<K>(module : Module) -> Self where K : FixedWidthKey {
         let import_objects = imports!{};
         // Create new sandbox
         let module = Instance::new(&module, &import_objects).unwrap();
@@ -160,7 +279,7 @@ impl WasmCachedBytemuckPolicyModule {
         let init = module.exports.get_function("init").unwrap();
         let stats = module.exports.get_function("stats").unwrap();
 
-        let buffer_size = std::mem::size_of::<FileRecord<i32>>();
+        let buffer_size = std::mem::size_of::<WireRecord>();
 
         let (ptr, len) = {
             let results = alloc.call(&[Val::I32(buffer_size as i32)]).unwrap();
@@ -176,7 +295,9 @@ impl WasmCachedBytemuckPolicyModule {
             alloc_builder: |module: &Instance| module.exports.get_function("alloc").unwrap(),
             send_builder: |module: &Instance| module.exports.get_function("send").unwrap(),
             init_builder: |module: &Instance| module.exports.get_function("init").unwrap(),
-            stats_builder: |module: &Instance| module.exports.get_function("stats").unwrap()
+            stats_builder: |module: &Instance| module.exports.get_function("stats").unwrap(),
+            resize_builder: |module: &Instance| module.exports.get_function("resize").unwrap(),
+            invalidate_builder: |module: &Instance| module.exports.get_function("invalidate").unwrap()
         };
 
         out.build()
@@ -184,16 +305,22 @@ impl WasmCachedBytemuckPolicyModule {
     }
 }
 
-impl PolicyModule<i32> for WasmCachedBytemuckPolicyModule {
-    fn initialize(&mut self, cache_size: i64) {
+impl<K> PolicyModule<K> for WasmCachedBytemuckPolicyModule where K : FixedWidthKey {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+        policy::write_guest_params(self.borrow_module(), params);
         self.borrow_init().call(&[Val::I64(cache_size)]).unwrap();
     }
-    fn send_request(&mut self, request: FileRecord<i32>) {
+
+    fn set_admission(&mut self, admission: Admission) {
+        policy::set_guest_admission(self.borrow_module(), admission)
+    }
+    fn send_request(&mut self, request: FileRecord<K>) -> bool {
         let mem = self.borrow_mem();
         let ptr = self.borrow_ptr();
         let len = self.borrow_len();
-        runtime::write_bytemuck_to_wasm_memory(request, mem, *ptr, *len);
-        self.borrow_send().call(&[Val::I32(*ptr as i32), Val::I32(*len as i32)]).unwrap();
+        runtime::write_bytemuck_to_wasm_memory(WireRecord::from(request), mem, *ptr, *len);
+        let result = self.borrow_send().call(&[Val::I32(*ptr as i32), Val::I32(*len as i32)]).unwrap();
+        result[0].unwrap_i32() != 0
     }
 
     fn stats(&self) -> (i32, i32) {
@@ -201,4 +328,47 @@ impl PolicyModule<i32> for WasmCachedBytemuckPolicyModule {
         let packed = result[0].i64().unwrap();
         packed_i32::split_i64_to_i32(packed)
     }
+
+    fn resize(&mut self, file: FileRecord<K>) -> bool {
+        let (ptr, len) = (*self.borrow_ptr(), *self.borrow_len());
+        runtime::write_bytemuck_to_wasm_memory(WireRecord::from(file), self.borrow_mem(), ptr, len);
+        let result = self.borrow_resize().call(&[Val::I32(ptr as i32), Val::I32(len as i32)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    // The guest only reads the label of the record
+    fn invalidate(&mut self, label: K) -> bool {
+        let (ptr, len) = (*self.borrow_ptr(), *self.borrow_len());
+        runtime::write_bytemuck_to_wasm_memory(WireRecord::from(FileRecord{ label, op: Operation::Delete, ..Default::default() }), self.borrow_mem(), ptr, len);
+        let result = self.borrow_invalidate().call(&[Val::I32(ptr as i32), Val::I32(len as i32)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn reset_stats(&mut self) {
+        policy::reset_guest_stats(self.borrow_module())
+    }
+
+    fn used(&self) -> i64 {
+        policy::guest_used(self.borrow_module())
+    }
+
+    fn filled(&mut self) -> bool {
+        policy::guest_filled(self.borrow_module())
+    }
+
+    fn enable_events(&mut self) {
+        policy::enable_guest_events(self.borrow_module())
+    }
+
+    fn events(&mut self) -> Vec<Event<K>> {
+        policy::drain_guest_events(self.borrow_module())
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        policy::snapshot_guest(self.borrow_module())
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        policy::restore_guest(self.borrow_module(), state)
+    }
 }
@@ -0,0 +1,145 @@
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use crate::cost::Costs;
+use crate::expiry::Expiry;
+use crate::registry::{Alg, Encoding};
+use crate::warmup::WarmupStats;
+
+/// State of one run part way through the trace, enough to carry on as if it never stopped.
+///
+/// Event summaries and hit rate series are not kept, a resumed run only reports what came after its checkpoint.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "K : Serialize + Hash + Eq", deserialize = "K : Deserialize<'de> + Hash + Eq"))]
+pub struct Checkpoint<K> {
+    pub requests: usize, // length of the trace, resuming on a different trace is refused
+    pub position: usize, // requests replayed so far
+    pub elapsed: f64, // seconds spent replaying before the checkpoint was taken
+    pub policy: Vec<u8>, // see `PolicyModule::snapshot`
+    pub expiry: Option<Expiry<K>>,
+    pub warmup: Option<WarmupStats>, // None while the warmup is still running
+    pub costs: Costs,
+}
+
+/// Where and how often runs save checkpoints, and whether they start from the saved ones.
+///
+/// Every run has its own file, so a crashed sweep resumes each run from its latest checkpoint and
+/// copying the directory forks every run from a warmed cache.
+pub struct Checkpoints {
+    dir: PathBuf,
+    interval: Option<usize>, // requests between checkpoints, None only resumes
+    resume: bool,
+}
+
+impl Checkpoints {
+    pub fn new(dir: &Path, interval: Option<usize>, resume: bool) -> Self {
+        if interval.is_some() {
+            fs::create_dir_all(dir).expect("Could not create checkpoint directory");
+        }
+        Checkpoints {
+            dir: dir.to_path_buf(),
+            interval,
+            resume,
+        }
+    }
+
+    pub fn path(&self, size: i64, alg: Alg, encoding: Encoding) -> PathBuf {
+        self.dir.join(format!("{}_{:?}_{:?}.ckpt", size, alg, encoding))
+    }
+
+    /// Position after the next stretch of requests to replay, the next checkpoint or the end of the trace.
+    pub fn next_stop(&self, position: usize, requests: usize) -> usize {
+        match self.interval {
+            Some(interval) => ((position / interval + 1) * interval).min(requests),
+            None => requests
+        }
+    }
+
+    pub fn saving(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// The saved checkpoint of a run when resuming, `None` starts the run from the beginning.
+    pub fn load<K>(&self, path: &Path, requests: usize) -> Option<Checkpoint<K>> where K : DeserializeOwned + Hash + Eq {
+        if !self.resume || !path.exists() {
+            return None;
+        }
+        let file = File::open(path).expect("Could not open checkpoint");
+        let checkpoint: Checkpoint<K> = bincode::deserialize_from(file).expect("Could not read checkpoint");
+        if checkpoint.requests != requests {
+            panic!("Checkpoint {} was taken on a trace of {} requests, not {}", path.display(), checkpoint.requests, requests);
+        }
+        println!("Resuming {} at request {}", path.display(), checkpoint.position);
+        Some(checkpoint)
+    }
+
+    // Written next to the old checkpoint and renamed over it, so a crash while saving keeps the last good one
+    pub fn save<K>(&self, path: &Path, checkpoint: &Checkpoint<K>) where K : Serialize + Hash + Eq {
+        let partial = path.with_extension("ckpt.partial");
+        let file = File::create(&partial).expect("Could not create checkpoint");
+        bincode::serialize_into(file, checkpoint).expect("Could not write checkpoint");
+        fs::rename(&partial, path).expect("Could not replace checkpoint");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use algorithm::Params;
+    use lru::LRU;
+    use simulator_shared_types::FileRecord;
+    use crate::native_modules::NativePolicyModule;
+    use crate::policy::PolicyModule;
+    use crate::trace::SizeChange;
+    use crate::{replay, RunState};
+    use super::*;
+
+    fn lru() -> NativePolicyModule<LRU<i32>, i32> {
+        let mut policy = NativePolicyModule::<LRU<i32>, i32>::new();
+        policy.initialize(30, &Params::new());
+        policy
+    }
+
+    fn run() -> RunState<i32> {
+        RunState { expiry: None, events: None, series: None, costs: Costs::default() }
+    }
+
+    #[test]
+    fn resumed_runs_match_uninterrupted_ones() {
+        let data: Vec<FileRecord<i32>> = [1, 2, 3, 1, 4, 2, 1, 5, 3, 1, 2, 4].iter()
+            .map(|&label| FileRecord { label, size: 10, ..Default::default() })
+            .collect();
+        let changed = vec![false; data.len()];
+
+        let mut whole = lru();
+        replay(&mut whole, &data, 0..data.len(), &changed, SizeChange::Last, &mut run());
+
+        let dir = std::env::temp_dir().join(format!("checkpoint_test_{}", std::process::id()));
+        let checkpoints = Checkpoints::new(&dir, Some(5), true);
+        assert_eq!(checkpoints.next_stop(0, data.len()), 5);
+        assert_eq!(checkpoints.next_stop(10, data.len()), data.len());
+        let path = checkpoints.path(30, Alg::Lru, Encoding::Native);
+
+        let mut first = lru();
+        replay(&mut first, &data, 0..5, &changed, SizeChange::Last, &mut run());
+        checkpoints.save(&path, &Checkpoint::<i32> {
+            requests: data.len(),
+            position: 5,
+            elapsed: 0.0,
+            policy: first.snapshot(),
+            expiry: None,
+            warmup: None,
+            costs: Costs::default(),
+        });
+
+        let checkpoint = checkpoints.load::<i32>(&path, data.len()).expect("Checkpoint was saved");
+        let mut resumed = lru();
+        resumed.restore(&checkpoint.policy);
+        replay(&mut resumed, &data, checkpoint.position..data.len(), &changed, SizeChange::Last, &mut run());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(resumed.stats(), whole.stats());
+        assert_eq!(resumed.used(), whole.used());
+    }
+}
@@ -0,0 +1,282 @@
+use std::fs::File;
+use std::hash::Hash;
+use std::path::Path;
+use std::str::FromStr;
+use algorithm::Params;
+use serde::Serialize;
+use simulator_shared_types::{FileRecord, Operation};
+use crate::policy::PolicyModule;
+use algorithm::hash64;
+
+/// How a request picks the node that caches its object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Routing {
+    Ring, // consistent hashing, each node owns the arcs before its virtual nodes
+    Rendezvous, // highest random weight, the live node scoring highest for the label
+    Modulo, // label hash modulo the live node count, remaps most objects on every change
+}
+
+impl FromStr for Routing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ring" => Ok(Routing::Ring),
+            "rendezvous" => Ok(Routing::Rendezvous),
+            "modulo" => Ok(Routing::Modulo),
+            _ => Err(format!("Unknown routing: {}", s))
+        }
+    }
+}
+
+/// A change to the nodes of a cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Membership {
+    Fail(usize), // the node leaves and loses its contents
+    Recover(usize), // a failed node rejoins with an empty cache
+    Add, // a new node joins with an empty cache
+}
+
+/// A membership change applied before the request at `at`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MembershipChange {
+    pub at: usize,
+    pub change: Membership,
+}
+
+impl FromStr for MembershipChange {
+    type Err = String;
+
+    // REQUEST:fail:NODE, REQUEST:recover:NODE or REQUEST:add
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let at = parts.first().and_then(|at| usize::from_str(at).ok()).ok_or(format!("Invalid membership change: {}", s))?;
+        let node = || parts.get(2).and_then(|node| usize::from_str(node).ok()).ok_or(format!("Membership change needs a node: {}", s));
+        let change = match parts.get(1) {
+            Some(&"fail") => Membership::Fail(node()?),
+            Some(&"recover") => Membership::Recover(node()?),
+            Some(&"add") => Membership::Add,
+            _ => return Err(format!("Invalid membership change: {}", s))
+        };
+        Ok(MembershipChange { at, change })
+    }
+}
+
+/// Requests one node served, and how well.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NodeStats {
+    pub node: usize,
+    pub alive: bool, // at the end of the trace
+    pub requests: u64,
+    pub reads: u64,
+    pub hits: u64,
+    pub bytes_read: u64,
+    pub bytes_hit: u64,
+    pub hit_ratio: f64,
+    pub load_share: f64, // share of every request in the trace
+}
+
+/// Requests between two membership changes, shows how hit rates dip and recover after a change.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Phase {
+    pub start: usize, // first request
+    pub nodes: usize, // live nodes
+    pub requests: u64,
+    pub reads: u64,
+    pub hits: u64,
+    pub hit_ratio: f64,
+    pub moved: f64, // share of the phase's requests routed to another node than before the change
+}
+
+/// Node, phase and cluster wide statistics of a run, written out as JSON by the `cluster` subcommand.
+#[derive(Debug, Serialize)]
+pub struct ClusterReport {
+    pub routing: Routing,
+    pub reads: u64,
+    pub hits: u64,
+    pub hit_ratio: f64,
+    pub byte_hit_ratio: f64,
+    pub imbalance: f64, // requests of the busiest node over the mean of the nodes that served any
+    pub nodes: Vec<NodeStats>,
+    pub phases: Vec<Phase>,
+    pub changes: Vec<MembershipChange>,
+}
+
+struct Node<K> {
+    policy: Box<dyn PolicyModule<K>>,
+    alive: bool,
+    stats: NodeStats,
+}
+
+/// Routes requests over nodes that each run their own instance of a policy.
+pub struct Cluster<K> {
+    nodes: Vec<Node<K>>,
+    node_size: i64,
+    params: Params, // of every node's policy
+    routing: Routing,
+    vnodes: usize,
+    members: Members, // the live nodes
+    previous: Option<Members>, // live nodes before the last change, to count the objects it moved
+    phases: Vec<Phase>,
+    changes: Vec<MembershipChange>,
+}
+
+impl <K> Cluster<K> where K : Hash + Eq + Clone {
+    /// Initializes a node per policy, each with a cache of `node_size` and the parameters `params`.
+    pub fn new(policies: Vec<Box<dyn PolicyModule<K>>>, node_size: i64, params: Params, routing: Routing, vnodes: usize) -> Self {
+        let mut cluster = Cluster {
+            nodes: vec![],
+            node_size,
+            params,
+            routing,
+            vnodes,
+            members: Members::default(),
+            previous: None,
+            phases: vec![],
+            changes: vec![],
+        };
+        for policy in policies {
+            cluster.join(policy);
+        }
+        cluster.phases.push(Phase { nodes: cluster.members.live.len(), ..Default::default() });
+        cluster
+    }
+
+    /// Applies a membership change before the request at `change.at`. `Add` takes its policy from `new_policy`.
+    pub fn change(&mut self, change: MembershipChange, new_policy: impl FnOnce() -> Box<dyn PolicyModule<K>>) {
+        let before = self.members.clone();
+        match change.change {
+            Membership::Fail(node) => {
+                let node = self.nodes.get_mut(node).unwrap_or_else(|| panic!("No node {} to fail", node));
+                node.alive = false;
+            }
+            Membership::Recover(node) => {
+                let node_size = self.node_size;
+                let node = self.nodes.get_mut(node).unwrap_or_else(|| panic!("No node {} to recover", node));
+                if !node.alive {
+                    node.policy.initialize(node_size, &self.params);
+                    node.alive = true;
+                }
+            }
+            Membership::Add => self.join(new_policy()),
+        }
+        self.members = self.members();
+        if self.members.live.is_empty() {
+            panic!("Membership change at request {} leaves no live nodes", change.at);
+        }
+        self.changes.push(change);
+        self.previous = Some(before);
+        self.phases.push(Phase { start: change.at, nodes: self.members.live.len(), ..Default::default() });
+    }
+
+    /// Sends a request to the node that owns its object. Returns whether it was a read that hit.
+    pub fn request(&mut self, file: FileRecord<K>) -> bool {
+        let hash = hash64(&file.label);
+        let owner = self.members.route(self.routing, hash);
+        let moved = match &self.previous {
+            Some(previous) => previous.route(self.routing, hash) != owner,
+            None => false
+        };
+
+        let is_read = file.op == Operation::Get;
+        let size = file.size as u64;
+        let node = &mut self.nodes[owner];
+        let hit = node.policy.send_request(file) && is_read;
+
+        node.stats.requests += 1;
+        let phase = self.phases.last_mut().unwrap();
+        phase.requests += 1;
+        if moved {
+            phase.moved += 1.0;
+        }
+        if is_read {
+            node.stats.reads += 1;
+            node.stats.bytes_read += size;
+            phase.reads += 1;
+            if hit {
+                node.stats.hits += 1;
+                node.stats.bytes_hit += size;
+                phase.hits += 1;
+            }
+        }
+        hit
+    }
+
+    pub fn report(&self) -> ClusterReport {
+        let requests: u64 = self.nodes.iter().map(|node| node.stats.requests).sum();
+        let reads: u64 = self.nodes.iter().map(|node| node.stats.reads).sum();
+        let hits: u64 = self.nodes.iter().map(|node| node.stats.hits).sum();
+        let bytes_read: u64 = self.nodes.iter().map(|node| node.stats.bytes_read).sum();
+        let bytes_hit: u64 = self.nodes.iter().map(|node| node.stats.bytes_hit).sum();
+
+        let serving: Vec<u64> = self.nodes.iter().map(|node| node.stats.requests).filter(|requests| *requests > 0).collect();
+        let busiest = serving.iter().max().copied().unwrap_or(0);
+        let mean = requests as f64 / serving.len().max(1) as f64;
+
+        ClusterReport {
+            routing: self.routing,
+            reads,
+            hits,
+            hit_ratio: hits as f64 / reads.max(1) as f64,
+            byte_hit_ratio: bytes_hit as f64 / bytes_read.max(1) as f64,
+            imbalance: if mean > 0.0 { busiest as f64 / mean } else { 0.0 },
+            nodes: self.nodes.iter().map(|node| NodeStats {
+                alive: node.alive,
+                hit_ratio: node.stats.hits as f64 / node.stats.reads.max(1) as f64,
+                load_share: node.stats.requests as f64 / requests.max(1) as f64,
+                ..node.stats.clone()
+            }).collect(),
+            phases: self.phases.iter().map(|phase| Phase {
+                hit_ratio: phase.hits as f64 / phase.reads.max(1) as f64,
+                moved: phase.moved / phase.requests.max(1) as f64,
+                ..phase.clone()
+            }).collect(),
+            changes: self.changes.clone(),
+        }
+    }
+
+    fn join(&mut self, mut policy: Box<dyn PolicyModule<K>>) {
+        policy.initialize(self.node_size, &self.params);
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            policy,
+            alive: true,
+            stats: NodeStats { node, ..Default::default() }
+        });
+        self.members = self.members();
+    }
+
+    fn members(&self) -> Members {
+        let live: Vec<usize> = (0..self.nodes.len()).filter(|node| self.nodes[*node].alive).collect();
+        let mut ring: Vec<(u64, usize)> = live.iter()
+            .flat_map(|node| (0..self.vnodes).map(move |vnode| (hash64(&(*node, vnode)), *node)))
+            .collect();
+        ring.sort_unstable();
+        Members { live, ring }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Members {
+    live: Vec<usize>,
+    ring: Vec<(u64, usize)>, // virtual node hashes of the live nodes, sorted
+}
+
+impl Members {
+    // Node that owns the label hashing to `hash`
+    fn route(&self, routing: Routing, hash: u64) -> usize {
+        match routing {
+            Routing::Ring => {
+                let next = self.ring.partition_point(|(point, _)| *point < hash);
+                self.ring[next % self.ring.len()].1
+            }
+            Routing::Rendezvous => *self.live.iter().max_by_key(|node| hash64(&(hash, **node))).unwrap(),
+            Routing::Modulo => self.live[(hash % self.live.len() as u64) as usize],
+        }
+    }
+}
+
+pub fn write_json(report: &ClusterReport, path: &Path) {
+    let file = File::create(path).expect("Could not create cluster output");
+    serde_json::to_writer_pretty(file, report).expect("Failed to serialize cluster report");
+}
@@ -0,0 +1,120 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{FileRecord, Operation};
+
+/// What a miss costs when the trace does not say, the time to fetch the object from the backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CostModel {
+    pub latency: f64, // fixed part of every fetch, in microseconds
+    pub bandwidth: f64, // backend throughput in MB/s, which is also bytes per microsecond
+}
+
+impl FromStr for CostModel {
+    type Err = String;
+
+    // LATENCY:BANDWIDTH
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid cost model: {}", s);
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 2 {
+            return Err(invalid());
+        }
+        match (f64::from_str(parts[0]), f64::from_str(parts[1])) {
+            (Ok(latency), Ok(bandwidth)) if latency >= 0.0 && bandwidth > 0.0 => Ok(CostModel { latency, bandwidth }),
+            _ => Err(invalid())
+        }
+    }
+}
+
+impl CostModel {
+    /// Microseconds to fetch an object of `size` bytes.
+    pub fn cost(&self, size: i64) -> u64 {
+        (self.latency + size as f64 / self.bandwidth).round() as u64
+    }
+}
+
+/// Gives every record without a cost of its own the cost `model` puts on its size.
+pub fn apply_cost_model<T>(data: Vec<FileRecord<T>>, model: &CostModel) -> Vec<FileRecord<T>> {
+    data.into_iter().map(|i| match i.cost {
+        0 => FileRecord { cost: model.cost(i.size), ..i },
+        _ => i
+    }).collect()
+}
+
+/// What the reads of a run cost, and what the cache saved of it.
+///
+/// Costs are in the unit of the trace's cost column, microseconds of fetch latency under a
+/// `CostModel`. Only misses reach the backend, writes are not counted.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Costs {
+    pub misses: u64,
+    pub total: u64, // of every read, as if nothing was cached
+    pub missed: u64, // of the reads that missed
+    pub backend_bytes: u64, // fetched by the misses
+    start: Option<u64>, // trace time of the first read
+    end: u64, // trace time of the last read
+}
+
+impl Costs {
+    pub fn record<T>(&mut self, file: &FileRecord<T>, hit: bool) {
+        if file.op != Operation::Get {
+            return;
+        }
+        self.start.get_or_insert(file.time);
+        self.end = file.time;
+        self.total += file.cost;
+        if !hit {
+            self.misses += 1;
+            self.missed += file.cost;
+            self.backend_bytes += file.size as u64;
+        }
+    }
+
+    pub fn saved(&self) -> u64 {
+        self.total - self.missed
+    }
+
+    /// Mean cost of a miss, the average miss latency under a `CostModel`.
+    pub fn average_miss(&self) -> f64 {
+        self.missed as f64 / self.misses.max(1) as f64
+    }
+
+    /// Backend bytes per unit of trace time, or per request when the trace has no time column.
+    pub fn backend_bandwidth(&self) -> f64 {
+        let span = self.end.saturating_sub(self.start.unwrap_or(self.end));
+        self.backend_bytes as f64 / span.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(label: i32, size: i64, cost: u64, time: u64) -> FileRecord<i32> {
+        FileRecord { label, size, cost, time, ..Default::default() }
+    }
+
+    #[test]
+    fn models_latency_plus_transfer_time() {
+        let model = CostModel::from_str("100:2").unwrap();
+        assert_eq!(model.cost(1000), 600);
+        assert!(CostModel::from_str("100:0").is_err());
+        assert!(CostModel::from_str("100").is_err());
+        let data = apply_cost_model(vec![read(1, 1000, 0, 0), read(2, 1000, 7, 0)], &model);
+        assert_eq!((data[0].cost, data[1].cost), (600, 7));
+    }
+
+    #[test]
+    fn hits_save_their_cost() {
+        let mut costs = Costs::default();
+        costs.record(&read(1, 100, 40, 10), false);
+        costs.record(&read(1, 100, 40, 20), true);
+        costs.record(&read(2, 300, 60, 30), false);
+        costs.record(&FileRecord { op: Operation::Set, ..read(3, 500, 90, 40) }, false);
+        assert_eq!((costs.total, costs.missed, costs.saved()), (140, 100, 40));
+        assert_eq!(costs.average_miss(), 50.0);
+        assert_eq!(costs.backend_bytes, 400);
+        // Writes are left out, so the span ends at the last read
+        assert_eq!(costs.backend_bandwidth(), 20.0);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::path::Path;
+use serde::Serialize;
+use simulator_shared_types::{Event, EventKind};
+
+/// Requests between drains of a policy's events, bounds the memory a guest spends buffering them.
+pub const DRAIN_INTERVAL: usize = 4096;
+
+/// What a policy did over a run, built from its event stream.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct EventSummary {
+    pub inserts: u64,
+    pub hits: u64,
+    pub evictions: u64,
+    pub bypasses: u64,
+    pub eviction_ages: Vec<u64>, // evictions by trace time since insertion, bucket i holds ages below 2^i
+    pub digest: u64, // order sensitive hash of every event, equal for policies that made the same decisions
+}
+
+/// Folds the events drained from a policy into an `EventSummary`.
+pub struct EventRecorder<T> {
+    inserted: HashMap<T, u64>, // insertion time of every cached object
+    summary: EventSummary,
+}
+
+impl <T> EventRecorder<T> where T : Hash + Eq + Clone {
+    pub fn new() -> Self {
+        EventRecorder {
+            inserted: HashMap::new(),
+            summary: EventSummary::default(),
+        }
+    }
+
+    /// Folds in the events drained from the policy, in the order they happened.
+    pub fn record_all(&mut self, events: Vec<Event<T>>) {
+        for event in events {
+            self.record(event);
+        }
+    }
+
+    fn record(&mut self, event: Event<T>) {
+        self.summary.digest = algorithm::hash64(&(self.summary.digest, &event));
+        match event.kind {
+            EventKind::Insert => {
+                self.summary.inserts += 1;
+                self.inserted.insert(event.label, event.time);
+            }
+            EventKind::Hit => self.summary.hits += 1,
+            EventKind::Evict => {
+                self.summary.evictions += 1;
+                // Objects the host invalidated and a policy reinserted count from the latest insertion
+                if let Some(inserted) = self.inserted.remove(&event.label) {
+                    let age = event.time.saturating_sub(inserted);
+                    let bucket = (64 - age.leading_zeros()) as usize;
+                    if self.summary.eviction_ages.len() <= bucket {
+                        self.summary.eviction_ages.resize(bucket + 1, 0);
+                    }
+                    self.summary.eviction_ages[bucket] += 1;
+                }
+            }
+            EventKind::Bypass => self.summary.bypasses += 1,
+        }
+    }
+
+    pub fn finish(self) -> EventSummary {
+        self.summary
+    }
+}
+
+/// Event summary of one policy at one cache size.
+#[derive(Serialize, Debug)]
+pub struct EventReport {
+    pub name: String,
+    pub size: i64,
+    #[serde(flatten)]
+    pub summary: EventSummary,
+}
+
+pub fn write_json(reports: &[EventReport], path: &Path) {
+    let file = File::create(path).expect("Could not create event report output");
+    serde_json::to_writer_pretty(file, reports).expect("Failed to serialize event report");
+}
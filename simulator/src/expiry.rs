@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use simulator_shared_types::{Event, EventKind, FileRecord, Operation};
+use crate::policy::PolicyModule;
+
+/// When objects past their TTL leave the cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpiryMode {
+    Lazy, // an expired object stays cached, taking up space, until it is requested or evicted
+    Wheel, // a timer wheel driven by trace time drops expired objects as soon as they expire
+}
+
+impl FromStr for ExpiryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lazy" => Ok(ExpiryMode::Lazy),
+            "wheel" => Ok(ExpiryMode::Wheel),
+            _ => Err(format!("Unknown expiry mode: {}", s))
+        }
+    }
+}
+
+/// Adds expiration to any policy through `invalidate`, so every policy gets the same TTL handling.
+///
+/// The deadline of an object is set when it is written into the cache, by a set, an update or a
+/// read that missed, and is not extended by hits.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "T : Serialize + Hash + Eq", deserialize = "T : Deserialize<'de> + Hash + Eq"))]
+pub struct Expiry<T> {
+    mode: ExpiryMode,
+    deadlines: HashMap<T, u64>,
+    dropped: HashSet<T>, // objects the wheel expired out of the cache, not requested since
+    wheel: TimerWheel<T>,
+    pub expired_misses: u64, // reads that found their object expired
+    pub expirations: u64, // cached objects dropped by the timer wheel
+}
+
+impl <T> Expiry<T> where T : Hash + Eq + Clone {
+    pub fn new(mode: ExpiryMode) -> Self {
+        Expiry {
+            mode,
+            deadlines: HashMap::new(),
+            dropped: HashSet::new(),
+            wheel: TimerWheel::new(4096),
+            expired_misses: 0,
+            expirations: 0,
+        }
+    }
+
+    /// Sends a request to the policy, expiring objects first. Returns whether it was a read that hit.
+    pub fn request(&mut self, policy: &mut dyn PolicyModule<T>, file: FileRecord<T>) -> bool {
+        if self.mode == ExpiryMode::Wheel {
+            for (label, deadline) in self.wheel.advance(file.time) {
+                // Skip timers left behind by a newer write of the object
+                if self.deadlines.get(&label) == Some(&deadline) {
+                    self.deadlines.remove(&label);
+                    if policy.invalidate(label.clone()) {
+                        self.expirations += 1;
+                        self.dropped.insert(label);
+                    }
+                }
+            }
+        }
+
+        if self.dropped.remove(&file.label) && file.op == Operation::Get {
+            self.expired_misses += 1;
+        }
+        if file.op == Operation::Get {
+            if let Some(deadline) = self.deadlines.get(&file.label) {
+                if *deadline <= file.time {
+                    self.deadlines.remove(&file.label);
+                    if policy.invalidate(file.label.clone()) {
+                        self.expired_misses += 1;
+                    }
+                }
+            }
+        }
+
+        let (label, time, ttl, op) = (file.label.clone(), file.time, file.ttl, file.op);
+        let hit = policy.send_request(file);
+
+        match op {
+            Operation::Get if hit => {}
+            Operation::Get | Operation::Set | Operation::Update => self.set_deadline(label, time, ttl),
+            Operation::Delete => { self.deadlines.remove(&label); }
+        }
+        hit
+    }
+
+    /// Forgets the objects the policy evicted or declined to cache, given every event it recorded
+    /// since the last call. Only the last event of an object counts, a later insert brings it back.
+    pub fn forget_evicted(&mut self, events: &[Event<T>]) {
+        let mut last = HashMap::new();
+        for event in events {
+            last.insert(&event.label, event.kind);
+        }
+        for (label, kind) in last {
+            if kind == EventKind::Evict || kind == EventKind::Bypass {
+                self.deadlines.remove(label);
+                self.dropped.remove(label);
+            }
+        }
+    }
+
+    fn set_deadline(&mut self, label: T, time: u64, ttl: u64) {
+        if ttl == 0 {
+            self.deadlines.remove(&label);
+            return;
+        }
+        let deadline = time + ttl;
+        self.deadlines.insert(label.clone(), deadline);
+        if self.mode == ExpiryMode::Wheel {
+            self.wheel.schedule(label, deadline);
+        }
+    }
+}
+
+/// Hashed timer wheel (Varghese and Lauck) with one slot per time unit.
+///
+/// Timers further out than one rotation share a slot with nearer ones and are passed over until
+/// their deadline comes up.
+#[derive(Clone, Serialize, Deserialize)]
+struct TimerWheel<T> {
+    slots: Vec<Vec<(T, u64)>>,
+    now: u64,
+}
+
+impl <T> TimerWheel<T> {
+    fn new(slots: usize) -> Self {
+        TimerWheel {
+            slots: (0..slots).map(|_| Vec::new()).collect(),
+            now: 0,
+        }
+    }
+
+    fn schedule(&mut self, label: T, deadline: u64) {
+        let slot = (deadline % self.slots.len() as u64) as usize;
+        self.slots[slot].push((label, deadline));
+    }
+
+    // Moves the wheel to `now`, returning the timers that fired on the way
+    fn advance(&mut self, now: u64) -> Vec<(T, u64)> {
+        let mut fired = vec![];
+        if now <= self.now {
+            return fired;
+        }
+        // A jump of a whole rotation or more visits every slot once
+        let steps = (now - self.now).min(self.slots.len() as u64);
+        for tick in self.now + 1..=self.now + steps {
+            let slot = (tick % self.slots.len() as u64) as usize;
+            let (due, pending): (Vec<_>, Vec<_>) = self.slots[slot].drain(..).partition(|(_, deadline)| *deadline <= now);
+            self.slots[slot] = pending;
+            fired.extend(due);
+        }
+        self.now = now;
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use algorithm::Params;
+    use lru::LRU;
+    use crate::native_modules::NativePolicyModule;
+    use super::*;
+
+    fn lru(size: i64) -> NativePolicyModule<LRU<i32>, i32> {
+        let mut policy = NativePolicyModule::new();
+        policy.initialize(size, &Params::new());
+        policy
+    }
+
+    fn get(label: i32, time: u64, ttl: u64) -> FileRecord<i32> {
+        FileRecord { label, size: 1, time, ttl, ..Default::default() }
+    }
+
+    #[test]
+    fn lazy_expiry_drops_objects_when_requested() {
+        let mut policy = lru(10);
+        let mut expiry = Expiry::new(ExpiryMode::Lazy);
+        assert!(!expiry.request(&mut policy, get(1, 0, 10)));
+        assert!(expiry.request(&mut policy, get(1, 9, 10)));
+        // Still cached until it is requested again, hits do not extend the deadline
+        expiry.request(&mut policy, get(2, 9, 0));
+        assert_eq!(policy.used(), 2);
+        assert!(!expiry.request(&mut policy, get(1, 10, 10)));
+        assert_eq!((expiry.expired_misses, expiry.expirations), (1, 0));
+        assert!(expiry.request(&mut policy, get(1, 19, 10)));
+    }
+
+    #[test]
+    fn the_wheel_drops_objects_as_they_expire() {
+        let mut policy = lru(10);
+        let mut expiry = Expiry::new(ExpiryMode::Wheel);
+        expiry.request(&mut policy, get(1, 0, 5));
+        expiry.request(&mut policy, get(2, 1, 0));
+        expiry.request(&mut policy, get(2, 6, 0));
+        assert_eq!(expiry.expirations, 1);
+        assert_eq!(policy.used(), 1);
+        assert!(!expiry.request(&mut policy, get(1, 7, 5)));
+        assert_eq!(expiry.expired_misses, 1);
+    }
+
+    #[test]
+    fn rewrites_replace_the_deadline() {
+        let mut policy = lru(10);
+        let mut expiry = Expiry::new(ExpiryMode::Wheel);
+        expiry.request(&mut policy, get(1, 0, 5));
+        expiry.request(&mut policy, FileRecord { op: Operation::Set, ..get(1, 3, 5) });
+        assert!(expiry.request(&mut policy, get(1, 6, 5)));
+        expiry.request(&mut policy, get(2, 8, 0));
+        assert_eq!(expiry.expirations, 1);
+        assert!(!policy.send_request(get(1, 9, 0)));
+    }
+
+    #[test]
+    fn evicted_objects_lose_their_deadline() {
+        let mut policy = lru(2);
+        policy.enable_events();
+        let mut expiry = Expiry::new(ExpiryMode::Wheel);
+        for label in 1..=3 {
+            expiry.request(&mut policy, get(label, label as u64, 100));
+        }
+        expiry.forget_evicted(&policy.events());
+        assert!(!expiry.deadlines.contains_key(&1));
+        assert!(expiry.deadlines.contains_key(&2) && expiry.deadlines.contains_key(&3));
+        // Brought back by a later insert, the object keeps its new deadline
+        expiry.request(&mut policy, get(1, 4, 100));
+        expiry.forget_evicted(&policy.events());
+        assert_eq!(expiry.deadlines.get(&1), Some(&104));
+        assert!(!expiry.deadlines.contains_key(&2));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::path::Path;
+use std::str::FromStr;
+use algorithm::Params;
+use serde::Serialize;
+use simulator_shared_types::{EventKind, FileRecord, Operation};
+use crate::policy::PolicyModule;
+
+/// Where objects are kept across the tiers of a hierarchy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Placement {
+    Inclusive, // objects are fetched into every tier they missed in, evicting from a tier drops the copies above it
+    Exclusive, // objects live in one tier, hits below the top move them up and evictions move them down a tier
+    Promote, // objects enter the bottom tier and are copied up a tier each time they hit
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inclusive" => Ok(Placement::Inclusive),
+            "exclusive" => Ok(Placement::Exclusive),
+            "promote" => Ok(Placement::Promote),
+            _ => Err(format!("Unknown placement: {}", s))
+        }
+    }
+}
+
+/// Reads that reached a tier, and what it did with them.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TierStats {
+    pub name: String,
+    pub size: i64,
+    pub reads: u64, // reads that missed every tier above
+    pub hits: u64,
+    pub bytes_read: u64,
+    pub bytes_hit: u64,
+    pub promotions: u64, // objects moved or copied up into this tier
+    pub demotions: u64, // objects moved down into this tier
+    pub hit_ratio: f64,
+    pub byte_hit_ratio: f64,
+}
+
+/// Tier and end-to-end statistics of a hierarchy run, written out as JSON by the `hierarchy` subcommand.
+#[derive(Debug, Serialize)]
+pub struct HierarchyReport {
+    pub placement: Placement,
+    pub reads: u64,
+    pub hits: u64, // reads that hit in any tier
+    pub hit_ratio: f64,
+    pub byte_hit_ratio: f64,
+    pub tiers: Vec<TierStats>,
+}
+
+struct Tier<K> {
+    policy: Box<dyn PolicyModule<K>>,
+    resident: HashMap<K, u64>, // objects the policy holds and their cost, followed through its events
+    stats: TierStats,
+}
+
+/// Name, cache size, policy parameters and policy of a tier.
+pub type TierSpec<K> = (String, i64, Params, Box<dyn PolicyModule<K>>);
+
+/// Chains policies from the top tier, such as memory, down to the bottom one, such as flash.
+///
+/// Each tier's contents are followed through its insert and evict events, so the hierarchy can look
+/// an object up without sending the tier a request. Statistics are kept here rather than in the
+/// policies, which also see the moves between tiers.
+pub struct Hierarchy<K> {
+    tiers: Vec<Tier<K>>,
+    placement: Placement,
+    reads: u64,
+    hits: u64,
+    bytes_read: u64,
+    bytes_hit: u64,
+}
+
+impl <K> Hierarchy<K> where K : Hash + Eq + Clone {
+    /// Initializes every tier from its name, cache size, policy parameters and policy, top tier first.
+    pub fn new(tiers: Vec<TierSpec<K>>, placement: Placement) -> Self {
+        let tiers = tiers.into_iter().map(|(name, size, params, mut policy)| {
+            policy.initialize(size, &params);
+            policy.enable_events();
+            Tier {
+                policy,
+                resident: HashMap::new(),
+                stats: TierStats { name, size, ..Default::default() }
+            }
+        }).collect();
+        Hierarchy {
+            tiers,
+            placement,
+            reads: 0,
+            hits: 0,
+            bytes_read: 0,
+            bytes_hit: 0,
+        }
+    }
+
+    /// Applies a request to the hierarchy. Returns whether it was a read that hit in any tier.
+    pub fn request(&mut self, file: FileRecord<K>) -> bool {
+        let bottom = self.tiers.len() - 1;
+        match file.op {
+            Operation::Get => return self.read(file),
+            Operation::Delete => {
+                for tier in 0..=bottom {
+                    self.invalidate(tier, &file.label);
+                }
+            }
+            Operation::Update => {
+                for tier in 0..=bottom {
+                    if self.tiers[tier].resident.contains_key(&file.label) {
+                        self.send(tier, file.clone());
+                    }
+                }
+            }
+            // Writes land where a miss would have fetched the object to
+            Operation::Set => match self.placement {
+                Placement::Inclusive => {
+                    for tier in 0..=bottom {
+                        self.send(tier, file.clone());
+                    }
+                }
+                Placement::Exclusive => {
+                    for tier in 1..=bottom {
+                        self.invalidate(tier, &file.label);
+                    }
+                    self.send(0, file);
+                }
+                Placement::Promote => {
+                    for tier in 0..bottom {
+                        self.invalidate(tier, &file.label);
+                    }
+                    self.send(bottom, file);
+                }
+            }
+        }
+        false
+    }
+
+    fn read(&mut self, file: FileRecord<K>) -> bool {
+        let bottom = self.tiers.len() - 1;
+        let found = (0..=bottom).find(|tier| self.tiers[*tier].resident.contains_key(&file.label));
+
+        self.reads += 1;
+        self.bytes_read += file.size as u64;
+        for tier in 0..=found.unwrap_or(bottom) {
+            let stats = &mut self.tiers[tier].stats;
+            stats.reads += 1;
+            stats.bytes_read += file.size as u64;
+        }
+        if let Some(tier) = found {
+            self.hits += 1;
+            self.bytes_hit += file.size as u64;
+            let stats = &mut self.tiers[tier].stats;
+            stats.hits += 1;
+            stats.bytes_hit += file.size as u64;
+        }
+
+        match (self.placement, found) {
+            (Placement::Inclusive, _) => {
+                for tier in 0..=found.unwrap_or(bottom) {
+                    self.send(tier, file.clone());
+                }
+            }
+            (Placement::Exclusive, Some(tier)) if tier > 0 => {
+                self.invalidate(tier, &file.label);
+                self.tiers[0].stats.promotions += 1;
+                self.send(0, file);
+            }
+            (Placement::Exclusive, _) => { self.send(0, file); }
+            (Placement::Promote, Some(tier)) => {
+                self.send(tier, file.clone());
+                if tier > 0 {
+                    self.tiers[tier - 1].stats.promotions += 1;
+                    self.send(tier - 1, FileRecord { op: Operation::Set, ..file });
+                }
+            }
+            (Placement::Promote, None) => { self.send(bottom, file); }
+        }
+        found.is_some()
+    }
+
+    fn send(&mut self, tier: usize, file: FileRecord<K>) -> bool {
+        let cost = file.cost;
+        let hit = self.tiers[tier].policy.send_request(file);
+        self.sync(tier, cost);
+        hit
+    }
+
+    fn invalidate(&mut self, tier: usize, label: &K) {
+        if self.tiers[tier].resident.remove(label).is_some() {
+            self.tiers[tier].policy.invalidate(label.clone());
+        }
+    }
+
+    // Follows a tier's inserts and evictions, moving evicted objects as the placement requires.
+    // Only the object of the request is inserted, so it brings the request's cost along
+    fn sync(&mut self, tier: usize, cost: u64) {
+        for event in self.tiers[tier].policy.events() {
+            match event.kind {
+                EventKind::Insert => { self.tiers[tier].resident.insert(event.label, cost); }
+                EventKind::Evict => {
+                    let cost = self.tiers[tier].resident.remove(&event.label).unwrap_or(0);
+                    match self.placement {
+                        Placement::Inclusive => {
+                            for above in 0..tier {
+                                self.invalidate(above, &event.label);
+                            }
+                        }
+                        Placement::Exclusive if tier + 1 < self.tiers.len() => {
+                            self.tiers[tier + 1].stats.demotions += 1;
+                            self.send(tier + 1, FileRecord { label: event.label, size: event.size, op: Operation::Set, time: event.time, ttl: 0, cost });
+                        }
+                        Placement::Exclusive | Placement::Promote => {}
+                    }
+                }
+                EventKind::Hit | EventKind::Bypass => {}
+            }
+        }
+    }
+
+    pub fn report(&self) -> HierarchyReport {
+        HierarchyReport {
+            placement: self.placement,
+            reads: self.reads,
+            hits: self.hits,
+            hit_ratio: self.hits as f64 / self.reads.max(1) as f64,
+            byte_hit_ratio: self.bytes_hit as f64 / self.bytes_read.max(1) as f64,
+            tiers: self.tiers.iter().map(|tier| TierStats {
+                hit_ratio: tier.stats.hits as f64 / tier.stats.reads.max(1) as f64,
+                byte_hit_ratio: tier.stats.bytes_hit as f64 / tier.stats.bytes_read.max(1) as f64,
+                ..tier.stats.clone()
+            }).collect(),
+        }
+    }
+}
+
+pub fn write_json(report: &HierarchyReport, path: &Path) {
+    let file = File::create(path).expect("Could not create hierarchy output");
+    serde_json::to_writer_pretty(file, report).expect("Failed to serialize hierarchy report");
+}
+
+#[cfg(test)]
+mod tests {
+    use lru::LRU;
+    use crate::native_modules::NativePolicyModule;
+    use super::*;
+
+    // Two LRU tiers of two 10 byte objects each
+    fn hierarchy(placement: Placement) -> Hierarchy<i32> {
+        let tier = |name: &str| -> TierSpec<i32> { (name.to_string(), 20, Params::new(), Box::new(NativePolicyModule::<LRU<i32>, i32>::new())) };
+        Hierarchy::new(vec![tier("memory"), tier("flash")], placement)
+    }
+
+    fn read(hierarchy: &mut Hierarchy<i32>, labels: &[i32]) -> Vec<bool> {
+        labels.iter().map(|&label| hierarchy.request(FileRecord { label, size: 10, ..Default::default() })).collect()
+    }
+
+    fn resident(hierarchy: &Hierarchy<i32>, tier: usize) -> Vec<i32> {
+        let mut labels: Vec<i32> = hierarchy.tiers[tier].resident.keys().cloned().collect();
+        labels.sort_unstable();
+        labels
+    }
+
+    #[test]
+    fn inclusive_evictions_drop_the_copies_above() {
+        let mut hierarchy = hierarchy(Placement::Inclusive);
+        assert_eq!(read(&mut hierarchy, &[1, 2, 1]), vec![false, false, true]);
+        // The top hit left flash's order alone, so flash evicts 1 and memory loses its copy
+        read(&mut hierarchy, &[3]);
+        assert_eq!(resident(&hierarchy, 0), vec![3]);
+        assert_eq!(resident(&hierarchy, 1), vec![2, 3]);
+    }
+
+    #[test]
+    fn exclusive_tiers_move_objects_between_them() {
+        let mut hierarchy = hierarchy(Placement::Exclusive);
+        assert_eq!(read(&mut hierarchy, &[1, 2, 3, 1]), vec![false, false, false, true]);
+        assert_eq!(resident(&hierarchy, 0), vec![1, 3]);
+        assert_eq!(resident(&hierarchy, 1), vec![2]);
+        let report = hierarchy.report();
+        assert_eq!((report.tiers[0].promotions, report.tiers[1].demotions), (1, 2));
+        assert_eq!((report.tiers[0].hits, report.tiers[1].reads, report.tiers[1].hits), (0, 4, 1));
+    }
+
+    #[test]
+    fn promote_copies_hits_up_a_tier() {
+        let mut hierarchy = hierarchy(Placement::Promote);
+        assert_eq!(read(&mut hierarchy, &[1]), vec![false]);
+        assert!(resident(&hierarchy, 0).is_empty());
+        assert_eq!(read(&mut hierarchy, &[1, 1]), vec![true, true]);
+        assert_eq!(resident(&hierarchy, 0), vec![1]);
+        assert_eq!(resident(&hierarchy, 1), vec![1]);
+        let report = hierarchy.report();
+        assert_eq!((report.tiers[0].promotions, report.tiers[0].hits, report.tiers[1].hits), (1, 1, 1));
+        assert_eq!((report.reads, report.hits), (3, 2));
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::str::FromStr;
+use admission::Admission;
+use algorithm::Params;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use wasmer::{Store, Val};
+use simulator_shared_types::{Event, FileRecord, WireKey};
+use crate::policy::PolicyModule;
+use crate::registry::{self, Alg, Encoding};
+
+/// Object key type a run is simulated with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    I32,
+    U64,
+    Bytes, // the label column as a byte string, such as a URL
+}
+
+impl FromStr for KeyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i32" => Ok(KeyType::I32),
+            "u64" => Ok(KeyType::U64),
+            "bytes" => Ok(KeyType::Bytes),
+            _ => Err(format!("Unknown key type: {}", s))
+        }
+    }
+}
+
+/// A key that can be read from the label column of a trace.
+pub trait TraceKey : Hash + Eq + Clone + Debug {
+    fn parse(field: &str) -> Self;
+}
+
+impl TraceKey for i32 {
+    fn parse(field: &str) -> Self {
+        i32::from_str(field).expect("Invalid i32 label")
+    }
+}
+
+impl TraceKey for u64 {
+    fn parse(field: &str) -> Self {
+        u64::from_str(field).expect("Invalid u64 label")
+    }
+}
+
+impl TraceKey for Vec<u8> {
+    fn parse(field: &str) -> Self {
+        field.as_bytes().to_vec()
+    }
+}
+
+// Byte string keys are read as strings where they end up in a report
+impl TraceKey for String {
+    fn parse(field: &str) -> Self {
+        field.to_string()
+    }
+}
+
+/// Keys with a fixed width, which the pair and bytemuck encodings can carry.
+pub trait FixedWidthKey : WireKey + Default + DeserializeOwned {
+    /// The key as a pair argument.
+    fn to_val(self) -> Val;
+}
+
+impl FixedWidthKey for i32 {
+    fn to_val(self) -> Val {
+        Val::I32(self)
+    }
+}
+
+impl FixedWidthKey for u64 {
+    fn to_val(self) -> Val {
+        Val::I64(self as i64)
+    }
+}
+
+/// Key types a whole simulation can run with, one per `KeyType`.
+pub trait SimKey : TraceKey + Serialize + DeserializeOwned + 'static {
+    /// Directory the guest modules built for this key type are in, see `simulator/build.rs`.
+    const MODULE_DIR: &'static str;
+
+    /// `alg` over a pair or bytemuck encoding, `None` when this key type cannot be passed that way.
+    fn fixed_width_policy(store: &Store, alg: Alg, encoding: Encoding, hash_keys: bool) -> Option<Box<dyn PolicyModule<Self>>>;
+}
+
+impl SimKey for i32 {
+    const MODULE_DIR: &'static str = "modules";
+
+    fn fixed_width_policy(store: &Store, alg: Alg, encoding: Encoding, _hash_keys: bool) -> Option<Box<dyn PolicyModule<Self>>> {
+        Some(registry::fixed_width_module::<i32>(store, Self::MODULE_DIR, alg, encoding))
+    }
+}
+
+impl SimKey for u64 {
+    const MODULE_DIR: &'static str = "modules/u64_keys";
+
+    fn fixed_width_policy(store: &Store, alg: Alg, encoding: Encoding, _hash_keys: bool) -> Option<Box<dyn PolicyModule<Self>>> {
+        Some(registry::fixed_width_module::<u64>(store, Self::MODULE_DIR, alg, encoding))
+    }
+}
+
+impl SimKey for Vec<u8> {
+    const MODULE_DIR: &'static str = "modules/byte_keys";
+
+    fn fixed_width_policy(store: &Store, alg: Alg, encoding: Encoding, hash_keys: bool) -> Option<Box<dyn PolicyModule<Self>>> {
+        if !hash_keys {
+            return None;
+        }
+        let policy = registry::fixed_width_module::<u64>(store, u64::MODULE_DIR, alg, encoding);
+        Some(Box::new(HashedKeys::new(policy)))
+    }
+}
+
+/// Runs a policy built for u64 keys on byte string keys by hashing every key.
+///
+/// Keys that collide share a cache entry, which at 64 bits is too rare to move hit rates.
+pub struct HashedKeys {
+    policy: Box<dyn PolicyModule<u64>>,
+    labels: Option<HashMap<u64, Vec<u8>>>, // original keys of the hashes, only kept while recording events
+}
+
+impl HashedKeys {
+    pub fn new(policy: Box<dyn PolicyModule<u64>>) -> Self {
+        HashedKeys {
+            policy,
+            labels: None
+        }
+    }
+
+    fn hash(&mut self, label: &[u8]) -> u64 {
+        let hash = algorithm::hash64(&label);
+        if let Some(labels) = self.labels.as_mut() {
+            labels.entry(hash).or_insert_with(|| label.to_vec());
+        }
+        hash
+    }
+}
+
+impl PolicyModule<Vec<u8>> for HashedKeys {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+        self.policy.initialize(cache_size, params)
+    }
+
+    fn set_admission(&mut self, admission: Admission) {
+        self.policy.set_admission(admission)
+    }
+
+    fn send_request(&mut self, file: FileRecord<Vec<u8>>) -> bool {
+        let label = self.hash(&file.label);
+        self.policy.send_request(FileRecord {
+            label,
+            size: file.size,
+            op: file.op,
+            time: file.time,
+            ttl: file.ttl,
+            cost: file.cost
+        })
+    }
+
+    fn stats(&self) -> (i32, i32) {
+        self.policy.stats()
+    }
+
+    fn resize(&mut self, file: FileRecord<Vec<u8>>) -> bool {
+        let label = self.hash(&file.label);
+        self.policy.resize(FileRecord {
+            label,
+            size: file.size,
+            op: file.op,
+            time: file.time,
+            ttl: file.ttl,
+            cost: file.cost
+        })
+    }
+
+    fn invalidate(&mut self, label: Vec<u8>) -> bool {
+        let label = self.hash(&label);
+        self.policy.invalidate(label)
+    }
+
+    fn reset_stats(&mut self) {
+        self.policy.reset_stats()
+    }
+
+    fn used(&self) -> i64 {
+        self.policy.used()
+    }
+
+    fn filled(&mut self) -> bool {
+        self.policy.filled()
+    }
+
+    fn enable_events(&mut self) {
+        self.labels.get_or_insert_with(HashMap::new);
+        self.policy.enable_events()
+    }
+
+    // Every hash in an event was made from a key sent since recording started
+    fn events(&mut self) -> Vec<Event<Vec<u8>>> {
+        let labels = self.labels.as_ref().expect("Events are not enabled");
+        self.policy.events().into_iter().map(|event| Event {
+            kind: event.kind,
+            label: labels[&event.label].clone(),
+            size: event.size,
+            time: event.time
+        }).collect()
+    }
+
+    // The original keys go along, events after a resume can name objects cached before it
+    fn snapshot(&self) -> Vec<u8> {
+        bincode::serialize(&(self.policy.snapshot(), &self.labels)).expect("Failed to serialize policy state")
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        let (policy, labels): (Vec<u8>, Option<HashMap<u64, Vec<u8>>>) = bincode::deserialize(state).expect("Failed to deserialize policy state");
+        self.policy.restore(&policy);
+        if let Some(labels) = labels {
+            self.labels.get_or_insert_with(HashMap::new).extend(labels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use lru::LRU;
+    use simulator_shared_types::{EventKind, WireRecord};
+    use crate::native_modules::NativePolicyModule;
+    use super::*;
+
+    fn hashed_lru(size: i64) -> HashedKeys {
+        let mut policy = HashedKeys::new(Box::new(NativePolicyModule::<LRU<u64>, u64>::new()));
+        policy.initialize(size, &Params::new());
+        policy
+    }
+
+    fn get(label: &str) -> FileRecord<Vec<u8>> {
+        FileRecord { label: label.as_bytes().to_vec(), size: 1, ..Default::default() }
+    }
+
+    #[test]
+    fn trace_keys_parse_the_label_column() {
+        assert_eq!(i32::parse("-7"), -7);
+        assert_eq!(u64::parse("18446744073709551615"), u64::MAX);
+        assert_eq!(Vec::<u8>::parse("/img/a.png"), b"/img/a.png".to_vec());
+        assert_eq!(String::parse("/img/a.png"), "/img/a.png");
+    }
+
+    #[test]
+    fn fixed_width_keys_survive_the_wire() {
+        for &label in &[0, 1, -1, i32::MIN, i32::MAX] {
+            let file = FileRecord { label, size: 3, ..Default::default() };
+            assert_eq!(FileRecord::<i32>::try_from(WireRecord::from(file)).unwrap(), file);
+        }
+        for &label in &[0, 1, u64::MAX] {
+            assert_eq!(u64::from_wire(label.to_wire()), label);
+        }
+    }
+
+    #[test]
+    fn hashed_keys_report_events_under_the_original_keys() {
+        let mut policy = hashed_lru(2);
+        policy.enable_events();
+        for label in &["a", "b", "a", "c"] {
+            policy.send_request(get(label));
+        }
+        let evicted: Vec<Vec<u8>> = policy.events().into_iter()
+            .filter(|event| event.kind == EventKind::Evict)
+            .map(|event| event.label)
+            .collect();
+        assert_eq!(evicted, vec![b"b".to_vec()]);
+        assert!(policy.send_request(get("a")));
+    }
+
+    #[test]
+    fn resumed_runs_name_objects_cached_before_the_checkpoint() {
+        let mut policy = hashed_lru(2);
+        policy.enable_events();
+        policy.send_request(get("a"));
+        policy.send_request(get("b"));
+        policy.events();
+        let state = policy.snapshot();
+
+        // Restored and then recording, as a resumed run is
+        let mut resumed = HashedKeys::new(Box::new(NativePolicyModule::<LRU<u64>, u64>::new()));
+        resumed.restore(&state);
+        resumed.enable_events();
+        assert!(resumed.send_request(get("b")));
+        resumed.send_request(get("c"));
+        let events: Vec<(EventKind, Vec<u8>)> = resumed.events().into_iter().map(|event| (event.kind, event.label)).collect();
+        assert_eq!(events, vec![
+            (EventKind::Hit, b"b".to_vec()),
+            (EventKind::Evict, b"a".to_vec()),
+            (EventKind::Insert, b"c".to_vec()),
+        ]);
+    }
+}
@@ -1,28 +1,52 @@
 use std::borrow::Borrow;
 use std::collections::{HashMap, HashSet};
-use flate2::read::GzDecoder;
-use std::fs::File;
-use std::io::Read;
+use std::ops::Range;
 use std::path::Path;
 use std::str::FromStr;
 use std::time::Duration;
 
 use itertools::{Group, GroupBy, Itertools};
 
-use clap::{App, Arg};
-use wasmer::{Module, Store};
-use fifo::FiFo;
-use gdsize::GdSize;
-use lfu::LFU;
-use lru::LRU;
+use admission::Admission;
+use algorithm::Params;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde::Serialize;
+use wasmer::Store;
 use simulator_shared_types::FileRecord;
-use crate::cached_policy::{WasmCachedBincodePolicyModule, WasmCachedBytemuckPolicyModule, WasmCachedPairPolicyModule};
-use crate::native_modules::NativePolicyModule;
-use crate::policy::{PolicyModule, WasmBincodePolicyModule, WasmBytemuckPolicyModule, WasmPairPolicyModule};
+use crate::checkpoint::{Checkpoint, Checkpoints};
+use crate::cost::{CostModel, Costs};
+use crate::cluster::{Cluster, MembershipChange, Routing};
+use crate::events::{EventRecorder, EventReport};
+use crate::expiry::{Expiry, ExpiryMode};
+use crate::hierarchy::{Hierarchy, Placement};
+use crate::keys::{KeyType, SimKey, TraceKey};
+use crate::mrc::MrcPoint;
+use crate::policy::PolicyModule;
+use crate::registry::{Alg, Encoding};
+use crate::series::{Series, SeriesReport, Window};
+use crate::shards::Sample;
+use crate::sweep::{Axis, Config};
+use crate::trace::{SizeChange, TraceFormat, WritePolicy};
+use crate::warmup::{Warmup, WarmupStats};
 
 mod policy;
 mod native_modules;
 mod cached_policy;
+mod checkpoint;
+mod cost;
+mod cluster;
+mod events;
+mod expiry;
+mod hierarchy;
+mod keys;
+mod mrc;
+mod registry;
+mod series;
+mod shards;
+mod sweep;
+mod trace;
+mod trace_stats;
+mod warmup;
 
 use plotters::prelude::*;
 
@@ -33,317 +57,401 @@ fn main() {
         .version("0.1")
         .author("Devon Hockley")
         .about("A caching policy simulator implemented using WASM")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("sample")
             .help("Sets the input data sample")
             .index(1)
             .required(true)
         )
+        .args(&sampling_args())
+        .arg(size_change_arg())
+        .arg(columns_arg())
+        .arg(keys_arg())
+        .arg(Arg::with_name("hash-keys")
+            .help("Runs the pair and bytemuck modules on byte string keys by hashing them to u64")
+            .long("hash-keys")
+        )
+        .arg(write_policy_arg())
+        .arg(Arg::with_name("admission")
+            .help("Admission filter in front of every policy: all, tinylfu[:THRESHOLD], second-hit, size:BYTES or adaptsize:C")
+            .long("admission")
+            .takes_value(true)
+            .default_value("all")
+        )
+        .arg(params_arg())
+        .arg(Arg::with_name("cost-model")
+            .help("Cost of a miss for records without a cost column, as LATENCY:BANDWIDTH, a fetch latency in microseconds plus the transfer at a backend bandwidth in MB/s, e.g. 20000:100")
+            .long("cost-model")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("ttl")
+            .help("Lifetime of objects without a TTL column, in trace time units, 0 never expires")
+            .long("ttl")
+            .takes_value(true)
+            .default_value("0")
+        )
+        .arg(Arg::with_name("events")
+            .help("Records every policy's insert, hit, evict and bypass events, writing eviction ages and a check of each encoding against Native to this JSON file")
+            .long("events")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("series")
+            .help("Writes hit ratio, byte hit ratio and occupancy per window to this CSV file, with a hit ratio chart per cache size next to it")
+            .long("series")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("series-window")
+            .help("Number of requests per series window")
+            .long("series-window")
+            .takes_value(true)
+            .default_value("10000")
+            .conflicts_with("series-window-time")
+        )
+        .arg(Arg::with_name("series-window-time")
+            .help("Length of the series windows in trace time units instead of requests")
+            .long("series-window-time")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("warmup")
+            .help("Leaves the first requests out of the reported statistics, given as a count, a fraction of the trace such as 0.1, or full to wait until the cache first fills")
+            .long("warmup")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("checkpoint-interval")
+            .help("Saves the state of every run after this many requests, and again when it finishes")
+            .long("checkpoint-interval")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("checkpoint-dir")
+            .help("Directory checkpoints are saved in and resumed from")
+            .long("checkpoint-dir")
+            .takes_value(true)
+            .default_value("checkpoints")
+        )
+        .arg(Arg::with_name("resume")
+            .help("Starts every run from its checkpoint where one was saved")
+            .long("resume")
+        )
+        .arg(Arg::with_name("expiry")
+            .help("Drops expired objects when they are next requested, or as they expire with a timer wheel")
+            .long("expiry")
+            .takes_value(true)
+            .possible_values(&["lazy", "wheel"])
+            .default_value("lazy")
+        )
+        .subcommand(SubCommand::with_name("mrc")
+            .about("Computes the LRU miss ratio curve of a trace in a single pass")
+            .arg(Arg::with_name("sample")
+                .help("Sets the input data sample")
+                .index(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("granularity")
+                .help("Rounds stack distances up to a multiple of this many bytes")
+                .long("granularity")
+                .takes_value(true)
+                .default_value("65536")
+            )
+            .arg(Arg::with_name("output")
+                .help("CSV file to write the curve to, a plot is written next to it")
+                .long("output")
+                .takes_value(true)
+                .default_value("result_graphs/mrc_lru.csv")
+            )
+            .args(&sampling_args())
+            .arg(size_change_arg())
+            .arg(columns_arg())
+            .arg(keys_arg())
+            .arg(write_policy_arg())
+        )
+        .subcommand(SubCommand::with_name("trace-stats")
+            .about("Characterizes a trace before picking cache sizes")
+            .arg(Arg::with_name("sample")
+                .help("Sets the input data sample")
+                .index(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("window")
+                .help("Number of requests per working set window")
+                .long("window")
+                .takes_value(true)
+                .default_value("10000")
+            )
+            .arg(Arg::with_name("top")
+                .help("Number of most popular objects to list")
+                .long("top")
+                .takes_value(true)
+                .default_value("10")
+            )
+            .arg(Arg::with_name("output")
+                .help("JSON file to write the report to")
+                .long("output")
+                .takes_value(true)
+                .default_value("result_graphs/trace_stats.json")
+            )
+            .arg(Arg::with_name("plot")
+                .help("Also writes popularity, reuse distance and working set plots next to the report")
+                .long("plot")
+            )
+            .arg(columns_arg())
+            .arg(keys_arg())
+        )
+        .subcommand(SubCommand::with_name("hierarchy")
+            .about("Simulates a hierarchy of caches, such as memory in front of flash")
+            .arg(Arg::with_name("sample")
+                .help("Sets the input data sample")
+                .index(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("tier")
+                .help("A tier as ALG:ENCODING:MB[:PARAMS], e.g. lru:native:64 or slru:native:64:protected_ratio=0.5, repeated from the top tier down")
+                .long("tier")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("placement")
+                .help("Whether objects are copied into every tier, kept in exactly one, or promoted a tier per hit")
+                .long("placement")
+                .takes_value(true)
+                .possible_values(&["inclusive", "exclusive", "promote"])
+                .default_value("inclusive")
+            )
+            .arg(Arg::with_name("output")
+                .help("JSON file to write the report to")
+                .long("output")
+                .takes_value(true)
+                .default_value("result_graphs/hierarchy.json")
+            )
+            .arg(Arg::with_name("hash-keys")
+                .help("Runs the pair and bytemuck modules on byte string keys by hashing them to u64")
+                .long("hash-keys")
+            )
+            .arg(columns_arg())
+            .arg(keys_arg())
+            .arg(write_policy_arg())
+        )
+        .subcommand(SubCommand::with_name("cluster")
+            .about("Simulates a cluster of cache nodes that requests are spread over by hashing their label")
+            .arg(Arg::with_name("sample")
+                .help("Sets the input data sample")
+                .index(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("alg")
+                .help("Policy every node runs")
+                .long("alg")
+                .takes_value(true)
+                .default_value("lru")
+            )
+            .arg(Arg::with_name("encoding")
+                .help("How requests reach the policy of every node")
+                .long("encoding")
+                .takes_value(true)
+                .default_value("native")
+            )
+            .arg(Arg::with_name("nodes")
+                .help("Number of nodes at the start of the trace")
+                .long("nodes")
+                .takes_value(true)
+                .default_value("4")
+            )
+            .arg(Arg::with_name("node-size")
+                .help("Cache size of every node, in MB")
+                .long("node-size")
+                .takes_value(true)
+                .default_value("64")
+            )
+            .arg(Arg::with_name("routing")
+                .help("How requests are routed to nodes")
+                .long("routing")
+                .takes_value(true)
+                .possible_values(&["ring", "rendezvous", "modulo"])
+                .default_value("ring")
+            )
+            .arg(Arg::with_name("vnodes")
+                .help("Virtual nodes per node on the consistent hash ring")
+                .long("vnodes")
+                .takes_value(true)
+                .default_value("160")
+            )
+            .arg(params_arg())
+            .arg(Arg::with_name("membership")
+                .help("A membership change as REQUEST:fail:NODE, REQUEST:recover:NODE or REQUEST:add, may be repeated")
+                .long("membership")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("output")
+                .help("JSON file to write the report to")
+                .long("output")
+                .takes_value(true)
+                .default_value("result_graphs/cluster.json")
+            )
+            .arg(Arg::with_name("hash-keys")
+                .help("Runs the pair and bytemuck modules on byte string keys by hashing them to u64")
+                .long("hash-keys")
+            )
+            .arg(columns_arg())
+            .arg(keys_arg())
+            .arg(write_policy_arg())
+        )
+        .subcommand(SubCommand::with_name("sweep")
+            .about("Searches a grid of policy parameters at several cache sizes, for the best configuration and how much each parameter matters")
+            .arg(Arg::with_name("sample")
+                .help("Sets the input data sample")
+                .index(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("alg")
+                .help("Policy to tune, may be repeated")
+                .long("alg")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("encoding")
+                .help("How requests reach the policies")
+                .long("encoding")
+                .takes_value(true)
+                .default_value("native")
+            )
+            .arg(Arg::with_name("grid")
+                .help("A parameter axis as [ALG:]NAME=START:END:STEP or [ALG:]NAME=V1,V2,..., e.g. slru:protected_ratio=0.1:0.9:0.1, repeated for a grid of every combination. Axes without ALG sweep every policy taking the parameter")
+                .long("grid")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+            )
+            .arg(params_arg())
+            .arg(Arg::with_name("sizes")
+                .help("Comma separated cache sizes in MB")
+                .long("sizes")
+                .takes_value(true)
+                .default_value("4,8,16,32,64")
+            )
+            .arg(Arg::with_name("admission")
+                .help("Admission filter in front of every policy: all, tinylfu[:THRESHOLD], second-hit, size:BYTES or adaptsize:C")
+                .long("admission")
+                .takes_value(true)
+                .default_value("all")
+            )
+            .arg(Arg::with_name("jobs")
+                .help("Number of configurations simulated at once, every available core by default")
+                .long("jobs")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("output")
+                .help("JSON file to write the report to")
+                .long("output")
+                .takes_value(true)
+                .default_value("result_graphs/sweep.json")
+            )
+            .arg(Arg::with_name("hash-keys")
+                .help("Runs the pair and bytemuck modules on byte string keys by hashing them to u64")
+                .long("hash-keys")
+            )
+            .arg(columns_arg())
+            .arg(keys_arg())
+            .arg(write_policy_arg())
+        )
         .get_matches();
 
-    let file_path = matches.value_of("sample").unwrap();
-
-    println!("Using input file: {}", file_path);
-
-
-
-    let file = File::open(file_path).unwrap();
-    let mut decompressed = GzDecoder::new(file);
-
+    if let Some(matches) = matches.subcommand_matches("mrc") {
+        let file_path = matches.value_of("sample").unwrap();
+        let granularity = i64::from_str(matches.value_of("granularity").unwrap()).expect("Invalid granularity");
+        let output = matches.value_of("output").unwrap();
 
+        println!("Using input file: {}", file_path);
+        let curve = match key_type(matches) {
+            KeyType::I32 => mrc_curve::<i32>(matches, granularity),
+            KeyType::U64 => mrc_curve::<u64>(matches, granularity),
+            KeyType::Bytes => mrc_curve::<Vec<u8>>(matches, granularity),
+        };
+        mrc::write_csv(&curve, Path::new(output));
+        mrc::plot(&curve, Path::new(output).with_extension("png").as_path());
+        return;
+    }
 
-    let mut string = String::new();
-    decompressed.read_to_string(&mut string).unwrap();
+    if let Some(matches) = matches.subcommand_matches("trace-stats") {
+        let file_path = matches.value_of("sample").unwrap();
 
-    let data : Vec<FileRecord<i32>> = string.lines().map(
-        |line| {
-            if let [first, second, ..] = line.trim().split_ascii_whitespace().collect::<Vec<&str>>().as_slice() {
-                (i32::from_str(first).unwrap(),i64::from_str(second).unwrap())
-            } else {
-                panic!()
-            }
-        }
-    ).map(
-        |(first, second)| {
-            FileRecord::<i32>{
-                label : first,
-                size : second
-            }
+        println!("Using input file: {}", file_path);
+        match key_type(matches) {
+            KeyType::I32 => trace_stats_report::<i32>(matches),
+            KeyType::U64 => trace_stats_report::<u64>(matches),
+            KeyType::Bytes => trace_stats_report::<String>(matches),
         }
-    ).collect();
+        return;
+    }
 
-    let mut map = HashMap::<i32,i64>::new();
-    for i in data.clone(){
-        if map.contains_key(&i.label){
+    if let Some(matches) = matches.subcommand_matches("hierarchy") {
+        let file_path = matches.value_of("sample").unwrap();
 
-        } else {
-            map.insert(i.label, i.size);
+        println!("Using input file: {}", file_path);
+        match key_type(matches) {
+            KeyType::I32 => hierarchy_report::<i32>(matches),
+            KeyType::U64 => hierarchy_report::<u64>(matches),
+            KeyType::Bytes => hierarchy_report::<Vec<u8>>(matches),
         }
+        return;
     }
 
-    let data : Vec<FileRecord<i32>> = data.iter().map(|i | {
-        let size = map.get(&i.label).unwrap();
-        FileRecord::<i32>{
-            label: i.label,
-            size: *size
-        }
-    }).collect();
-
-    let mut results : Vec<SimResult> = vec![];
-
-    //let module_names = vec!["wasm_pair_fifo"];
-
-    let mut size : i64 = 512 * 1024 * 4;
-    while size < 1024*1024*1024*8 {
-        size *= 2;
-
-        let store = Store::default();
-
-        let mut policies: Vec<(&str,Box<dyn PolicyModule<i32>>)> = vec![];
-        policies.push((
-            "Native FiFo"
-             ,Box::new(NativePolicyModule::<FiFo<i32>,i32>::new()
-            )
-        ));
-
-
-
-        let wasm_pair = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_fifo.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Pair FiFo",wasm_pair));
-
-        let wasm_pair = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_fifo.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Cached Pair FiFo",wasm_pair));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_fifo.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bincode FiFo",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_fifo.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bincode FiFo",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_fifo.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bytemuck FiFo",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_fifo.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bytemuck FiFo",wasm_bincode));
-
-        policies.push(("Native LRU",Box::new(NativePolicyModule::<LRU<i32>,i32>::new())));
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_lru.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Pair LRU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_lru.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Pair LRU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_lru.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bincode LRU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_lru.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bincode LRU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_lru.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bytemuck LRU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_lru.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bytemuck LRU",wasm_bincode));
-
-
-        policies.push(("Native LFU",Box::new(NativePolicyModule::<LFU<i32>,i32>::new())));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_lfu.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Pair LFU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_lfu.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Pair LFU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_lfu.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bincode LFU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_lfu.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bincode LFU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_lfu.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bytemuck LFU",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_lfu.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bytemuck LFU",wasm_bincode));
-
-        policies.push(("Native GdSize",Box::new(NativePolicyModule::<GdSize<i32>,i32>::new())));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_gdsize.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Pair GdSize",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_pair_gdsize.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedPairPolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Pair GdSize",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_gdsize.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bincode GdSize",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_bincode_gdsize.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBincodePolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bincode GdSize",wasm_bincode));
-
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_gdsize.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("WASM Bytemuck GdSize",wasm_bincode));
-
-        let wasm_bincode = {
-            let path = Path::new("./modules/wasm32-unknown-unknown/release/wasm_c_gdsize.wasm");
-            let module = Module::from_file(&store,path).expect("Module Not Found");
-            Box::new(WasmCachedBytemuckPolicyModule::from_module(module))
-        };
-
-        policies.push(("Cached WASM Bytemuck GdSize",wasm_bincode));
-
-        for (name, mut policy) in policies {
-
-            let start = std::time::Instant::now();
-            policy.initialize(size);
-            for file in &data {
-                policy.send_request((*file).clone())
-            }
-            let (total, hits) = policy.stats();
-            let end = std::time::Instant::now();
+    if let Some(matches) = matches.subcommand_matches("cluster") {
+        let file_path = matches.value_of("sample").unwrap();
 
+        println!("Using input file: {}", file_path);
+        match key_type(matches) {
+            KeyType::I32 => cluster_report::<i32>(matches),
+            KeyType::U64 => cluster_report::<u64>(matches),
+            KeyType::Bytes => cluster_report::<Vec<u8>>(matches),
+        }
+        return;
+    }
 
-            let alg_string = match name {
-                x if x.contains("FiFo") => Alg::Fifo,
-                x if x.contains("GdSize") => Alg::GdSize,
-                x if x.contains("LRU") => Alg::LRU,
-                x if x.contains("LFU") => Alg::LFU,
-                _ => panic!()
-            };
+    if let Some(matches) = matches.subcommand_matches("sweep") {
+        let file_path = matches.value_of("sample").unwrap();
 
-            let a = SimResult{
-                size: size,
-                alg: alg_string,
-                name: name,
-                hits: hits,
-                time: (end-start).as_secs_f64(),
-                hitrate: (hits as f32/total as f32 * 100.0)
-            };
+        println!("Using input file: {}", file_path);
+        match key_type(matches) {
+            KeyType::I32 => sweep_report::<i32>(matches),
+            KeyType::U64 => sweep_report::<u64>(matches),
+            KeyType::Bytes => sweep_report::<Vec<u8>>(matches),
+        }
+        return;
+    }
 
-            results.push(a)
+    let file_path = matches.value_of("sample").unwrap();
 
-        }
+    println!("Using input file: {}", file_path);
 
-    }
+    let (results, lru_curve, expiring, costing) = match key_type(&matches) {
+        KeyType::I32 => simulate::<i32>(&matches),
+        KeyType::U64 => simulate::<u64>(&matches),
+        KeyType::Bytes => simulate::<Vec<u8>>(&matches),
+    };
+    let warming = matches.is_present("warmup");
 
     for (key,group) in &results.clone().into_iter().group_by(|a| a.size){
         println!("Size: {0:<10} ",key/(1024*1024));
         for a in group{
-            println!("Name: {0:<30} | Hits: {1:<10} | Time: {2:<10} | Hitrate: {3:<10}", a.name, a.hits,a.time, a.hitrate);
+            let mut line = format!("Name: {0:<30} | Hits: {1:<10} | ", a.name(), a.hits);
+            if expiring {
+                line += &format!("Expired Misses: {0:<10} | Expirations: {1:<10} | ", a.expired_misses, a.expirations);
+            }
+            if warming {
+                line += &format!("Warmup Requests: {0:<10} | Warmup Hits: {1:<10} | Warmup Hitrate: {2:<10} | ", a.warmup_requests, a.warmup_hits, a.warmup_hitrate);
+            }
+            if costing {
+                line += &format!("Cost Saved: {0:<14} | Avg Miss Cost: {1:<10.1} | Backend MB: {2:<10} | Backend Bandwidth: {3:<10.1} | ",
+                                 a.cost_saved, a.average_miss_cost, a.backend_bytes / (1024 * 1024), a.backend_bandwidth);
+            }
+            line += &format!("Time: {0:<10} | Hitrate: {1:<10}", a.time, a.hitrate);
+            println!("{}", line);
             //println!("{0:<30} {1:<10} {2:<10} {3:<10}", a.name, a.hits, a.time, a.hitrate);
         }
     }
@@ -385,15 +493,15 @@ fn main() {
 
 
 
-    let colors: Vec<ShapeStyle> = vec![
-                                        BLACK.mix(0.0).filled(),
-                                        BLACK.filled(),
-                                        DR_YELLOW.mix(0.7).filled(),
-                                        DR_YELLOW.mix(0.4).filled(),
-                                        BLUE.mix(0.7).filled(),
-                                        BLUE.mix(0.4).filled(),
-                                        D_GREEN.mix(0.7).filled(),
-                                        D_GREEN.mix(0.4).filled()
+    let colors: Vec<plotters::style::RGBAColor> = vec![
+                                        BLACK.mix(0.0),
+                                        BLACK.mix(1.0),
+                                        DR_YELLOW.mix(0.7),
+                                        DR_YELLOW.mix(0.4),
+                                        BLUE.mix(0.7),
+                                        BLUE.mix(0.4),
+                                        D_GREEN.mix(0.7),
+                                        D_GREEN.mix(0.4)
     ];
 
     for (size,group) in &results.clone().into_iter().group_by(|a| a.size){
@@ -405,8 +513,9 @@ fn main() {
 
         root.fill(&WHITE);
 
-        let x_spec =(0u32..((group.len() + 4) as u32)).with_key_points(
-            vec![4,12,20,28]
+        // Eight columns per algorithm, a blank one then one per encoding
+        let x_spec =(0u32..((Alg::ALL.len() * 8) as u32)).with_key_points(
+            (0..Alg::ALL.len() as u32).map(|i| i*8 + 4).collect()
         );
         //let x_text_spec = plotters::prelude::ToGroupByRange::group_by(0u32..(group.len() as u32), 6).into_segmented();
 
@@ -425,14 +534,11 @@ fn main() {
             .unwrap();
 
         &chart.configure_mesh()
-            .x_labels(4)
+            .x_labels(Alg::ALL.len())
             .x_label_formatter(&|x| {
 
-                match x  {
-                    4 => "FIFO",
-                    12 => "LRU",
-                    20 => "LFU",
-                    28 => "GD-SIZE",
+                match Alg::ALL.get((*x / 8) as usize) {
+                    Some(alg) if x % 8 == 4 => alg.name(),
                     _ => {"E"}
                 }.to_string()
             })
@@ -449,36 +555,22 @@ fn main() {
 
             let names = vec!["FILLER","Native","Pair","Cached Pair","Bincode","Cached Bincode","Bytemuck","Cached Bytemuck"];
 
-            let i = i.map(|b| b.time);
-
-            // create blank column to insert at start to separate groups
-            let blank = std::iter::once(0.0); // blank column for spacer
+            // Colours and legend entries follow the encoding, so runs that leave an encoding out stay readable
+            let column = |encoding: Encoding| Encoding::ALL.iter().position(|e| *e == encoding).unwrap() + 1;
 
-            let data = blank
-                .chain(i).enumerate().zip(colors.clone())
-                .map(|((a,b),color)| (((group as u32)*8+a as u32,b),color)); // re-wrap data columns from structs to raw pair (might not be needed)
+            // blank column at the start of each group to separate them
+            chart.draw_series(vec![Rectangle::new([((group as u32)*8,0f64),((group as u32)*8+1,0f64)],colors[0].filled())]).unwrap();
 
-            // Combine and map to rectangles
-            let rects = data.map(|((a,b),color)|{
-                Rectangle::new([(a,0f64),(a+1,b)],color)
-            });
-
-
-
-            if group == 0 {
-                for (i,rect) in rects.into_iter().enumerate(){
-                    if i == 0 {
-                        chart.draw_series(vec![rect]).unwrap();
-                    } else {
-                        let color = colors[i].clone();
-                        chart.draw_series(vec![rect]).unwrap()
-                            .label(names[i])
-                            .legend(move |(x, y)| Rectangle::new([(x, y-5), (x + 20, y+5)], color.clone()),);
-                    }
+            for (a,result) in i.enumerate() {
+                let x = (group as u32)*8 + a as u32 + 1;
+                let c = column(result.encoding);
+                let color = colors[c];
+                let series = chart.draw_series(vec![Rectangle::new([(x,0f64),(x+1,result.time)],color.filled())]).unwrap();
 
+                if group == 0 {
+                    series.label(names[c])
+                        .legend(move |(x, y)| Rectangle::new([(x, y-5), (x + 20, y+5)], color.filled()),);
                 }
-            } else {
-                chart.draw_series(rects);
             }
 
 
@@ -531,7 +623,7 @@ fn main() {
     //println!("{:?}", natives_by_alg);
 
 
-    let colors: Vec<RGBColor> = vec![RED,GREEN,BLUE,MAGENTA];
+    let colors: Vec<RGBColor> = vec![RED,GREEN,BLUE,MAGENTA,CYAN,D_YELLOW,D_GREEN,DR_YELLOW];
 
     {
         let file = format!("result_graphs/hitrate_{}.png",mode);
@@ -562,35 +654,38 @@ fn main() {
             .y_desc("Hitrate (%)")
             .draw().unwrap();
 
-        let display_order = vec![Alg::Fifo,Alg::LRU,Alg::LFU,Alg::GdSize];
-
-        let results: Vec<Vec<SimResult>> = display_order.iter().map(|a|{
+        let results: Vec<Vec<SimResult>> = Alg::ALL.iter().map(|a|{
             natives_by_alg.get(a).unwrap().clone()
         }
         ).collect();
 
-        for (i,c) in results.iter().zip(colors) {
+        for (i,c) in results.iter().zip(colors.into_iter().cycle()) {
 
-            let name = match i.first().unwrap().alg {
-                Alg::Fifo => "FiFo",
-                Alg::GdSize => "GdSize",
-                Alg::LFU => "LFU",
-                Alg::LRU => "LRU",
-            };
+            let name = i.first().unwrap().alg.name();
             let count = i.len();
             chart.draw_series(
                 i.iter().map(|a|{
                    Circle::new((a.size,a.hitrate as f64),2, c.mix(0.5).filled())
-                }).take(count-6)
+                }).take(count.saturating_sub(6))
             ).unwrap().label(name).legend(move |(x, y)| Rectangle::new([(x, y-5), (x + 20, y+5)], c.mix(0.5).filled()),);
 
             chart.draw_series(
                 LineSeries::new(
-                    i.iter().take(count-6).map(|a| ((a.size,a.hitrate as f64))),
+                    i.iter().take(count.saturating_sub(6)).map(|a| (a.size,a.hitrate as f64)),
                     c
                 )
             );
         }
+        // Analytic LRU curve from the stack distance pass, to check the simulated LRU points against
+        chart.draw_series(
+            LineSeries::new(
+                lru_curve.iter()
+                    .map(|p| (p.size, (1.0 - p.miss_ratio) * 100.0))
+                    .filter(|(size, hitrate)| *size >= 2*(1024*1024) && *size < 150*(1024*1024) && *hitrate >= 70.0),
+                &BLACK
+            )
+        ).unwrap().label("LRU (MRC)").legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK));
+
         chart.configure_series_labels()
             .border_style(&BLACK)
             .background_style(&WHITE)
@@ -606,20 +701,589 @@ fn main() {
 
 }
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
-enum Alg{
-    Fifo,
-    LFU,
-    LRU,
-    GdSize,
+// Runs every policy over the trace at every cache size, with keys of type K
+fn simulate<K>(matches: &ArgMatches) -> (Vec<SimResult>, Vec<MrcPoint>, bool, bool) where K : SimKey {
+    let size_change = SizeChange::from_str(matches.value_of("size-change").unwrap()).unwrap();
+
+    let expiry_mode = ExpiryMode::from_str(matches.value_of("expiry").unwrap()).unwrap();
+    let ttl = u64::from_str(matches.value_of("ttl").unwrap()).expect("Invalid TTL");
+    let hash_keys = matches.is_present("hash-keys");
+    let admission = Admission::from_str(matches.value_of("admission").unwrap()).unwrap();
+    let params = policy_params(matches);
+    // Every policy gets the same parameters, each only has to know some of them
+    let declared: Vec<&str> = Alg::ALL.iter().flat_map(|alg| alg.params().iter().copied()).collect();
+    params.check(&declared).unwrap();
+    for alg in Alg::ALL.iter() {
+        alg.check_params(&params.only(alg.params())).unwrap();
+    }
+    let events_output = matches.value_of("events");
+    let warmup = matches.value_of("warmup").map(|w| Warmup::from_str(w).unwrap());
+    let series_output = matches.value_of("series");
+    let series_window = match matches.value_of("series-window-time") {
+        Some(span) => Window::Time(u64::from_str(span).expect("Invalid window length")),
+        None => Window::Requests(u64::from_str(matches.value_of("series-window").unwrap()).expect("Invalid window size"))
+    };
+    if let Window::Requests(0) | Window::Time(0) = series_window {
+        panic!("Series windows must be at least 1 long");
+    }
+    let checkpoint_interval = matches.value_of("checkpoint-interval").map(|n| usize::from_str(n).expect("Invalid checkpoint interval"));
+    let checkpoints = Checkpoints::new(Path::new(matches.value_of("checkpoint-dir").unwrap()), checkpoint_interval, matches.is_present("resume"));
+
+    let data = trace::apply_write_policy(load_trace::<K>(matches), write_policy(matches));
+    let data = trace::apply_default_ttl(data, ttl);
+    let data = match matches.value_of("cost-model") {
+        Some(model) => cost::apply_cost_model(data, &CostModel::from_str(model).unwrap()),
+        None => data
+    };
+    let sample = sample_trace(matches, trace::apply_size_change(data, size_change));
+    let data = &sample.data;
+    let changed = match size_change {
+        SizeChange::Last | SizeChange::Invalidate | SizeChange::Resize => trace::size_changes(data),
+        SizeChange::First => vec![]
+    };
+    let largest = data.iter().map(|file| file.size).max().unwrap_or(0);
+    let expiring = data.iter().any(|file| file.ttl > 0);
+    let costing = data.iter().any(|file| file.cost > 0);
+
+    let lru_curve = sampled_lru_curve(&sample, 64 * 1024);
+
+    let mut results : Vec<SimResult> = vec![];
+    let mut event_reports : Vec<EventReport> = vec![];
+    let mut series_reports : Vec<SeriesReport> = vec![];
+
+    let mut size : i64 = 512 * 1024 * 4;
+    while size < 1024*1024*1024*8 {
+        size *= 2;
+
+        // Sampled traces run against a cache scaled down by the same rate
+        let cache_size = sample.scale_size(size);
+        if cache_size < largest {
+            println!("Skipping {} MB, the scaled cache cannot hold the largest sampled object", size/(1024*1024));
+            continue;
+        }
+
+        let store = Store::default();
+
+        for alg in Alg::ALL.iter() {
+            let mut native_digest = None;
+            for (encoding, mut policy) in registry::policies::<K>(&store, *alg, hash_keys) {
+
+                let start = std::time::Instant::now();
+                let checkpoint_path = checkpoints.path(size, *alg, encoding);
+                let (mut position, elapsed, expiry, mut warmup_stats, costs) = match checkpoints.load::<K>(&checkpoint_path, data.len()) {
+                    Some(checkpoint) => {
+                        policy.restore(&checkpoint.policy);
+                        (checkpoint.position, checkpoint.elapsed, checkpoint.expiry, checkpoint.warmup, checkpoint.costs)
+                    }
+                    None => {
+                        policy.initialize(cache_size, &params);
+                        policy.set_admission(admission);
+                        // Without a warmup there is nothing to wait for
+                        let warmup_stats = if warmup.is_some() { None } else { Some(WarmupStats::default()) };
+                        (0, 0.0, if expiring { Some(Expiry::new(expiry_mode)) } else { None }, warmup_stats, Costs::default())
+                    }
+                };
+                // Expiry follows evictions through the events, to forget objects that left the cache
+                if events_output.is_some() || expiry.is_some() {
+                    policy.enable_events();
+                }
+                let mut run = RunState {
+                    expiry,
+                    events: events_output.map(|_| EventRecorder::new()),
+                    series: series_output.map(|_| Series::new(series_window, cache_size)),
+                    costs,
+                };
+                loop {
+                    let checkpoint_stop = checkpoints.next_stop(position, data.len());
+                    // A warmup until the cache fills is checked after every request
+                    let warmup_stop = match (warmup, warmup_stats) {
+                        (Some(warmup), None) => warmup.end(data.len()).unwrap_or(position + 1).max(position),
+                        _ => data.len()
+                    };
+                    let stop = checkpoint_stop.min(warmup_stop);
+                    replay(policy.as_mut(), data, position..stop, &changed, size_change, &mut run);
+                    position = stop;
+                    if warmup_stats.is_none() {
+                        let ended = match warmup.unwrap().end(data.len()) {
+                            Some(end) => position >= end,
+                            None => policy.filled() || position == data.len()
+                        };
+                        if ended {
+                            warmup_stats = Some(end_warmup(policy.as_mut(), position, &mut run));
+                        }
+                    }
+                    if checkpoints.saving() && stop == checkpoint_stop {
+                        checkpoints.save(&checkpoint_path, &Checkpoint {
+                            requests: data.len(),
+                            position,
+                            elapsed: elapsed + start.elapsed().as_secs_f64(),
+                            policy: policy.snapshot(),
+                            expiry: run.expiry.clone(),
+                            warmup: warmup_stats,
+                            costs: run.costs,
+                        });
+                    }
+                    if position == data.len() {
+                        break;
+                    }
+                }
+                let (total, hits) = policy.stats();
+                let expired_misses = run.expiry.as_ref().map(|e| e.expired_misses as i32).unwrap_or(0);
+                let expirations = run.expiry.as_ref().map(|e| e.expirations as i32).unwrap_or(0);
+                let warmup_stats = warmup_stats.unwrap();
+                if warmup == Some(Warmup::Full) && warmup_stats.requests == data.len() {
+                    println!("{} {} never filled a {} MB cache, the whole trace was warmup", encoding.name(), alg.name(), size/(1024*1024));
+                }
+                let end = std::time::Instant::now();
+
+                let a = SimResult{
+                    size,
+                    alg: *alg,
+                    encoding,
+                    hits: sample.scale_count(hits),
+                    expired_misses: sample.scale_count(expired_misses),
+                    expirations: sample.scale_count(expirations),
+                    time: elapsed + (end-start).as_secs_f64(),
+                    hitrate: (hits as f32/total as f32 * 100.0),
+                    warmup_requests: sample.scale_count(warmup_stats.requests as i32),
+                    warmup_hits: sample.scale_count(warmup_stats.hits),
+                    warmup_hitrate: (warmup_stats.hits as f32/warmup_stats.reads as f32 * 100.0),
+                    cost_saved: sample.scale_total(run.costs.saved()),
+                    average_miss_cost: run.costs.average_miss(),
+                    backend_bytes: sample.scale_total(run.costs.backend_bytes),
+                    backend_bandwidth: run.costs.backend_bandwidth() / sample.rate
+                };
+
+                if let Some(series) = run.series {
+                    series_reports.push(SeriesReport { name: a.name(), encoding, size, points: series.finish(policy.as_ref()) });
+                }
+
+                if let Some(recorder) = run.events {
+                    let summary = recorder.finish();
+                    // Every encoding runs the same algorithm code, so any difference in decisions is a bug
+                    match native_digest {
+                        None => native_digest = Some(summary.digest),
+                        Some(digest) if digest != summary.digest => println!("{} at {} MB made different decisions than Native {}", a.name(), size/(1024*1024), alg.name()),
+                        Some(_) => {}
+                    }
+                    event_reports.push(EventReport { name: a.name(), size, summary });
+                }
+
+                results.push(a)
+            }
+        }
+    }
+
+    if let Some(output) = events_output {
+        events::write_json(&event_reports, Path::new(output));
+    }
+
+    if let Some(output) = series_output {
+        series::write_csv(&series_reports, Path::new(output));
+        // Every encoding of an algorithm has the same series, so only Native is drawn
+        series::plot(series_reports.iter().filter(|r| r.encoding == Encoding::Native), Path::new(output));
+    }
+
+    (results, lru_curve, expiring, costing)
+}
+
+fn mrc_curve<K>(matches: &ArgMatches, granularity: i64) -> Vec<MrcPoint> where K : TraceKey {
+    let size_change = SizeChange::from_str(matches.value_of("size-change").unwrap()).unwrap();
+    let data = trace::apply_write_policy(load_trace::<K>(matches), write_policy(matches));
+    let sample = sample_trace(matches, trace::apply_size_change(data, size_change));
+    sampled_lru_curve(&sample, granularity)
+}
+
+fn trace_stats_report<K>(matches: &ArgMatches) where K : TraceKey + Serialize + Ord {
+    let window = usize::from_str(matches.value_of("window").unwrap()).expect("Invalid window");
+    let top = usize::from_str(matches.value_of("top").unwrap()).expect("Invalid object count");
+    let output = Path::new(matches.value_of("output").unwrap());
+
+    let data = load_trace::<K>(matches);
+
+    let stats = trace_stats::analyze(&data, window, top);
+    println!("Requests: {} | Objects: {} | Footprint: {} MB | Zipf alpha: {:.3} | One-hit wonders: {:.3}",
+             stats.requests, stats.unique_objects, stats.footprint_bytes/(1024*1024), stats.zipf_alpha, stats.one_hit_wonder_ratio);
+    println!("Gets: {} | Sets: {} | Deletes: {} | Updates: {}",
+             stats.operations.gets, stats.operations.sets, stats.operations.deletes, stats.operations.updates);
+
+    trace_stats::write_json(&stats, output);
+    if matches.is_present("plot") {
+        trace_stats::plot(&stats, output);
+    }
+}
+
+fn hierarchy_report<K>(matches: &ArgMatches) where K : SimKey {
+    let placement = Placement::from_str(matches.value_of("placement").unwrap()).unwrap();
+    let hash_keys = matches.is_present("hash-keys");
+    let output = Path::new(matches.value_of("output").unwrap());
+
+    let data = trace::apply_write_policy(load_trace::<K>(matches), write_policy(matches));
+    let data = trace::apply_size_change(data, SizeChange::First);
+    let largest = data.iter().map(|file| file.size).max().unwrap_or(0);
+
+    let store = Store::default();
+    let tiers = matches.values_of("tier").unwrap().enumerate().map(|(i, spec)| {
+        let parts: Vec<&str> = spec.split(':').collect();
+        if parts.len() != 3 && parts.len() != 4 {
+            panic!("Tiers are given as ALG:ENCODING:MB[:PARAMS], not {}", spec);
+        }
+        let alg = Alg::from_str(parts[0]).unwrap();
+        let encoding = Encoding::from_str(parts[1]).unwrap();
+        let size = i64::from_str(parts[2]).expect("Invalid tier size") * 1024 * 1024;
+        if size < largest {
+            panic!("Tier {} cannot hold the largest object of the trace", spec);
+        }
+        let policy = registry::policy::<K>(&store, alg, encoding, hash_keys)
+            .unwrap_or_else(|| panic!("The {} encoding cannot carry these keys, try --hash-keys", encoding.name()));
+        let params = parts.get(3).map_or_else(Params::new, |params| Params::from_str(params).unwrap());
+        alg.check_params(&params).unwrap();
+        (format!("L{} {} {}", i + 1, encoding.name(), alg.name()), size, params, policy)
+    }).collect();
+
+    let mut hierarchy = Hierarchy::new(tiers, placement);
+    for file in data {
+        hierarchy.request(file);
+    }
+
+    let report = hierarchy.report();
+    for tier in &report.tiers {
+        println!("Name: {0:<30} | Size: {1:<10} | Reads: {2:<10} | Hits: {3:<10} | Hitrate: {4:<10} | Byte Hitrate: {5:<10} | Promotions: {6:<10} | Demotions: {7:<10}",
+                 tier.name, tier.size/(1024*1024), tier.reads, tier.hits, tier.hit_ratio, tier.byte_hit_ratio, tier.promotions, tier.demotions);
+    }
+    println!("Placement: {0:<27?} | Reads: {1:<10} | Hits: {2:<10} | Hitrate: {3:<10} | Byte Hitrate: {4:<10}",
+             report.placement, report.reads, report.hits, report.hit_ratio, report.byte_hit_ratio);
+
+    hierarchy::write_json(&report, output);
+}
+
+fn cluster_report<K>(matches: &ArgMatches) where K : SimKey {
+    let alg = Alg::from_str(matches.value_of("alg").unwrap()).unwrap();
+    let encoding = Encoding::from_str(matches.value_of("encoding").unwrap()).unwrap();
+    let nodes = usize::from_str(matches.value_of("nodes").unwrap()).expect("Invalid node count");
+    let node_size = i64::from_str(matches.value_of("node-size").unwrap()).expect("Invalid node size") * 1024 * 1024;
+    let routing = Routing::from_str(matches.value_of("routing").unwrap()).unwrap();
+    let vnodes = usize::from_str(matches.value_of("vnodes").unwrap()).expect("Invalid virtual node count");
+    let hash_keys = matches.is_present("hash-keys");
+    let output = Path::new(matches.value_of("output").unwrap());
+    let mut changes: Vec<MembershipChange> = matches.values_of("membership").into_iter().flatten()
+        .map(|change| MembershipChange::from_str(change).unwrap())
+        .collect();
+    changes.sort_by_key(|change| change.at);
+
+    if nodes == 0 {
+        panic!("A cluster needs at least one node");
+    }
+    if routing == Routing::Ring && vnodes == 0 {
+        panic!("Ring routing needs at least one virtual node per node");
+    }
+
+    let data = trace::apply_write_policy(load_trace::<K>(matches), write_policy(matches));
+    let data = trace::apply_size_change(data, SizeChange::First);
+    let largest = data.iter().map(|file| file.size).max().unwrap_or(0);
+    if node_size < largest {
+        panic!("A node of {} MB cannot hold the largest object of the trace", node_size / (1024 * 1024));
+    }
+
+    let store = Store::default();
+    let new_policy = || registry::policy::<K>(&store, alg, encoding, hash_keys)
+        .unwrap_or_else(|| panic!("The {} encoding cannot carry these keys, try --hash-keys", encoding.name()));
+
+    let params = policy_params(matches);
+    alg.check_params(&params).unwrap();
+    let mut cluster = Cluster::new((0..nodes).map(|_| new_policy()).collect(), node_size, params, routing, vnodes);
+    let mut pending = changes.into_iter().peekable();
+    for (i, file) in data.into_iter().enumerate() {
+        while let Some(change) = pending.next_if(|change| change.at <= i) {
+            cluster.change(change, new_policy);
+        }
+        cluster.request(file);
+    }
+
+    let report = cluster.report();
+    for node in &report.nodes {
+        println!("Node: {0:<5} | Alive: {1:<5} | Requests: {2:<10} | Load Share: {3:<10.4} | Hits: {4:<10} | Hitrate: {5:<10}",
+                 node.node, node.alive, node.requests, node.load_share, node.hits, node.hit_ratio);
+    }
+    for phase in &report.phases {
+        println!("Phase at: {0:<10} | Nodes: {1:<5} | Moved: {2:<10.4} | Hits: {3:<10} | Hitrate: {4:<10}",
+                 phase.start, phase.nodes, phase.moved, phase.hits, phase.hit_ratio);
+    }
+    println!("Name: {0:<30} | Routing: {1:<10?} | Imbalance: {2:<10.4} | Hits: {3:<10} | Hitrate: {4:<10} | Byte Hitrate: {5:<10}",
+             format!("{} {}", encoding.name(), alg.name()), report.routing, report.imbalance, report.hits, report.hit_ratio, report.byte_hit_ratio);
+
+    cluster::write_json(&report, output);
+}
+
+fn sweep_report<K>(matches: &ArgMatches) where K : SimKey + Sync {
+    let algs: Vec<Alg> = matches.values_of("alg").unwrap().map(|alg| Alg::from_str(alg).unwrap()).collect();
+    let encoding = Encoding::from_str(matches.value_of("encoding").unwrap()).unwrap();
+    let axes: Vec<Axis> = matches.values_of("grid").unwrap().map(|axis| Axis::from_str(axis).unwrap()).collect();
+    let sizes: Vec<i64> = matches.value_of("sizes").unwrap().split(',')
+        .map(|size| i64::from_str(size.trim()).expect("Invalid cache size") * 1024 * 1024)
+        .collect();
+    let admission = Admission::from_str(matches.value_of("admission").unwrap()).unwrap();
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => usize::from_str(jobs).expect("Invalid job count"),
+        None => std::thread::available_parallelism().map_or(1, |jobs| jobs.get())
+    };
+    let hash_keys = matches.is_present("hash-keys");
+    let output = Path::new(matches.value_of("output").unwrap());
+
+    let data = trace::apply_write_policy(load_trace::<K>(matches), write_policy(matches));
+    let data = trace::apply_size_change(data, SizeChange::First);
+    let largest = data.iter().map(|file| file.size).max().unwrap_or(0);
+
+    let params = policy_params(matches);
+    sweep::check(&algs, &params, &axes).unwrap();
+    let mut configs = vec![];
+    for size in sizes {
+        if size < largest {
+            println!("Skipping {} MB, the cache cannot hold the largest object of the trace", size / (1024 * 1024));
+            continue;
+        }
+        for alg in &algs {
+            configs.extend(sweep::grid(*alg, &params, &axes).into_iter().map(|params| Config { alg: *alg, size, params }));
+        }
+    }
+    println!("Sweeping {} configurations on {} threads", configs.len(), jobs);
+
+    let runs = sweep::run(&data, &configs, admission, jobs, |store, alg| {
+        registry::policy::<K>(store, alg, encoding, hash_keys)
+            .unwrap_or_else(|| panic!("The {} encoding cannot carry these keys, try --hash-keys", encoding.name()))
+    });
+
+    let report = sweep::report(runs, &axes);
+    for best in &report.best {
+        println!("Best: {0:<20} | Size: {1:<10} | Params: {2:<40} | Hits: {3:<10} | Hitrate: {4:<10} | Byte Hitrate: {5:<10}",
+                 best.alg.name(), best.size / (1024 * 1024), best.params.to_string(), best.hits, best.hit_ratio, best.byte_hit_ratio);
+    }
+    for sensitivity in &report.sensitivity {
+        let values: Vec<String> = sensitivity.values.iter().map(|value| format!("{}: {:.4}", value.value, value.hit_ratio)).collect();
+        println!("Sensitivity: {0:<20} | Size: {1:<10} | Param: {2:<20} | Spread: {3:<10.4} | {4}",
+                 sensitivity.alg, sensitivity.size / (1024 * 1024), sensitivity.param, sensitivity.spread, values.join(", "));
+    }
+
+    sweep::write_json(&report, output);
+}
+
+// Parts of a run that follow it request by request, each optional
+struct RunState<K> {
+    expiry: Option<Expiry<K>>,
+    events: Option<EventRecorder<K>>,
+    series: Option<Series>,
+    costs: Costs, // of the reads since the warmup
+}
+
+// Takes the statistics of the `position` requests so far as the warmup's and starts counting the run's from zero.
+fn end_warmup<K>(policy: &mut dyn PolicyModule<K>, position: usize, run: &mut RunState<K>) -> WarmupStats where K : TraceKey {
+    let (reads, hits) = policy.stats();
+    let expired_misses = run.expiry.as_ref().map(|e| e.expired_misses as i32).unwrap_or(0);
+    policy.reset_stats();
+    if let Some(expiry) = run.expiry.as_mut() {
+        expiry.expired_misses = 0;
+        expiry.expirations = 0;
+    }
+    run.costs = Costs::default();
+    WarmupStats { requests: position, reads, hits, expired_misses }
+}
+
+// Sends the requests in `range`, updating the cached copy of objects whose size changed
+fn replay<K>(policy: &mut dyn PolicyModule<K>, data: &[FileRecord<K>], range: Range<usize>, changed: &[bool], size_change: SizeChange, run: &mut RunState<K>) where K : TraceKey {
+    for (i, file) in data.iter().enumerate().take(range.end).skip(range.start) {
+        let size_changed = changed.get(i) == Some(&true);
+        if size_changed {
+            match size_change {
+                SizeChange::Invalidate => { policy.invalidate(file.label.clone()); }
+                SizeChange::Resize => { policy.resize((*file).clone()); }
+                SizeChange::First | SizeChange::Last => {}
+            }
+        }
+        if let Some(series) = run.series.as_mut() {
+            series.begin(i, file.time, policy);
+        }
+        let hit = match run.expiry.as_mut() {
+            Some(expiry) => expiry.request(policy, (*file).clone()),
+            None => policy.send_request((*file).clone())
+        };
+        // The hit is served from the cached copy, which then takes the size of the request
+        if hit && size_changed && size_change == SizeChange::Last {
+            policy.resize((*file).clone());
+        }
+        run.costs.record(file, hit);
+        if let Some(series) = run.series.as_mut() {
+            series.record(file, hit);
+        }
+        if i % events::DRAIN_INTERVAL == events::DRAIN_INTERVAL - 1 {
+            drain_events(policy, run);
+        }
+    }
+    drain_events(policy, run);
+}
+
+fn drain_events<K>(policy: &mut dyn PolicyModule<K>, run: &mut RunState<K>) where K : TraceKey {
+    if run.events.is_none() && run.expiry.is_none() {
+        return;
+    }
+    let events = policy.events();
+    if let Some(expiry) = run.expiry.as_mut() {
+        expiry.forget_evicted(&events);
+    }
+    if let Some(recorder) = run.events.as_mut() {
+        recorder.record_all(events);
+    }
+}
+
+fn size_change_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("size-change")
+        .help("How objects whose size changes between requests are simulated")
+        .long("size-change")
+        .takes_value(true)
+        .possible_values(&["first", "last", "invalidate", "resize"])
+        .default_value("first")
+}
+
+fn columns_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("columns")
+        .help("Comma separated trace columns, from label, size, op, time, ttl, cost and _ for ignored columns")
+        .long("columns")
+        .takes_value(true)
+        .default_value("label,size")
+}
+
+fn write_policy_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("write-policy")
+        .help("Whether sets and updates place the object in the cache or drop the cached copy")
+        .long("write-policy")
+        .takes_value(true)
+        .possible_values(&["allocate", "around"])
+        .default_value("allocate")
+}
+
+fn params_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("params")
+        .help("Policy parameters as NAME=VALUE separated by commas, e.g. protected_ratio=0.5,seed=7. Names none of the simulated policies take are rejected, the others ignore what they do not use")
+        .long("params")
+        .takes_value(true)
+        .default_value("")
+}
+
+fn load_trace<K>(matches: &ArgMatches) -> Vec<FileRecord<K>> where K : TraceKey {
+    let format = TraceFormat::from_str(matches.value_of("columns").unwrap()).unwrap();
+    trace::load_trace(matches.value_of("sample").unwrap(), &format)
+}
+
+fn key_type(matches: &ArgMatches) -> KeyType {
+    KeyType::from_str(matches.value_of("keys").unwrap()).unwrap()
+}
+
+fn keys_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("keys")
+        .help("Type the trace labels are read as, byte strings suit URLs and other text keys")
+        .long("keys")
+        .takes_value(true)
+        .possible_values(&["i32", "u64", "bytes"])
+        .default_value("i32")
+}
+
+fn policy_params(matches: &ArgMatches) -> Params {
+    Params::from_str(matches.value_of("params").unwrap()).unwrap()
+}
+
+fn write_policy(matches: &ArgMatches) -> WritePolicy {
+    WritePolicy::from_str(matches.value_of("write-policy").unwrap()).unwrap()
+}
+
+fn sampling_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("shards-rate")
+            .help("Samples trace keys with SHARDS at this rate, e.g. 0.01")
+            .long("shards-rate")
+            .takes_value(true)
+            .conflicts_with("shards-max-keys"),
+        Arg::with_name("shards-max-keys")
+            .help("Samples trace keys with fixed-size SHARDS, keeping at most this many keys")
+            .long("shards-max-keys")
+            .takes_value(true),
+    ]
+}
+
+fn sample_trace<K>(matches: &ArgMatches, data: Vec<FileRecord<K>>) -> Sample<K> where K : TraceKey {
+    let total = data.len();
+    let sample = if let Some(rate) = matches.value_of("shards-rate") {
+        shards::fixed_rate(data, f64::from_str(rate).expect("Invalid sampling rate"))
+    } else if let Some(max_keys) = matches.value_of("shards-max-keys") {
+        shards::fixed_size(data, usize::from_str(max_keys).expect("Invalid key count"))
+    } else {
+        return Sample::full(data);
+    };
+    println!("Sampled {} of {} requests at rate {}", sample.data.len(), total, sample.rate);
+    sample
+}
+
+// Curve of the sampled trace, with cache sizes mapped back to the full trace
+fn sampled_lru_curve<K>(sample: &Sample<K>, granularity: i64) -> Vec<MrcPoint> where K : TraceKey {
+    mrc::lru_curve(&sample.data, sample.scale_size(granularity).max(1))
+        .into_iter()
+        .map(|point| MrcPoint { size: sample.unscale_size(point.size), ..point })
+        .collect()
 }
 
 #[derive(Clone, Copy,Debug)]
 struct SimResult{
     size: i64,
     alg: Alg,
-    name: &'static str,
+    encoding: Encoding,
     hits: i32,
+    expired_misses: i32,
+    expirations: i32, // cached objects the timer wheel dropped
     time: f64,
-    hitrate: f32
-}
\ No newline at end of file
+    hitrate: f32, // of the requests after the warmup
+    warmup_requests: i32,
+    warmup_hits: i32,
+    warmup_hitrate: f32,
+    cost_saved: u64,
+    average_miss_cost: f64,
+    backend_bytes: u64,
+    backend_bandwidth: f64, // bytes per unit of trace time
+}
+
+impl SimResult {
+    fn name(&self) -> String {
+        format!("{} {}", self.encoding.name(), self.alg.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lru::LRU;
+    use crate::native_modules::NativePolicyModule;
+    use super::*;
+
+    // Replays 1, 2 and 1 again with a new size, returning the hits and the bytes cached
+    fn replay_size_change(cache_size: i64, new_size: i64, size_change: SizeChange) -> (i32, i64) {
+        let data: Vec<FileRecord<i32>> = [(1, 10), (2, 20), (1, new_size)].iter()
+            .map(|&(label, size)| FileRecord { label, size, ..Default::default() })
+            .collect();
+        let changed = trace::size_changes(&data);
+        let mut policy = NativePolicyModule::<LRU<i32>, i32>::new();
+        policy.initialize(cache_size, &Params::new());
+        let mut run = RunState { expiry: None, events: None, series: None, costs: Costs::default() };
+        replay(&mut policy, &data, 0..data.len(), &changed, size_change, &mut run);
+        (policy.stats().1, policy.used())
+    }
+
+    #[test]
+    fn cached_copies_take_the_size_of_the_request() {
+        assert_eq!(replay_size_change(100, 15, SizeChange::Last), (1, 35));
+        assert_eq!(replay_size_change(100, 15, SizeChange::Resize), (1, 35));
+        assert_eq!(replay_size_change(100, 15, SizeChange::Invalidate), (0, 35));
+    }
+
+    #[test]
+    fn last_resizes_after_the_hit() {
+        // Growing 1 first evicts it, growing it after the hit evicts 2 instead
+        assert_eq!(replay_size_change(30, 25, SizeChange::Resize), (0, 25));
+        assert_eq!(replay_size_change(30, 25, SizeChange::Last), (1, 25));
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::Write;
+use std::path::Path;
+use plotters::prelude::*;
+use simulator_shared_types::{FileRecord, Operation};
+
+/// One point of a miss ratio curve.
+#[derive(Clone, Copy, Debug)]
+pub struct MrcPoint {
+    pub size: i64,
+    pub miss_ratio: f64,
+    pub byte_miss_ratio: f64,
+}
+
+/// LRU stack distances over a stream of accesses (Mattson et al.), weighted per object.
+///
+/// A request hits in an LRU cache of `c` bytes exactly when its own size plus the bytes of every
+/// distinct object touched since its previous access fit in `c`, so one pass over the trace gives
+/// the hit rate at every cache size. The weight above each object is summed with a Fenwick tree
+/// indexed by last access time, which is compacted once it grows past twice the number of live
+/// objects so memory follows the footprint of the trace rather than its length.
+pub struct StackDistance<T> {
+    last_access: HashMap<T, (usize, i64)>, // tree position and weight of each object's last access
+    tree: Vec<i64>, // 1-indexed, slot 0 unused
+}
+
+impl <T> StackDistance<T> where T : Hash + Eq + Clone {
+    pub fn new() -> Self {
+        StackDistance {
+            last_access: HashMap::new(),
+            tree: vec![0],
+        }
+    }
+
+    /// Weight of the distinct objects accessed since the previous access to `label`, itself included.
+    /// Returns `None` on the first access.
+    pub fn access(&mut self, label: &T, weight: i64) -> Option<i64> {
+        let distance = match self.last_access.get(label) {
+            Some(&(pos, old_weight)) => {
+                let above = self.prefix(self.tree.len() - 1) - self.prefix(pos);
+                self.add(pos, -old_weight);
+                Some(above + weight)
+            }
+            None => None
+        };
+
+        let pos = self.push(weight);
+        self.last_access.insert(label.clone(), (pos, weight));
+
+        if self.tree.len() > 2 * self.last_access.len() + 1024 {
+            self.compact();
+        }
+        distance
+    }
+
+    /// Drops `label` from the stack. Returns whether it was on it.
+    pub fn remove(&mut self, label: &T) -> bool {
+        match self.last_access.remove(label) {
+            Some((pos, weight)) => {
+                self.add(pos, -weight);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Changes the weight of `label` without moving it. Returns whether it was on the stack.
+    pub fn reweight(&mut self, label: &T, weight: i64) -> bool {
+        match self.last_access.get_mut(label) {
+            Some((pos, old_weight)) => {
+                let (pos, delta) = (*pos, weight - *old_weight);
+                *old_weight = weight;
+                self.add(pos, delta);
+                true
+            }
+            None => false
+        }
+    }
+
+    fn prefix(&self, mut i: usize) -> i64 {
+        let mut sum = 0;
+        while i > 0 {
+            sum += self.tree[i];
+            i &= i - 1;
+        }
+        sum
+    }
+
+    fn add(&mut self, mut i: usize, delta: i64) {
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // Appends a slot, filling in the partial sum it is responsible for.
+    fn push(&mut self, value: i64) -> usize {
+        let n = self.tree.len();
+        let covered = self.prefix(n - 1) - self.prefix(n - (n & n.wrapping_neg()));
+        self.tree.push(value + covered);
+        n
+    }
+
+    // Renumbers the live objects in access order, dropping the slots of stale accesses.
+    fn compact(&mut self) {
+        let mut live: Vec<(T, usize, i64)> = self.last_access.drain()
+            .map(|(label, (pos, weight))| (label, pos, weight))
+            .collect();
+        live.sort_by_key(|(_, pos, _)| *pos);
+
+        self.tree = vec![0];
+        for (label, _, weight) in live {
+            let pos = self.push(weight);
+            self.last_access.insert(label, (pos, weight));
+        }
+    }
+}
+
+/// Byte-weighted LRU miss ratio curve built from stack distances in a single pass.
+pub struct StackDistanceAnalyzer<T> {
+    distances: StackDistance<T>,
+    histogram: BTreeMap<u64, (u64, u64)>, // distance bucket -> (requests, bytes)
+    granularity: i64,
+    requests: u64,
+    bytes: u64,
+}
+
+impl <T> StackDistanceAnalyzer<T> where T : Hash + Eq + Clone {
+    /// Distances are rounded up to a multiple of `granularity` bytes.
+    pub fn new(granularity: i64) -> Self {
+        assert!(granularity > 0, "Granularity must be positive");
+        StackDistanceAnalyzer {
+            distances: StackDistance::new(),
+            histogram: BTreeMap::new(),
+            granularity,
+            requests: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Applies a request the way an LRU cache would, only reads are counted.
+    ///
+    /// Deletes and shrinking updates free space the stack hands to deeper objects, which a real
+    /// LRU cache had already evicted, so traces with them get a slightly optimistic curve.
+    pub fn access(&mut self, file: &FileRecord<T>) {
+        match file.op {
+            Operation::Get => {}
+            Operation::Set => {
+                self.distances.access(&file.label, file.size);
+                return;
+            }
+            Operation::Update => {
+                self.distances.reweight(&file.label, file.size);
+                return;
+            }
+            Operation::Delete => {
+                self.distances.remove(&file.label);
+                return;
+            }
+        }
+        self.requests += 1;
+        self.bytes += file.size as u64;
+
+        if let Some(distance) = self.distances.access(&file.label, file.size) {
+            let bucket = ((distance + self.granularity - 1) / self.granularity) as u64;
+            let entry = self.histogram.entry(bucket).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += file.size as u64;
+        }
+    }
+
+    /// Miss ratios after every bucket, starting from an empty cache.
+    pub fn curve(&self) -> Vec<MrcPoint> {
+        let mut points = vec![MrcPoint { size: 0, miss_ratio: 1.0, byte_miss_ratio: 1.0 }];
+        let (mut hits, mut hit_bytes) = (0u64, 0u64);
+        for (bucket, (count, bytes)) in &self.histogram {
+            hits += count;
+            hit_bytes += bytes;
+            points.push(MrcPoint {
+                size: *bucket as i64 * self.granularity,
+                miss_ratio: 1.0 - hits as f64 / self.requests.max(1) as f64,
+                byte_miss_ratio: 1.0 - hit_bytes as f64 / self.bytes.max(1) as f64,
+            });
+        }
+        points
+    }
+}
+
+/// Computes the LRU miss ratio curve of a trace in one pass.
+pub fn lru_curve<T>(data: &[FileRecord<T>], granularity: i64) -> Vec<MrcPoint> where T : Hash + Eq + Clone {
+    let mut analyzer = StackDistanceAnalyzer::new(granularity);
+    for file in data {
+        analyzer.access(file);
+    }
+    analyzer.curve()
+}
+
+pub fn write_csv(curve: &[MrcPoint], path: &Path) {
+    let mut file = File::create(path).expect("Could not create MRC output");
+    writeln!(file, "size,miss_ratio,byte_miss_ratio").unwrap();
+    for point in curve {
+        writeln!(file, "{},{},{}", point.size, point.miss_ratio, point.byte_miss_ratio).unwrap();
+    }
+}
+
+pub fn plot(curve: &[MrcPoint], path: &Path) {
+    let root = BitMapBackend::new(path, (600, 400)).into_drawing_area();
+    root.fill(&WHITE).unwrap();
+
+    let max_size = curve.last().map(|p| p.size).unwrap_or(0).max(1);
+
+    let mut chart = ChartBuilder::on(&root)
+        .x_label_area_size(35)
+        .y_label_area_size(40)
+        .caption("LRU Miss Ratio Curve", ("sans-serif", 30.0))
+        .build_cartesian_2d(0i64..max_size, 0f64..1f64)
+        .unwrap();
+
+    chart.configure_mesh()
+        .x_labels(8)
+        .x_label_formatter(&|x| format!("{} MB", x / (1024 * 1024)))
+        .x_desc("Cache Size")
+        .y_desc("Miss Ratio")
+        .draw().unwrap();
+
+    chart.draw_series(LineSeries::new(curve.iter().map(|p| (p.size, p.miss_ratio)), &RED))
+        .unwrap()
+        .label("Miss Ratio")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RED));
+
+    chart.draw_series(LineSeries::new(curve.iter().map(|p| (p.size, p.byte_miss_ratio)), &BLUE))
+        .unwrap()
+        .label("Byte Miss Ratio")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+
+    chart.configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE)
+        .position(SeriesLabelPosition::UpperRight)
+        .draw().unwrap();
+
+    root.present().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use algorithm::Rng;
+    use super::*;
+
+    // Every key keeps one size, so the LRU contents are always a prefix of the stack
+    fn trace() -> Vec<FileRecord<i32>> {
+        let mut rng = Rng::new(7);
+        (0..2000).map(|_| {
+            let label = rng.below(50) as i32;
+            FileRecord { label, size: 1 + (label % 7) as i64 * 3, ..Default::default() }
+        }).collect()
+    }
+
+    fn lru_hits(data: &[FileRecord<i32>], capacity: i64) -> (u64, u64) {
+        let mut stack = VecDeque::<(i32, i64)>::new();
+        let (mut hits, mut hit_bytes) = (0, 0);
+        for file in data {
+            if let Some(pos) = stack.iter().position(|&(label, _)| label == file.label) {
+                stack.remove(pos);
+                hits += 1;
+                hit_bytes += file.size as u64;
+            }
+            stack.push_front((file.label, file.size));
+            while stack.iter().map(|&(_, size)| size).sum::<i64>() > capacity {
+                stack.pop_back();
+            }
+        }
+        (hits, hit_bytes)
+    }
+
+    #[test]
+    fn curve_matches_lru_simulation() {
+        let data = trace();
+        let bytes: u64 = data.iter().map(|file| file.size as u64).sum();
+        let curve = lru_curve(&data, 1);
+        assert!(curve.len() > 10);
+        for point in curve {
+            let (hits, hit_bytes) = lru_hits(&data, point.size);
+            assert!((point.miss_ratio - (1.0 - hits as f64 / data.len() as f64)).abs() < 1e-9, "size {}", point.size);
+            assert!((point.byte_miss_ratio - (1.0 - hit_bytes as f64 / bytes as f64)).abs() < 1e-9, "size {}", point.size);
+        }
+    }
+}
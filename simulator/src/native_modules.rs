@@ -1,11 +1,14 @@
 use std::hash::Hash;
 use std::marker::PhantomData;
-use algorithm::CacheAlgorithm;
-use simulator_shared_types::FileRecord;
+use admission::{Admission, Admitted};
+use algorithm::{CacheAlgorithm, Params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use simulator_shared_types::{Event, FileRecord};
 use crate::policy::PolicyModule;
 
 pub struct NativePolicyModule<Alg,T> where Alg : CacheAlgorithm<T> {
-    fifo: Option<Alg>,
+    fifo: Option<Admitted<Alg,T>>,
     phantom: PhantomData<T>
 }
 
@@ -18,18 +21,58 @@ impl <Alg,T> NativePolicyModule<Alg,T> where Alg : CacheAlgorithm<T> {
     }
 }
 
-impl <Alg,T> PolicyModule<T> for NativePolicyModule<Alg,T> where T : Hash + Eq + Clone, Alg: CacheAlgorithm<T>{
-    fn initialize(&mut self, cache_size: i64) {
-       self.fifo = Some(Alg::new(cache_size))
+impl <Alg,T> PolicyModule<T> for NativePolicyModule<Alg,T> where T : Hash + Eq + Clone, Alg: CacheAlgorithm<T> + Serialize + DeserializeOwned {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+       self.fifo = Some(Admitted::with_params(cache_size, params))
     }
 
-    fn send_request(&mut self, pair: FileRecord<T>) {
-        self.fifo.as_mut().unwrap().simulate(pair);
+    fn set_admission(&mut self, admission: Admission) {
+        self.fifo.as_mut().unwrap().set_admission(admission)
+    }
+
+    fn send_request(&mut self, pair: FileRecord<T>) -> bool {
+        self.fifo.as_mut().unwrap().request(pair)
     }
 
     fn stats(&self) -> (i32, i32) {
         self.fifo.as_ref().unwrap().stats()
     }
 
+    fn resize(&mut self, file: FileRecord<T>) -> bool {
+        self.fifo.as_mut().unwrap().resize(&file)
+    }
+
+    fn invalidate(&mut self, label: T) -> bool {
+        self.fifo.as_mut().unwrap().invalidate(&label)
+    }
+
+    fn reset_stats(&mut self) {
+        self.fifo.as_mut().unwrap().reset_stats()
+    }
+
+    fn used(&self) -> i64 {
+        self.fifo.as_ref().unwrap().used()
+    }
+
+    fn filled(&mut self) -> bool {
+        self.fifo.as_mut().unwrap().filled()
+    }
+
+    fn enable_events(&mut self) {
+        self.fifo.as_mut().unwrap().events().enable()
+    }
+
+    fn events(&mut self) -> Vec<Event<T>> {
+        self.fifo.as_mut().unwrap().events().drain()
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        self.fifo.as_ref().unwrap().snapshot()
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        self.fifo = Some(Admitted::restore(state))
+    }
+
 
 }
\ No newline at end of file
@@ -1,5 +1,10 @@
 use wasmer::{Store, Module, Instance, imports, Value, Val, Function, Memory};
-use simulator_shared_types::FileRecord;
+use admission::Admission;
+use algorithm::Params;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use simulator_shared_types::{Event, FileRecord, Operation, WireRecord};
+use crate::keys::FixedWidthKey;
 
 
 
@@ -20,11 +25,98 @@ pub struct WasmPairPolicyModule {
 
 pub trait PolicyModule<T> {
 
-    fn initialize(&mut self, cache_size : i64);
+    /// Builds the policy for a cache of `cache_size` bytes, with the parameters it knows from `params`.
+    fn initialize(&mut self, cache_size : i64, params: &Params);
 
-    fn send_request(&mut self, pair : FileRecord<T>);
+    /// Puts an admission filter in front of the policy, must come after `initialize`.
+    fn set_admission(&mut self, admission: Admission);
+
+    fn send_request(&mut self, pair : FileRecord<T>) -> bool;
 
     fn stats(&self) -> (i32, i32);
+
+    /// Zeroes the statistics, such as at the end of a warmup.
+    fn reset_stats(&mut self);
+
+    /// Bytes currently cached.
+    fn used(&self) -> i64;
+
+    /// Whether the cache has filled up, see `CacheAlgorithm::filled`.
+    fn filled(&mut self) -> bool;
+
+    /// Resizes a cached object in place, returns whether it was cached.
+    fn resize(&mut self, file : FileRecord<T>) -> bool;
+
+    /// Drops an object from the cache, returns whether it was cached.
+    fn invalidate(&mut self, label : T) -> bool;
+
+    /// Starts recording the policy's events, must come after `initialize`.
+    fn enable_events(&mut self);
+
+    /// Takes the events recorded since the last call, in the order they happened.
+    fn events(&mut self) -> Vec<Event<T>>;
+
+    /// Serializes the full state of the policy, see `CacheAlgorithm::snapshot`.
+    fn snapshot(&self) -> Vec<u8>;
+
+    /// Replaces the policy with one from `snapshot`, in place of `initialize`.
+    fn restore(&mut self, state: &[u8]);
+}
+
+// Copies bytes into the guest's state buffer, see `policy_exports::alloc_state`
+fn write_guest_state(module: &Instance, state: &[u8]) {
+    let result = module.exports.get_function("alloc_state").unwrap().call(&[Val::I32(state.len() as i32)]).unwrap();
+    let (ptr, len) = packed_i32::split_i64_to_i32(result[0].unwrap_i64());
+    let mem = module.exports.get_memory("memory").expect("Could not get memory");
+    runtime::write_bytes_to_wasm_memory(state, mem, ptr as usize, len as usize);
+}
+
+// Leaves the parameters in the state buffer, for the `init` that follows to build the policy with
+pub(crate) fn write_guest_params(module: &Instance, params: &Params) {
+    write_guest_state(module, &bincode::serialize(params).expect("Could not serialize policy parameters"));
+}
+
+pub(crate) fn set_guest_admission(module: &Instance, admission: Admission) {
+    write_guest_state(module, &bincode::serialize(&admission).expect("Could not serialize admission"));
+    module.exports.get_function("set_admission").unwrap().call(&[]).unwrap();
+}
+
+pub(crate) fn reset_guest_stats(module: &Instance) {
+    module.exports.get_function("reset_stats").unwrap().call(&[]).unwrap();
+}
+
+pub(crate) fn guest_used(module: &Instance) -> i64 {
+    let result = module.exports.get_function("used").unwrap().call(&[]).unwrap();
+    result[0].unwrap_i64()
+}
+
+pub(crate) fn guest_filled(module: &Instance) -> bool {
+    let result = module.exports.get_function("filled").unwrap().call(&[]).unwrap();
+    result[0].unwrap_i32() != 0
+}
+
+pub(crate) fn enable_guest_events(module: &Instance) {
+    module.exports.get_function("enable_events").unwrap().call(&[]).unwrap();
+}
+
+// Guests hand events back serialized with bincode whatever the request encoding, see `policy_exports::write_events`
+pub(crate) fn drain_guest_events<K>(module: &Instance) -> Vec<Event<K>> where K : DeserializeOwned {
+    let result = module.exports.get_function("drain_events").unwrap().call(&[]).unwrap();
+    let (ptr, len) = packed_i32::split_i64_to_i32(result[0].unwrap_i64());
+    let mem = module.exports.get_memory("memory").expect("Could not get memory");
+    runtime::read_bincode_from_wasm_memory(mem, ptr as usize, len as usize)
+}
+
+pub(crate) fn snapshot_guest(module: &Instance) -> Vec<u8> {
+    let result = module.exports.get_function("snapshot").unwrap().call(&[]).unwrap();
+    let (ptr, len) = packed_i32::split_i64_to_i32(result[0].unwrap_i64());
+    let mem = module.exports.get_memory("memory").expect("Could not get memory");
+    runtime::read_bytes_from_wasm_memory(mem, ptr as usize, len as usize)
+}
+
+pub(crate) fn restore_guest(module: &Instance, state: &[u8]) {
+    write_guest_state(module, state);
+    module.exports.get_function("restore").unwrap().call(&[]).unwrap();
 }
 
 impl WasmBincodePolicyModule {
@@ -34,6 +126,17 @@ impl WasmBincodePolicyModule {
         packed_i32::split_i64_to_i32(merged)
     }
 
+    // Copies a value into a fresh guest buffer, returning where it was written
+    fn write<V : serde::Serialize>(&self, value: &V) -> (i32,i32) {
+        let buffer_size = bincode::serialized_size(value).expect("Could not calculate buffer size") as i32;
+
+        let (ptr, len) = self.alloc(buffer_size);
+
+        let mem = self.module.exports.get_memory("memory").expect("Could not get memory");
+        runtime::write_bincode_to_wasm_memory(value, mem, ptr as usize, len as usize);
+        (ptr, len)
+    }
+
     pub fn from_module(module : Module) -> Self {
         let import_objects = imports!{};
         // Create new sandbox
@@ -63,7 +166,7 @@ impl WasmBytemuckPolicyModule {
         return (self.ptr as i32, self.len as i32)
     }
 
-    pub fn from_module(module: Module) -> Self {
+    pub fn from_module<K>(module: Module) -> Self where K : FixedWidthKey {
         let import_objects = imports!{};
         // Create new sandbox
         let module = Instance::new(&module, &import_objects).unwrap();
@@ -72,7 +175,7 @@ impl WasmBytemuckPolicyModule {
         let _init = module.exports.get_function("init").unwrap();
         let _init = module.exports.get_function("stats").unwrap();
 
-        let size = std::mem::size_of::<FileRecord<i32>>();
+        let size = std::mem::size_of::<WireRecord>();
         let results = alloc.call(&[Val::I32(size as i32)]).unwrap();
         let merged = results[0].unwrap_i64();
         let (ptr,len) = packed_i32::split_i64_to_i32(merged);
@@ -103,12 +206,18 @@ impl WasmPairPolicyModule {
     }
 }
 
-impl PolicyModule<i32> for WasmPairPolicyModule {
-    fn initialize(&mut self, cache_size: i64) {
+impl<K> PolicyModule<K> for WasmPairPolicyModule where K : FixedWidthKey {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+        write_guest_params(&self.module, params);
         self.module.exports.get_function("init").unwrap().call(&[Val::I64(cache_size)]).unwrap();
     }
-    fn send_request(&mut self, request : FileRecord<i32>){
-        self.module.exports.get_function("send").unwrap().call(&[Val::I32(request.label),Val::I64(request.size)]).unwrap();
+
+    fn set_admission(&mut self, admission: Admission) {
+        set_guest_admission(&self.module, admission)
+    }
+    fn send_request(&mut self, request: FileRecord<K>) -> bool {
+        let result = self.module.exports.get_function("send").unwrap().call(&[request.label.to_val(),Val::I64(request.size),Val::I32(request.op as i32),Val::I64(request.time as i64),Val::I64(request.ttl as i64),Val::I64(request.cost as i64)]).unwrap();
+        result[0].unwrap_i32() != 0
     }
 
     fn stats(&self) -> (i32, i32) {
@@ -116,13 +225,56 @@ impl PolicyModule<i32> for WasmPairPolicyModule {
         let packed = result[0].i64().unwrap();
         packed_i32::split_i64_to_i32(packed)
     }
+
+    fn resize(&mut self, file: FileRecord<K>) -> bool {
+        let result = self.module.exports.get_function("resize").unwrap().call(&[file.label.to_val(),Val::I64(file.size)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn invalidate(&mut self, label: K) -> bool {
+        let result = self.module.exports.get_function("invalidate").unwrap().call(&[label.to_val()]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn reset_stats(&mut self) {
+        reset_guest_stats(&self.module)
+    }
+
+    fn used(&self) -> i64 {
+        guest_used(&self.module)
+    }
+
+    fn filled(&mut self) -> bool {
+        guest_filled(&self.module)
+    }
+
+    fn enable_events(&mut self) {
+        enable_guest_events(&self.module)
+    }
+
+    fn events(&mut self) -> Vec<Event<K>> {
+        drain_guest_events(&self.module)
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        snapshot_guest(&self.module)
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        restore_guest(&self.module, state)
+    }
 }
 
-impl PolicyModule<i32> for WasmBincodePolicyModule {
-    fn initialize(&mut self, cache_size: i64) {
+impl<K> PolicyModule<K> for WasmBincodePolicyModule where K : Serialize + DeserializeOwned {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+        write_guest_params(&self.module, params);
         self.module.exports.get_function("init").unwrap().call(&[Val::I64(cache_size)]).unwrap();
     }
-    fn send_request(&mut self, request: FileRecord<i32>) {
+
+    fn set_admission(&mut self, admission: Admission) {
+        set_guest_admission(&self.module, admission)
+    }
+    fn send_request(&mut self, request: FileRecord<K>) -> bool {
         let buffer_size = bincode::serialized_size(&request).expect("Could not calculate buffer size") as i32;
 
         let (ptr, len) = self.alloc(buffer_size);
@@ -138,7 +290,8 @@ impl PolicyModule<i32> for WasmBincodePolicyModule {
                 mem_array[ptr as usize + i as usize] = serialized_array[i as usize];
             }
         }
-        self.module.exports.get_function("send").unwrap().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        let result = self.module.exports.get_function("send").unwrap().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        result[0].unwrap_i32() != 0
     }
 
     fn stats(&self) -> (i32, i32) {
@@ -146,23 +299,68 @@ impl PolicyModule<i32> for WasmBincodePolicyModule {
         let packed = result[0].i64().unwrap();
         packed_i32::split_i64_to_i32(packed)
     }
+
+    fn resize(&mut self, file: FileRecord<K>) -> bool {
+        let (ptr, len) = self.write(&file);
+        let result = self.module.exports.get_function("resize").unwrap().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn invalidate(&mut self, label: K) -> bool {
+        let (ptr, len) = self.write(&label);
+        let result = self.module.exports.get_function("invalidate").unwrap().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn reset_stats(&mut self) {
+        reset_guest_stats(&self.module)
+    }
+
+    fn used(&self) -> i64 {
+        guest_used(&self.module)
+    }
+
+    fn filled(&mut self) -> bool {
+        guest_filled(&self.module)
+    }
+
+    fn enable_events(&mut self) {
+        enable_guest_events(&self.module)
+    }
+
+    fn events(&mut self) -> Vec<Event<K>> {
+        drain_guest_events(&self.module)
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        snapshot_guest(&self.module)
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        restore_guest(&self.module, state)
+    }
 }
 
-impl PolicyModule<i32> for WasmBytemuckPolicyModule {
-    fn initialize(&mut self, cache_size: i64) {
+impl<K> PolicyModule<K> for WasmBytemuckPolicyModule where K : FixedWidthKey {
+    fn initialize(&mut self, cache_size: i64, params: &Params) {
+        write_guest_params(&self.module, params);
         self.module.exports.get_function("init").unwrap().call(&[Val::I64(cache_size)]).unwrap();
     }
 
-    fn send_request(&mut self, pair: FileRecord<i32>) {
-        let buffer_size = std::mem::size_of::<FileRecord<i32>>();
+    fn set_admission(&mut self, admission: Admission) {
+        set_guest_admission(&self.module, admission)
+    }
+
+    fn send_request(&mut self, pair: FileRecord<K>) -> bool {
+        let buffer_size = std::mem::size_of::<WireRecord>();
         let (ptr, len) = self.alloc(buffer_size as i32);
 
         let mem = self.module.exports.get_memory("memory").expect("Could not get memory");
 
-        runtime::write_bytemuck_to_wasm_memory(pair, mem, ptr as usize,len as usize);
-
-        self.module.exports.get_function("send").unwrap().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        runtime::write_bytemuck_to_wasm_memory(WireRecord::from(pair), mem, ptr as usize,len as usize);
 
+        let result = self.module.exports.get_function("send").unwrap().call(&[Val::I32(ptr), Val::I32(len)]).unwrap();
+        result[0].unwrap_i32() != 0
     }
 
     fn stats(&self) -> (i32, i32) {
@@ -170,6 +368,49 @@ impl PolicyModule<i32> for WasmBytemuckPolicyModule {
         let packed = result[0].i64().unwrap();
         packed_i32::split_i64_to_i32(packed)
     }
+
+    fn resize(&mut self, file: FileRecord<K>) -> bool {
+        let mem = self.module.exports.get_memory("memory").expect("Could not get memory");
+        runtime::write_bytemuck_to_wasm_memory(WireRecord::from(file), mem, self.ptr, self.len);
+        let result = self.module.exports.get_function("resize").unwrap().call(&[Val::I32(self.ptr as i32), Val::I32(self.len as i32)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    // The guest only reads the label of the record
+    fn invalidate(&mut self, label: K) -> bool {
+        let mem = self.module.exports.get_memory("memory").expect("Could not get memory");
+        runtime::write_bytemuck_to_wasm_memory(WireRecord::from(FileRecord{ label, op: Operation::Delete, ..Default::default() }), mem, self.ptr, self.len);
+        let result = self.module.exports.get_function("invalidate").unwrap().call(&[Val::I32(self.ptr as i32), Val::I32(self.len as i32)]).unwrap();
+        result[0].unwrap_i32() != 0
+    }
+
+    fn reset_stats(&mut self) {
+        reset_guest_stats(&self.module)
+    }
+
+    fn used(&self) -> i64 {
+        guest_used(&self.module)
+    }
+
+    fn filled(&mut self) -> bool {
+        guest_filled(&self.module)
+    }
+
+    fn enable_events(&mut self) {
+        enable_guest_events(&self.module)
+    }
+
+    fn events(&mut self) -> Vec<Event<K>> {
+        drain_guest_events(&self.module)
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        snapshot_guest(&self.module)
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        restore_guest(&self.module, state)
+    }
 }
 
 
@@ -0,0 +1,296 @@
+use std::path::Path;
+use std::str::FromStr;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use wasmer::{Module, Store};
+use algorithm::{CacheAlgorithm, Params};
+use fifo::FiFo;
+use gdsize::GdSize;
+use lfu::LFU;
+use lru::LRU;
+use wtinylfu::WTinyLFU;
+use s3fifo::S3Fifo;
+use sieve::Sieve;
+use lirs::Lirs;
+use clockpro::ClockPro;
+use clock::Clock;
+use slru::Slru;
+use twoq::TwoQ;
+use mq::MQ;
+use lfuda::LFUDA;
+use lruk::LRUK;
+use hyperbolic::Hyperbolic;
+use lhd::Lhd;
+use random::Random;
+use fifo_reinsertion::FifoReinsertion;
+use crate::cached_policy::{WasmCachedBincodePolicyModule, WasmCachedBytemuckPolicyModule, WasmCachedPairPolicyModule};
+use crate::keys::{FixedWidthKey, SimKey};
+use crate::native_modules::NativePolicyModule;
+use crate::policy::{PolicyModule, WasmBincodePolicyModule, WasmBytemuckPolicyModule, WasmPairPolicyModule};
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum Alg{
+    Fifo,
+    Lfu,
+    Lru,
+    GdSize,
+    WTinyLfu,
+    S3Fifo,
+    Sieve,
+    Lirs,
+    ClockPro,
+    Clock,
+    Slru,
+    TwoQ,
+    MQ,
+    Lfuda,
+    LruK,
+    Hyperbolic,
+    Lhd,
+    Random,
+    FifoReinsertion,
+}
+
+impl Alg {
+    /// Every algorithm, in the order they are simulated and plotted.
+    pub const ALL: [Alg; 19] = [Alg::Fifo, Alg::Lru, Alg::Lfu, Alg::GdSize, Alg::WTinyLfu, Alg::S3Fifo, Alg::Sieve, Alg::Lirs, Alg::ClockPro, Alg::Clock, Alg::Slru, Alg::TwoQ, Alg::MQ, Alg::Lfuda, Alg::LruK, Alg::Hyperbolic, Alg::Lhd, Alg::Random, Alg::FifoReinsertion];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alg::Fifo => "FiFo",
+            Alg::GdSize => "GdSize",
+            Alg::Lfu => "LFU",
+            Alg::Lru => "LRU",
+            Alg::WTinyLfu => "W-TinyLFU",
+            Alg::S3Fifo => "S3-FIFO",
+            Alg::Sieve => "SIEVE",
+            Alg::Lirs => "LIRS",
+            Alg::ClockPro => "CLOCK-Pro",
+            Alg::Clock => "CLOCK",
+            Alg::Slru => "SLRU",
+            Alg::TwoQ => "2Q",
+            Alg::MQ => "MQ",
+            Alg::Lfuda => "LFUDA",
+            Alg::LruK => "LRU-K",
+            Alg::Hyperbolic => "Hyperbolic",
+            Alg::Lhd => "LHD",
+            Alg::Random => "Random",
+            Alg::FifoReinsertion => "FIFO-Reinsertion",
+        }
+    }
+
+    /// Names of the parameters the policy takes, see `Params::check`.
+    pub fn params(&self) -> &'static [&'static str] {
+        match self {
+            Alg::Fifo => FiFo::<i32>::PARAMS,
+            Alg::GdSize => GdSize::<i32>::PARAMS,
+            Alg::Lfu => LFU::<i32>::PARAMS,
+            Alg::Lru => LRU::<i32>::PARAMS,
+            Alg::WTinyLfu => WTinyLFU::<i32>::PARAMS,
+            Alg::S3Fifo => S3Fifo::<i32>::PARAMS,
+            Alg::Sieve => Sieve::<i32>::PARAMS,
+            Alg::Lirs => Lirs::<i32>::PARAMS,
+            Alg::ClockPro => ClockPro::<i32>::PARAMS,
+            Alg::Clock => Clock::<i32>::PARAMS,
+            Alg::Slru => Slru::<i32>::PARAMS,
+            Alg::TwoQ => TwoQ::<i32>::PARAMS,
+            Alg::MQ => MQ::<i32>::PARAMS,
+            Alg::Lfuda => LFUDA::<i32>::PARAMS,
+            Alg::LruK => LRUK::<i32>::PARAMS,
+            Alg::Hyperbolic => Hyperbolic::<i32>::PARAMS,
+            Alg::Lhd => Lhd::<i32>::PARAMS,
+            Alg::Random => Random::<i32>::PARAMS,
+            Alg::FifoReinsertion => FifoReinsertion::<i32>::PARAMS,
+        }
+    }
+
+    /// Fails on parameters the policy does not take or cannot work with, see `CacheAlgorithm::check_params`.
+    pub fn check_params(&self, params: &Params) -> Result<(), String> {
+        match self {
+            Alg::Fifo => FiFo::<i32>::check_params(params),
+            Alg::GdSize => GdSize::<i32>::check_params(params),
+            Alg::Lfu => LFU::<i32>::check_params(params),
+            Alg::Lru => LRU::<i32>::check_params(params),
+            Alg::WTinyLfu => WTinyLFU::<i32>::check_params(params),
+            Alg::S3Fifo => S3Fifo::<i32>::check_params(params),
+            Alg::Sieve => Sieve::<i32>::check_params(params),
+            Alg::Lirs => Lirs::<i32>::check_params(params),
+            Alg::ClockPro => ClockPro::<i32>::check_params(params),
+            Alg::Clock => Clock::<i32>::check_params(params),
+            Alg::Slru => Slru::<i32>::check_params(params),
+            Alg::TwoQ => TwoQ::<i32>::check_params(params),
+            Alg::MQ => MQ::<i32>::check_params(params),
+            Alg::Lfuda => LFUDA::<i32>::check_params(params),
+            Alg::LruK => LRUK::<i32>::check_params(params),
+            Alg::Hyperbolic => Hyperbolic::<i32>::check_params(params),
+            Alg::Lhd => Lhd::<i32>::check_params(params),
+            Alg::Random => Random::<i32>::check_params(params),
+            Alg::FifoReinsertion => FifoReinsertion::<i32>::check_params(params),
+        }
+    }
+
+    // Suffix of the guest module crates, as in wasm_pair_fifo
+    fn module(&self) -> &'static str {
+        match self {
+            Alg::Fifo => "fifo",
+            Alg::GdSize => "gdsize",
+            Alg::Lfu => "lfu",
+            Alg::Lru => "lru",
+            Alg::WTinyLfu => "wtinylfu",
+            Alg::S3Fifo => "s3fifo",
+            Alg::Sieve => "sieve",
+            Alg::Lirs => "lirs",
+            Alg::ClockPro => "clockpro",
+            Alg::Clock => "clock",
+            Alg::Slru => "slru",
+            Alg::TwoQ => "twoq",
+            Alg::MQ => "mq",
+            Alg::Lfuda => "lfuda",
+            Alg::LruK => "lruk",
+            Alg::Hyperbolic => "hyperbolic",
+            Alg::Lhd => "lhd",
+            Alg::Random => "random",
+            Alg::FifoReinsertion => "fifo_reinsertion",
+        }
+    }
+}
+
+impl FromStr for Alg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Alg::ALL.iter().find(|alg| alg.module() == s).copied().ok_or(format!("Unknown algorithm: {}", s))
+    }
+}
+
+/// How requests reach a policy.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum Encoding {
+    Native,
+    Pair,
+    CachedPair,
+    Bincode,
+    CachedBincode,
+    Bytemuck,
+    CachedBytemuck,
+}
+
+impl Encoding {
+    /// Every encoding, in the order they are simulated and plotted.
+    pub const ALL: [Encoding; 7] = [
+        Encoding::Native,
+        Encoding::Pair,
+        Encoding::CachedPair,
+        Encoding::Bincode,
+        Encoding::CachedBincode,
+        Encoding::Bytemuck,
+        Encoding::CachedBytemuck,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Native => "Native",
+            Encoding::Pair => "WASM Pair",
+            Encoding::CachedPair => "Cached WASM Pair",
+            Encoding::Bincode => "WASM Bincode",
+            Encoding::CachedBincode => "Cached WASM Bincode",
+            Encoding::Bytemuck => "WASM Bytemuck",
+            Encoding::CachedBytemuck => "Cached WASM Bytemuck",
+        }
+    }
+
+    // Prefix of the guest module crates for this encoding
+    fn module_prefix(&self) -> &'static str {
+        match self {
+            Encoding::Native => panic!("Native policies have no module"),
+            Encoding::Pair | Encoding::CachedPair => "wasm_pair",
+            Encoding::Bincode | Encoding::CachedBincode => "wasm_bincode",
+            Encoding::Bytemuck | Encoding::CachedBytemuck => "wasm_c",
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Encoding::Native),
+            "pair" => Ok(Encoding::Pair),
+            "cached-pair" => Ok(Encoding::CachedPair),
+            "bincode" => Ok(Encoding::Bincode),
+            "cached-bincode" => Ok(Encoding::CachedBincode),
+            "bytemuck" => Ok(Encoding::Bytemuck),
+            "cached-bytemuck" => Ok(Encoding::CachedBytemuck),
+            _ => Err(format!("Unknown encoding: {}", s))
+        }
+    }
+}
+
+/// Every way of running `alg` with keys of type `K`, in `Encoding::ALL` order.
+///
+/// Encodings that cannot carry `K` are left out, byte string keys only reach the pair and
+/// bytemuck modules with `hash_keys`.
+pub fn policies<K>(store: &Store, alg: Alg, hash_keys: bool) -> Vec<(Encoding, Box<dyn PolicyModule<K>>)> where K : SimKey {
+    Encoding::ALL.iter().filter_map(|encoding| {
+        policy::<K>(store, alg, *encoding, hash_keys).map(|policy| (*encoding, policy))
+    }).collect()
+}
+
+/// `alg` over `encoding`, `None` when the encoding cannot carry keys of type `K`.
+pub fn policy<K>(store: &Store, alg: Alg, encoding: Encoding, hash_keys: bool) -> Option<Box<dyn PolicyModule<K>>> where K : SimKey {
+    match encoding {
+        Encoding::Native => Some(native::<K>(alg)),
+        Encoding::Bincode | Encoding::CachedBincode => Some(bincode_module::<K>(store, K::MODULE_DIR, alg, encoding)),
+        _ => K::fixed_width_policy(store, alg, encoding, hash_keys)
+    }
+}
+
+pub fn native<K>(alg: Alg) -> Box<dyn PolicyModule<K>> where K : SimKey {
+    match alg {
+        Alg::Fifo => Box::new(NativePolicyModule::<FiFo<K>,K>::new()),
+        Alg::Lru => Box::new(NativePolicyModule::<LRU<K>,K>::new()),
+        Alg::Lfu => Box::new(NativePolicyModule::<LFU<K>,K>::new()),
+        Alg::GdSize => Box::new(NativePolicyModule::<GdSize<K>,K>::new()),
+        Alg::WTinyLfu => Box::new(NativePolicyModule::<WTinyLFU<K>,K>::new()),
+        Alg::S3Fifo => Box::new(NativePolicyModule::<S3Fifo<K>,K>::new()),
+        Alg::Sieve => Box::new(NativePolicyModule::<Sieve<K>,K>::new()),
+        Alg::Lirs => Box::new(NativePolicyModule::<Lirs<K>,K>::new()),
+        Alg::ClockPro => Box::new(NativePolicyModule::<ClockPro<K>,K>::new()),
+        Alg::Clock => Box::new(NativePolicyModule::<Clock<K>,K>::new()),
+        Alg::Slru => Box::new(NativePolicyModule::<Slru<K>,K>::new()),
+        Alg::TwoQ => Box::new(NativePolicyModule::<TwoQ<K>,K>::new()),
+        Alg::MQ => Box::new(NativePolicyModule::<MQ<K>,K>::new()),
+        Alg::Lfuda => Box::new(NativePolicyModule::<LFUDA<K>,K>::new()),
+        Alg::LruK => Box::new(NativePolicyModule::<LRUK<K>,K>::new()),
+        Alg::Hyperbolic => Box::new(NativePolicyModule::<Hyperbolic<K>,K>::new()),
+        Alg::Lhd => Box::new(NativePolicyModule::<Lhd<K>,K>::new()),
+        Alg::Random => Box::new(NativePolicyModule::<Random<K>,K>::new()),
+        Alg::FifoReinsertion => Box::new(NativePolicyModule::<FifoReinsertion<K>,K>::new()),
+    }
+}
+
+pub fn bincode_module<K>(store: &Store, dir: &str, alg: Alg, encoding: Encoding) -> Box<dyn PolicyModule<K>> where K : Serialize + DeserializeOwned {
+    let module = load(store, dir, alg, encoding);
+    match encoding {
+        Encoding::Bincode => Box::new(WasmBincodePolicyModule::from_module(module)),
+        Encoding::CachedBincode => Box::new(WasmCachedBincodePolicyModule::from_module(module)),
+        _ => panic!("{:?} is not a bincode encoding", encoding)
+    }
+}
+
+pub fn fixed_width_module<K>(store: &Store, dir: &str, alg: Alg, encoding: Encoding) -> Box<dyn PolicyModule<K>> where K : FixedWidthKey {
+    let module = load(store, dir, alg, encoding);
+    match encoding {
+        Encoding::Pair => Box::new(WasmPairPolicyModule::from_module(module)),
+        Encoding::CachedPair => Box::new(WasmCachedPairPolicyModule::from_module(module)),
+        Encoding::Bytemuck => Box::new(WasmBytemuckPolicyModule::from_module::<K>(module)),
+        Encoding::CachedBytemuck => Box::new(WasmCachedBytemuckPolicyModule::from_module::<K>(module)),
+        _ => panic!("{:?} is not a fixed width encoding", encoding)
+    }
+}
+
+fn load(store: &Store, dir: &str, alg: Alg, encoding: Encoding) -> Module {
+    let path = format!("./{}/wasm32-unknown-unknown/release/{}_{}.wasm", dir, encoding.module_prefix(), alg.module());
+    Module::from_file(store, Path::new(&path)).expect("Module Not Found")
+}
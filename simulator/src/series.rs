@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use plotters::prelude::*;
+use simulator_shared_types::{FileRecord, Operation};
+use crate::policy::PolicyModule;
+use crate::registry::Encoding;
+
+/// How requests are grouped into the windows of a series.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Window {
+    Requests(u64),
+    Time(u64), // trace time units
+}
+
+impl Window {
+    // Windows are numbered from the start of the trace, or from trace time 0
+    fn index(&self, request: usize, time: u64) -> u64 {
+        match self {
+            Window::Requests(count) => request as u64 / count,
+            Window::Time(span) => time / span,
+        }
+    }
+
+    fn start(&self, index: u64) -> u64 {
+        match self {
+            Window::Requests(count) => index * count,
+            Window::Time(span) => index * span,
+        }
+    }
+}
+
+/// Statistics of one window, counting every request including those of a warmup.
+#[derive(Clone, Debug, Default)]
+pub struct WindowPoint {
+    pub start: u64, // first request, or trace time, of the window
+    pub requests: u64,
+    pub reads: u64,
+    pub hits: u64,
+    pub bytes_read: u64,
+    pub bytes_hit: u64,
+    pub occupancy: f64, // share of the cache in use at the end of the window
+}
+
+impl WindowPoint {
+    pub fn hit_ratio(&self) -> f64 {
+        self.hits as f64 / self.reads.max(1) as f64
+    }
+
+    pub fn byte_hit_ratio(&self) -> f64 {
+        self.bytes_hit as f64 / self.bytes_read.max(1) as f64
+    }
+}
+
+/// Hit ratio, byte hit ratio and occupancy of a run over time.
+pub struct Series {
+    window: Window,
+    cache_size: i64,
+    current: Option<(u64, WindowPoint)>, // index and statistics of the open window
+    points: Vec<WindowPoint>,
+}
+
+impl Series {
+    pub fn new(window: Window, cache_size: i64) -> Self {
+        Series {
+            window,
+            cache_size,
+            current: None,
+            points: vec![],
+        }
+    }
+
+    /// Called before every request is sent, closing the open window when the request falls outside it.
+    pub fn begin<K>(&mut self, request: usize, time: u64, policy: &dyn PolicyModule<K>) {
+        let index = self.window.index(request, time);
+        match &self.current {
+            Some((current, _)) if *current == index => {}
+            Some(_) => {
+                self.close(policy);
+                self.current = Some((index, WindowPoint { start: self.window.start(index), ..Default::default() }));
+            }
+            None => self.current = Some((index, WindowPoint { start: self.window.start(index), ..Default::default() })),
+        }
+    }
+
+    /// Called after every request with whether it hit.
+    pub fn record<K>(&mut self, file: &FileRecord<K>, hit: bool) {
+        let (_, point) = self.current.as_mut().expect("Request recorded before it began");
+        point.requests += 1;
+        if file.op == Operation::Get {
+            point.reads += 1;
+            point.bytes_read += file.size as u64;
+            if hit {
+                point.hits += 1;
+                point.bytes_hit += file.size as u64;
+            }
+        }
+    }
+
+    /// Closes the last window, which may be shorter than the others.
+    pub fn finish<K>(mut self, policy: &dyn PolicyModule<K>) -> Vec<WindowPoint> {
+        self.close(policy);
+        self.points
+    }
+
+    fn close<K>(&mut self, policy: &dyn PolicyModule<K>) {
+        if let Some((_, mut point)) = self.current.take() {
+            point.occupancy = policy.used() as f64 / self.cache_size as f64;
+            self.points.push(point);
+        }
+    }
+}
+
+/// Series of one policy at one cache size.
+pub struct SeriesReport {
+    pub name: String,
+    pub encoding: Encoding,
+    pub size: i64,
+    pub points: Vec<WindowPoint>,
+}
+
+pub fn write_csv(reports: &[SeriesReport], path: &Path) {
+    let mut file = File::create(path).expect("Could not create series output");
+    writeln!(file, "name,size,window_start,requests,hit_ratio,byte_hit_ratio,occupancy").unwrap();
+    for report in reports {
+        for point in &report.points {
+            writeln!(file, "{},{},{},{},{},{},{}", report.name, report.size, point.start, point.requests, point.hit_ratio(), point.byte_hit_ratio(), point.occupancy).unwrap();
+        }
+    }
+}
+
+/// Draws one hit ratio chart per cache size next to `path`, with a line per report.
+pub fn plot<'a>(reports: impl IntoIterator<Item = &'a SeriesReport>, path: &Path) {
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+    let colors = [RED, BLUE, GREEN, MAGENTA, CYAN, BLACK, YELLOW];
+
+    let mut by_size: BTreeMap<i64, Vec<&SeriesReport>> = BTreeMap::new();
+    for report in reports {
+        by_size.entry(report.size).or_default().push(report);
+    }
+
+    for (size, reports) in by_size {
+        let file = path.with_file_name(format!("{}_{}MB.png", stem, size / (1024 * 1024)));
+        let root = BitMapBackend::new(&file, (800, 400)).into_drawing_area();
+        root.fill(&WHITE).unwrap();
+
+        let end = reports.iter().filter_map(|r| r.points.last()).map(|p| p.start).max().unwrap_or(0).max(1);
+
+        let mut chart = ChartBuilder::on(&root)
+            .x_label_area_size(35)
+            .y_label_area_size(40)
+            .caption(format!("Hit Ratio Over Time, {} MB", size / (1024 * 1024)), ("sans-serif", 30.0))
+            .build_cartesian_2d(0u64..end, 0f64..1f64)
+            .unwrap();
+
+        chart.configure_mesh()
+            .x_desc("Window Start")
+            .y_desc("Hit Ratio")
+            .draw().unwrap();
+
+        for (report, color) in reports.iter().zip(colors.iter().cycle()) {
+            chart.draw_series(LineSeries::new(report.points.iter().map(|p| (p.start, p.hit_ratio())), color))
+                .unwrap()
+                .label(report.name.as_str())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+
+        chart.configure_series_labels()
+            .border_style(BLACK)
+            .background_style(WHITE)
+            .position(SeriesLabelPosition::LowerRight)
+            .draw().unwrap();
+
+        root.present().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use algorithm::Params;
+    use lru::LRU;
+    use crate::native_modules::NativePolicyModule;
+    use super::*;
+
+    // Sends (label, time) requests of 10 bytes to a 100 byte LRU cache, returning the windows
+    fn windows(window: Window, requests: &[(i32, u64)]) -> Vec<WindowPoint> {
+        let mut policy = NativePolicyModule::<LRU<i32>, i32>::new();
+        policy.initialize(100, &Params::new());
+        let mut series = Series::new(window, 100);
+        for (i, &(label, time)) in requests.iter().enumerate() {
+            let file = FileRecord { label, size: 10, time, ..Default::default() };
+            series.begin(i, time, &policy);
+            let hit = policy.send_request(file);
+            series.record(&file, hit);
+        }
+        series.finish(&policy)
+    }
+
+    #[test]
+    fn request_windows_close_every_count_requests() {
+        let points = windows(Window::Requests(3), &[(1, 0), (2, 0), (1, 0), (1, 0), (3, 0), (4, 0), (2, 0)]);
+        let summary: Vec<_> = points.iter().map(|p| (p.start, p.requests, p.hits)).collect();
+        // The last window holds what is left of the trace
+        assert_eq!(summary, vec![(0, 3, 1), (3, 3, 1), (6, 1, 1)]);
+        assert_eq!(points[0].occupancy, 0.2);
+        assert_eq!(points[2].occupancy, 0.4);
+    }
+
+    #[test]
+    fn time_windows_skip_spans_without_requests() {
+        let points = windows(Window::Time(10), &[(1, 3), (1, 9), (2, 10), (3, 35), (3, 39)]);
+        let summary: Vec<_> = points.iter().map(|p| (p.start, p.requests, p.hits)).collect();
+        assert_eq!(summary, vec![(0, 2, 1), (10, 1, 0), (30, 2, 1)]);
+        assert_eq!(points[0].hit_ratio(), 0.5);
+        assert_eq!(points[0].byte_hit_ratio(), 0.5);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::Hash;
+use algorithm::hash64;
+use simulator_shared_types::FileRecord;
+
+// Hash space the sampling threshold is expressed in, as in the SHARDS paper
+const MODULUS: u64 = 1 << 24;
+
+/// A trace filtered with spatially hashed sampling (SHARDS, Waldspurger et al.).
+///
+/// A key is kept when its hash falls under a threshold, so every request to a sampled key survives
+/// and reuse patterns are preserved. Simulating the sample against a cache scaled down by the
+/// sampling rate approximates the full trace against the full cache.
+pub struct Sample<T> {
+    pub data: Vec<FileRecord<T>>,
+    pub rate: f64,
+}
+
+impl <T> Sample<T> {
+    /// The whole trace, for runs without sampling.
+    pub fn full(data: Vec<FileRecord<T>>) -> Self {
+        Sample { data, rate: 1.0 }
+    }
+
+    /// Cache size to simulate the sample against for a full-trace cache size.
+    pub fn scale_size(&self, size: i64) -> i64 {
+        (size as f64 * self.rate) as i64
+    }
+
+    /// Full-trace size matching a cache size used on the sample.
+    pub fn unscale_size(&self, size: i64) -> i64 {
+        (size as f64 / self.rate) as i64
+    }
+
+    /// Full-trace estimate of a count measured on the sample.
+    pub fn scale_count(&self, count: i32) -> i32 {
+        (count as f64 / self.rate).round() as i32
+    }
+
+    /// Full-trace estimate of a total, such as bytes, measured on the sample.
+    pub fn scale_total(&self, total: u64) -> u64 {
+        (total as f64 / self.rate).round() as u64
+    }
+}
+
+/// Keeps the keys whose hash falls under `rate` of the hash space.
+pub fn fixed_rate<T>(data: Vec<FileRecord<T>>, rate: f64) -> Sample<T> where T : Hash {
+    assert!(rate > 0.0 && rate <= 1.0, "Sampling rate must be in (0, 1]");
+    let threshold = (rate * MODULUS as f64).ceil() as u64;
+    filter(data, threshold)
+}
+
+/// Keeps at most `max_keys` distinct keys, lowering the rate until the sample fits.
+///
+/// A first pass tracks the `max_keys` smallest key hashes in a bounded heap, and the rate is the
+/// largest of those. Memory stays proportional to `max_keys` however large the trace is.
+pub fn fixed_size<T>(data: Vec<FileRecord<T>>, max_keys: usize) -> Sample<T> where T : Hash {
+    assert!(max_keys > 0, "Sample must keep at least one key");
+    let mut heap = BinaryHeap::<u64>::new();
+    let mut kept = HashSet::<u64>::new();
+
+    for file in &data {
+        let hash = hash_label(&file.label);
+        if kept.contains(&hash) {
+            continue;
+        }
+        if heap.len() < max_keys {
+            heap.push(hash);
+            kept.insert(hash);
+        } else if hash < *heap.peek().unwrap() {
+            let dropped = heap.pop().unwrap();
+            kept.remove(&dropped);
+            heap.push(hash);
+            kept.insert(hash);
+        }
+    }
+
+    let threshold = if heap.len() < max_keys {
+        MODULUS
+    } else {
+        heap.peek().unwrap() + 1
+    };
+    filter(data, threshold)
+}
+
+fn filter<T>(data: Vec<FileRecord<T>>, threshold: u64) -> Sample<T> where T : Hash {
+    let data = data.into_iter()
+        .filter(|file| hash_label(&file.label) < threshold)
+        .collect();
+    Sample {
+        data,
+        rate: threshold as f64 / MODULUS as f64,
+    }
+}
+
+// Position of a key in the hash space. Fixed so samples are the same across runs and platforms.
+fn hash_label<T>(label: &T) -> u64 where T : Hash {
+    hash64(label) % MODULUS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every key is requested twice
+    fn trace(keys: i32) -> Vec<FileRecord<i32>> {
+        (0..2 * keys).map(|i| FileRecord { label: i % keys, size: 1, ..Default::default() }).collect()
+    }
+
+    fn distinct(sample: &Sample<i32>) -> usize {
+        sample.data.iter().map(|file| file.label).collect::<HashSet<_>>().len()
+    }
+
+    #[test]
+    fn fixed_rate_keeps_about_rate_of_the_keys_and_all_their_requests() {
+        let sample = fixed_rate(trace(100_000), 0.1);
+        let kept = distinct(&sample);
+        assert!((9_000..11_000).contains(&kept), "kept {} keys", kept);
+        assert_eq!(sample.data.len(), 2 * kept);
+        assert!((sample.rate - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fixed_size_keeps_at_most_max_keys() {
+        let sample = fixed_size(trace(10_000), 500);
+        let kept = distinct(&sample);
+        assert!(kept <= 500 && kept > 450, "kept {} keys", kept);
+        assert_eq!(sample.data.len(), 2 * kept);
+        assert!((sample.rate - 0.05).abs() < 0.01, "rate {}", sample.rate);
+    }
+
+    #[test]
+    fn fixed_size_keeps_small_traces_whole() {
+        let sample = fixed_size(trace(100), 500);
+        assert_eq!(sample.data.len(), 200);
+        assert_eq!(sample.rate, 1.0);
+    }
+}
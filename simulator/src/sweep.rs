@@ -0,0 +1,323 @@
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use admission::Admission;
+use algorithm::{Param, Params};
+use serde::{Serialize, Serializer};
+use wasmer::Store;
+use simulator_shared_types::{FileRecord, Operation};
+use crate::policy::PolicyModule;
+use crate::registry::Alg;
+
+/// Values one policy parameter takes in a sweep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Axis {
+    pub alg: Option<Alg>, // the only policy swept along the axis, otherwise every policy taking the parameter
+    pub name: String,
+    pub values: Vec<Param>,
+}
+
+impl Axis {
+    pub fn applies(&self, alg: Alg) -> bool {
+        self.alg.is_none_or(|only| only == alg) && alg.params().contains(&self.name.as_str())
+    }
+}
+
+impl FromStr for Axis {
+    type Err = String;
+
+    // [ALG:]NAME=START:END:STEP for a range including END, or [ALG:]NAME=V1,V2,... for a list
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid parameter axis: {}", s);
+        let (name, values) = match s.find('=') {
+            Some(i) => (s[..i].trim(), s[i + 1..].trim()),
+            None => return Err(invalid())
+        };
+        let (alg, name) = match name.find(':') {
+            Some(i) => (Some(Alg::from_str(name[..i].trim())?), name[i + 1..].trim()),
+            None => (None, name)
+        };
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        let values = if values.contains(':') {
+            let bounds: Vec<&str> = values.split(':').collect();
+            if bounds.len() != 3 {
+                return Err(invalid());
+            }
+            match (i64::from_str(bounds[0]), i64::from_str(bounds[1]), i64::from_str(bounds[2])) {
+                (Ok(start), Ok(end), Ok(step)) if step > 0 && start <= end => {
+                    (start..=end).step_by(step as usize).map(Param::Int).collect()
+                }
+                (Ok(_), Ok(_), Ok(_)) => return Err(invalid()),
+                _ => {
+                    let bounds: Vec<f64> = bounds.iter().map(|bound| f64::from_str(bound)).collect::<Result<_, _>>().map_err(|_| invalid())?;
+                    let (start, end, step) = (bounds[0], bounds[1], bounds[2]);
+                    if step <= 0.0 || start > end {
+                        return Err(invalid());
+                    }
+                    // Stepping by multiples keeps rounding errors from adding up, and END from being missed
+                    let steps = ((end - start) / step + 1e-9).floor() as usize;
+                    (0..=steps).map(|i| Param::Float(((start + i as f64 * step) * 1e9).round() / 1e9)).collect()
+                }
+            }
+        } else {
+            values.split(',').map(|value| Param::from_str(value.trim()).map_err(|_| invalid())).collect::<Result<_, _>>()?
+        };
+        Ok(Axis { alg, name: name.to_string(), values })
+    }
+}
+
+/// Fails on parameters and axes that none of the swept policies take, and on values one of them cannot work with.
+pub fn check(algs: &[Alg], base: &Params, axes: &[Axis]) -> Result<(), String> {
+    let declared: Vec<&str> = algs.iter().flat_map(|alg| alg.params().iter().copied()).collect();
+    base.check(&declared)?;
+    for axis in axes {
+        if !algs.iter().any(|alg| axis.applies(*alg)) {
+            return Err(format!("No swept policy takes the parameter {}", axis.name));
+        }
+    }
+    for alg in algs {
+        for params in grid(*alg, base, axes) {
+            alg.check_params(&params)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every combination of the values of the axes that apply to `alg`, each on top of the parameters
+/// in `base` that `alg` takes.
+pub fn grid(alg: Alg, base: &Params, axes: &[Axis]) -> Vec<Params> {
+    axes.iter().filter(|axis| axis.applies(alg)).fold(vec![base.only(alg.params())], |points, axis| {
+        points.iter().flat_map(|point| axis.values.iter().map(move |value| {
+            let mut point = point.clone();
+            point.set(&axis.name, *value);
+            point
+        })).collect()
+    })
+}
+
+/// A policy, cache size and parameters to simulate.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub alg: Alg,
+    pub size: i64,
+    pub params: Params,
+}
+
+fn serialize_alg<S : Serializer>(alg: &Alg, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(alg.name())
+}
+
+fn serialize_params<S : Serializer>(params: &Params, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&params.to_string())
+}
+
+/// How one configuration did over the trace.
+#[derive(Clone, Debug, Serialize)]
+pub struct SweepRun {
+    #[serde(serialize_with = "serialize_alg")]
+    pub alg: Alg,
+    pub size: i64,
+    #[serde(serialize_with = "serialize_params")]
+    pub params: Params,
+    pub reads: u64,
+    pub hits: u64,
+    pub hit_ratio: f64,
+    pub byte_hit_ratio: f64,
+    pub time: f64, // seconds
+}
+
+/// Best hit ratio of a policy at a cache size with one parameter held at `value`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ValueRatio {
+    pub value: f64,
+    pub hit_ratio: f64,
+}
+
+/// How much one parameter moves the hit ratio of a policy at a cache size.
+///
+/// Each value is credited with the best run it took part in, so the other parameters are at their
+/// best for it. A spread near 0 means the parameter can be left alone, or that the policy ignores it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sensitivity {
+    pub alg: String,
+    pub size: i64,
+    pub param: String,
+    pub values: Vec<ValueRatio>,
+    pub spread: f64, // between the best and the worst value
+}
+
+/// Every run of a sweep, the best configuration per policy and cache size, and the sensitivity to
+/// each parameter. Written out as JSON by the `sweep` subcommand.
+#[derive(Debug, Serialize)]
+pub struct SweepReport {
+    pub runs: Vec<SweepRun>,
+    pub best: Vec<SweepRun>,
+    pub sensitivity: Vec<Sensitivity>,
+}
+
+/// Simulates every configuration over the trace on `jobs` threads. Each thread builds its own
+/// policies from `new_policy`, with a store of its own for the guest modules.
+pub fn run<K, F>(data: &[FileRecord<K>], configs: &[Config], admission: Admission, jobs: usize, new_policy: F) -> Vec<SweepRun>
+    where K : Clone + Sync, F : Fn(&Store, Alg) -> Box<dyn PolicyModule<K>> + Sync {
+    let next = AtomicUsize::new(0);
+    let runs = Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| {
+                let store = Store::default();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let config = match configs.get(i) {
+                        Some(config) => config,
+                        None => break
+                    };
+                    let run = simulate(new_policy(&store, config.alg), data, config, admission);
+                    println!("Swept {} at {} MB with {} | Hitrate: {:.4}", run.alg.name(), run.size / (1024 * 1024), run.params, run.hit_ratio);
+                    runs.lock().unwrap().push((i, run));
+                }
+            });
+        }
+    });
+    // Back in the order of the configurations, whichever thread finished first
+    let mut runs = runs.into_inner().unwrap();
+    runs.sort_by_key(|(i, _)| *i);
+    runs.into_iter().map(|(_, run)| run).collect()
+}
+
+fn simulate<K>(mut policy: Box<dyn PolicyModule<K>>, data: &[FileRecord<K>], config: &Config, admission: Admission) -> SweepRun where K : Clone {
+    let start = std::time::Instant::now();
+    policy.initialize(config.size, &config.params);
+    policy.set_admission(admission);
+    let (mut reads, mut hits, mut bytes_read, mut bytes_hit) = (0, 0, 0, 0);
+    for file in data {
+        let is_read = file.op == Operation::Get;
+        let hit = policy.send_request(file.clone()) && is_read;
+        if is_read {
+            reads += 1;
+            bytes_read += file.size as u64;
+            if hit {
+                hits += 1;
+                bytes_hit += file.size as u64;
+            }
+        }
+    }
+    SweepRun {
+        alg: config.alg,
+        size: config.size,
+        params: config.params.clone(),
+        reads,
+        hits,
+        hit_ratio: hits as f64 / reads.max(1) as f64,
+        byte_hit_ratio: bytes_hit as f64 / bytes_read.max(1) as f64,
+        time: start.elapsed().as_secs_f64(),
+    }
+}
+
+fn as_f64(param: Param) -> f64 {
+    match param {
+        Param::Int(value) => value as f64,
+        Param::Float(value) => value,
+    }
+}
+
+/// Picks the best run of every policy and cache size, and measures the sensitivity to each axis.
+pub fn report(runs: Vec<SweepRun>, axes: &[Axis]) -> SweepReport {
+    let mut groups: Vec<(Alg, i64)> = vec![];
+    for run in &runs {
+        if !groups.contains(&(run.alg, run.size)) {
+            groups.push((run.alg, run.size));
+        }
+    }
+
+    let mut best = vec![];
+    let mut sensitivity = vec![];
+    for (alg, size) in groups {
+        let group: Vec<&SweepRun> = runs.iter().filter(|run| run.alg == alg && run.size == size).collect();
+        // Ties go to the first configuration of the grid
+        let top = group.iter().fold(group[0], |top, run| if run.hit_ratio > top.hit_ratio { run } else { top });
+        best.push(top.clone());
+
+        for axis in axes.iter().filter(|axis| axis.applies(alg)) {
+            let values: Vec<ValueRatio> = axis.values.iter().map(|value| ValueRatio {
+                value: as_f64(*value),
+                hit_ratio: group.iter()
+                    .filter(|run| run.params.get(&axis.name) == Some(*value))
+                    .map(|run| run.hit_ratio)
+                    .fold(0.0, f64::max),
+            }).collect();
+            let highest = values.iter().map(|value| value.hit_ratio).fold(0.0, f64::max);
+            let lowest = values.iter().map(|value| value.hit_ratio).fold(1.0, f64::min);
+            sensitivity.push(Sensitivity {
+                alg: alg.name().to_string(),
+                size,
+                param: axis.name.clone(),
+                values,
+                spread: highest - lowest,
+            });
+        }
+    }
+
+    SweepReport { runs, best, sensitivity }
+}
+
+pub fn write_json(report: &SweepReport, path: &Path) {
+    let file = File::create(path).expect("Could not create sweep output");
+    serde_json::to_writer_pretty(file, report).expect("Failed to serialize sweep report");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axes(axes: &[&str]) -> Vec<Axis> {
+        axes.iter().map(|axis| Axis::from_str(axis).unwrap()).collect()
+    }
+
+    fn points(alg: Alg, base: &str, axes: &[Axis]) -> Vec<String> {
+        grid(alg, &Params::from_str(base).unwrap(), axes).iter().map(|params| params.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_ranges_and_lists() {
+        let axis = Axis::from_str("window_ratio=0.1:0.3:0.1").unwrap();
+        assert_eq!((axis.alg, axis.values), (None, vec![Param::Float(0.1), Param::Float(0.2), Param::Float(0.3)]));
+        let axis = Axis::from_str("twoq:in_ratio=0.2, 0.3").unwrap();
+        assert_eq!((axis.alg, axis.name.as_str()), (Some(Alg::TwoQ), "in_ratio"));
+        assert_eq!(axis.values, vec![Param::Float(0.2), Param::Float(0.3)]);
+        assert_eq!(Axis::from_str("queues=1:6:2").unwrap().values, vec![Param::Int(1), Param::Int(3), Param::Int(5)]);
+        assert!(Axis::from_str("queues").is_err());
+        assert!(Axis::from_str("queues=1:2").is_err());
+        assert!(Axis::from_str("queues=3:1:1").is_err());
+    }
+
+    #[test]
+    fn grids_cross_the_axes_each_policy_takes() {
+        let axes = axes(&["window_ratio=0.1,0.2", "protected_ratio=0.6,0.7"]);
+        assert_eq!(points(Alg::WTinyLfu, "", &axes), vec![
+            "protected_ratio=0.6,window_ratio=0.1", "protected_ratio=0.7,window_ratio=0.1",
+            "protected_ratio=0.6,window_ratio=0.2", "protected_ratio=0.7,window_ratio=0.2",
+        ]);
+        // Axes override the base, and base parameters the policy does not take are left out
+        assert_eq!(points(Alg::Slru, "protected_ratio=0.5,seed=3", &axes), vec!["protected_ratio=0.6", "protected_ratio=0.7"]);
+        assert_eq!(points(Alg::Lru, "seed=3", &axes), vec![""]);
+    }
+
+    #[test]
+    fn axes_for_one_policy_leave_the_others_alone() {
+        let axes = axes(&["slru:protected_ratio=0.6,0.7"]);
+        assert_eq!(points(Alg::Slru, "", &axes).len(), 2);
+        assert_eq!(points(Alg::WTinyLfu, "protected_ratio=0.5", &axes), vec!["protected_ratio=0.5"]);
+    }
+
+    #[test]
+    fn rejects_axes_no_policy_takes_and_values_out_of_range() {
+        assert!(check(&[Alg::Lru], &Params::new(), &axes(&["protected_ratio=0.5"])).is_err());
+        assert!(check(&[Alg::Lru], &Params::from_str("protected_ratio=0.5").unwrap(), &[]).is_err());
+        assert!(check(&[Alg::Lru, Alg::Slru], &Params::new(), &axes(&["protected_ratio=0.5"])).is_ok());
+        assert!(check(&[Alg::S3Fifo], &Params::new(), &axes(&["small_ratio=0.5,1.5"])).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::Read;
+use std::str::FromStr;
+use flate2::read::GzDecoder;
+use simulator_shared_types::{FileRecord, Operation};
+use crate::keys::TraceKey;
+
+/// What each whitespace separated column of a trace line holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Label,
+    Size,
+    Op, // GET, SET, DELETE or UPDATE, see `Operation`
+    Time, // integer timestamp, requests must be in time order
+    Ttl, // lifetime in the same unit as the timestamps, 0 never expires
+    Cost, // integer cost of a miss, such as the fetch latency in microseconds
+    Ignored, // written as `_`
+}
+
+/// Column layout of a trace, parsed from a comma separated list such as `label,size,op`.
+#[derive(Clone, Debug)]
+pub struct TraceFormat {
+    pub columns: Vec<Column>,
+}
+
+impl Default for TraceFormat {
+    fn default() -> Self {
+        TraceFormat { columns: vec![Column::Label, Column::Size] }
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let columns = s.split(',').map(|column| match column.trim() {
+            "label" => Ok(Column::Label),
+            "size" => Ok(Column::Size),
+            "op" => Ok(Column::Op),
+            "time" => Ok(Column::Time),
+            "ttl" => Ok(Column::Ttl),
+            "cost" => Ok(Column::Cost),
+            "_" => Ok(Column::Ignored),
+            other => Err(format!("Unknown trace column: {}", other))
+        }).collect::<Result<Vec<Column>, String>>()?;
+
+        for required in &[Column::Label, Column::Size] {
+            if !columns.contains(required) {
+                return Err(format!("Trace format is missing the {:?} column", required));
+            }
+        }
+        Ok(TraceFormat { columns })
+    }
+}
+
+/// Reads a gzipped trace, one request per line laid out as `format`. Requests without an
+/// operation column are reads, and without a time column the position of the request is its time.
+pub fn load_trace<K>(file_path: &str, format: &TraceFormat) -> Vec<FileRecord<K>> where K : TraceKey {
+    let file = File::open(file_path).unwrap();
+    let mut decompressed = GzDecoder::new(file);
+
+    let mut string = String::new();
+    decompressed.read_to_string(&mut string).unwrap();
+
+    string.lines().enumerate().map(
+        |(i, line)| {
+            let fields = line.trim().split_ascii_whitespace().collect::<Vec<&str>>();
+            if fields.len() < format.columns.len() {
+                panic!("Trace line has fewer columns than the format: {}", line)
+            }
+            let (mut label, mut size, mut op, mut time, mut ttl, mut cost) = (None, 0, Operation::Get, i as u64, 0, 0);
+            for (column, field) in format.columns.iter().zip(fields) {
+                match column {
+                    Column::Label => label = Some(K::parse(field)),
+                    Column::Size => size = i64::from_str(field).unwrap(),
+                    Column::Op => op = Operation::from_str(field).unwrap(),
+                    Column::Time => time = u64::from_str(field).unwrap(),
+                    Column::Ttl => ttl = u64::from_str(field).unwrap(),
+                    Column::Cost => cost = u64::from_str(field).unwrap(),
+                    Column::Ignored => {}
+                }
+            }
+            FileRecord::<K>{
+                label : label.unwrap(),
+                size,
+                op,
+                time,
+                ttl,
+                cost
+            }
+        }
+    ).collect()
+}
+
+/// Whether writes place the object in the cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WritePolicy {
+    Allocate, // sets insert the object, updates change it in place
+    Around, // writes skip the cache and drop any stale copy
+}
+
+impl FromStr for WritePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allocate" => Ok(WritePolicy::Allocate),
+            "around" => Ok(WritePolicy::Around),
+            _ => Err(format!("Unknown write policy: {}", s))
+        }
+    }
+}
+
+/// Turns writes into deletes for write-around, leaves the trace as is for write-allocate.
+pub fn apply_write_policy<T>(data: Vec<FileRecord<T>>, policy: WritePolicy) -> Vec<FileRecord<T>> {
+    match policy {
+        WritePolicy::Allocate => data,
+        WritePolicy::Around => data.into_iter().map(|i| match i.op {
+            Operation::Set | Operation::Update => FileRecord { op: Operation::Delete, ..i },
+            Operation::Get | Operation::Delete => i
+        }).collect()
+    }
+}
+
+/// Gives every record without a TTL of its own a lifetime of `ttl`, leaves the trace as is for 0.
+pub fn apply_default_ttl<T>(data: Vec<FileRecord<T>>, ttl: u64) -> Vec<FileRecord<T>> {
+    if ttl == 0 {
+        return data;
+    }
+    data.into_iter().map(|i| match i.ttl {
+        0 => FileRecord { ttl, ..i },
+        _ => i
+    }).collect()
+}
+
+/// How requests are simulated when an object shows up with a different size than before.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeChange {
+    First, // every request uses the first size seen for its label
+    Last, // every request uses its own size, a hit is served from the cached copy, which then takes the new size
+    Invalidate, // a new size is an update that drops the cached copy, so the request misses
+    Resize, // a new size is an update that resizes the cached copy in place
+}
+
+impl FromStr for SizeChange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(SizeChange::First),
+            "last" => Ok(SizeChange::Last),
+            "invalidate" => Ok(SizeChange::Invalidate),
+            "resize" => Ok(SizeChange::Resize),
+            _ => Err(format!("Unknown size change mode: {}", s))
+        }
+    }
+}
+
+/// Rewrites record sizes for the modes that fix one size per label, leaves them as is otherwise.
+pub fn apply_size_change<T>(data: Vec<FileRecord<T>>, mode: SizeChange) -> Vec<FileRecord<T>> where T : Hash + Eq + Clone {
+    match mode {
+        SizeChange::First => freeze_sizes(data),
+        SizeChange::Last | SizeChange::Invalidate | SizeChange::Resize => data
+    }
+}
+
+/// Rewrites every record to the first size seen for its label. Deletes carry no size and are skipped.
+pub fn freeze_sizes<T>(data: Vec<FileRecord<T>>) -> Vec<FileRecord<T>> where T : Hash + Eq + Clone {
+    let mut map = HashMap::<T,i64>::new();
+    for i in data.iter().filter(|i| i.op != Operation::Delete) {
+        map.entry(i.label.clone()).or_insert(i.size);
+    }
+    rewrite_sizes(data, &map)
+}
+
+fn rewrite_sizes<T>(data: Vec<FileRecord<T>>, map: &HashMap<T,i64>) -> Vec<FileRecord<T>> where T : Hash + Eq {
+    data.into_iter().map(|i | {
+        let size = map.get(&i.label).copied().unwrap_or(i.size);
+        FileRecord::<T>{
+            size,
+            ..i
+        }
+    }).collect()
+}
+
+/// Flags the requests whose size differs from the previous request for the same label.
+/// A delete forgets the size, so the next request after it is never flagged.
+pub fn size_changes<T>(data: &[FileRecord<T>]) -> Vec<bool> where T : Hash + Eq + Clone {
+    let mut map = HashMap::<T,i64>::new();
+    data.iter().map(|i| {
+        if i.op == Operation::Delete {
+            map.remove(&i.label);
+            return false;
+        }
+        match map.insert(i.label.clone(), i.size) {
+            Some(previous) => previous != i.size,
+            None => false
+        }
+    }).collect()
+}
@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::hash::Hash;
+use std::path::Path;
+use plotters::prelude::*;
+use serde::Serialize;
+use simulator_shared_types::{FileRecord, Operation};
+use crate::mrc::StackDistance;
+
+/// Characterization of a trace, written out as JSON by the `trace-stats` subcommand.
+#[derive(Serialize, Debug)]
+pub struct TraceStats<T> {
+    pub requests: u64,
+    pub operations: OperationCounts,
+    pub unique_objects: u64,
+    pub requested_bytes: u64,
+    pub footprint_bytes: u64, // sum of the last seen size of every object
+    pub one_hit_wonder_ratio: f64, // share of objects requested exactly once
+    pub zipf_alpha: f64,
+    pub object_sizes: SizeDistribution,
+    pub most_popular: Vec<(T, u64)>,
+    #[serde(skip)]
+    pub rank_frequency: Vec<u64>, // request count of every object, most popular first
+    pub cold_requests: u64,
+    pub reuse_distances: Vec<Bucket>, // distinct objects between two requests to the same object
+    pub working_set: Vec<Window>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct OperationCounts {
+    pub gets: u64,
+    pub sets: u64,
+    pub deletes: u64,
+    pub updates: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SizeDistribution {
+    pub min: i64,
+    pub max: i64,
+    pub mean: f64,
+    pub median: i64,
+    pub p90: i64,
+    pub p99: i64,
+    pub histogram: Vec<Bucket>,
+}
+
+/// Values in `(upper_bound / 2, upper_bound]`, the bucket of 1 also holds 0.
+#[derive(Serialize, Debug)]
+pub struct Bucket {
+    pub upper_bound: u64,
+    pub count: u64,
+}
+
+/// Distinct objects and bytes touched by one window of requests.
+#[derive(Serialize, Debug)]
+pub struct Window {
+    pub start: u64,
+    pub requests: u64,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+/// Analyzes a trace in one pass, splitting it into windows of `window` requests for the working set.
+pub fn analyze<T>(data: &[FileRecord<T>], window: usize, top: usize) -> TraceStats<T> where T : Hash + Ord + Clone {
+    let mut counts = HashMap::<T, u64>::new();
+    let mut sizes = HashMap::<T, i64>::new();
+    let mut distances = StackDistance::<T>::new();
+    let mut reuse = BTreeMap::<u64, u64>::new();
+    let mut cold_requests = 0;
+    let mut requested_bytes = 0;
+    let mut operations = OperationCounts::default();
+
+    for file in data {
+        match file.op {
+            Operation::Get => operations.gets += 1,
+            Operation::Set => operations.sets += 1,
+            Operation::Delete => operations.deletes += 1,
+            Operation::Update => operations.updates += 1,
+        }
+        *counts.entry(file.label.clone()).or_insert(0) += 1;
+        if file.op != Operation::Delete {
+            sizes.insert(file.label.clone(), file.size);
+        }
+        requested_bytes += file.size as u64;
+
+        // The stack distance counts the object itself
+        match distances.access(&file.label, 1) {
+            Some(distance) => *reuse.entry(log2_bucket(distance - 1)).or_insert(0) += 1,
+            None => cold_requests += 1
+        }
+    }
+
+    let working_set = data.chunks(window.max(1)).enumerate().map(|(i, chunk)| {
+        let mut seen = HashSet::<&T>::new();
+        let mut bytes = 0;
+        for file in chunk {
+            if seen.insert(&file.label) {
+                bytes += file.size as u64;
+            }
+        }
+        Window {
+            start: (i * window) as u64,
+            requests: chunk.len() as u64,
+            objects: seen.len() as u64,
+            bytes,
+        }
+    }).collect();
+
+    let mut popularity: Vec<(T, u64)> = counts.into_iter().collect();
+    // Equally popular objects are listed by label, so reports are the same across runs
+    popularity.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+    let one_hit_wonders = popularity.iter().filter(|(_, count)| *count == 1).count();
+
+    TraceStats {
+        requests: data.len() as u64,
+        operations,
+        unique_objects: popularity.len() as u64,
+        requested_bytes,
+        footprint_bytes: sizes.values().map(|size| *size as u64).sum(),
+        one_hit_wonder_ratio: one_hit_wonders as f64 / popularity.len().max(1) as f64,
+        zipf_alpha: zipf_alpha(&popularity),
+        object_sizes: size_distribution(sizes.into_values().collect()),
+        rank_frequency: popularity.iter().map(|(_, count)| *count).collect(),
+        most_popular: popularity.into_iter().take(top).collect(),
+        cold_requests,
+        reuse_distances: reuse.into_iter().map(|(upper_bound, count)| Bucket { upper_bound, count }).collect(),
+        working_set,
+    }
+}
+
+// Least squares fit of log(frequency) against log(rank), frequencies sorted in descending order.
+fn zipf_alpha<T>(popularity: &[(T, u64)]) -> f64 {
+    let n = popularity.len() as f64;
+    if popularity.len() < 2 {
+        return 0.0;
+    }
+    let points: Vec<(f64, f64)> = popularity.iter().enumerate()
+        .map(|(rank, (_, count))| (((rank + 1) as f64).ln(), (*count as f64).ln()))
+        .collect();
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    -covariance / variance
+}
+
+fn size_distribution(mut sizes: Vec<i64>) -> SizeDistribution {
+    sizes.sort_unstable();
+    let percentile = |p: f64| sizes.get(((sizes.len() as f64 * p) as usize).min(sizes.len().saturating_sub(1))).copied().unwrap_or(0);
+
+    let mut histogram = BTreeMap::<u64, u64>::new();
+    for size in &sizes {
+        *histogram.entry(log2_bucket(*size)).or_insert(0) += 1;
+    }
+
+    SizeDistribution {
+        min: sizes.first().copied().unwrap_or(0),
+        max: sizes.last().copied().unwrap_or(0),
+        mean: sizes.iter().sum::<i64>() as f64 / sizes.len().max(1) as f64,
+        median: percentile(0.5),
+        p90: percentile(0.9),
+        p99: percentile(0.99),
+        histogram: histogram.into_iter().map(|(upper_bound, count)| Bucket { upper_bound, count }).collect(),
+    }
+}
+
+fn log2_bucket(value: i64) -> u64 {
+    (value.max(1) as u64).next_power_of_two()
+}
+
+pub fn write_json<T>(stats: &TraceStats<T>, path: &Path) where T : Serialize {
+    let file = File::create(path).expect("Could not create trace stats output");
+    serde_json::to_writer_pretty(file, stats).expect("Failed to serialize trace stats");
+}
+
+/// Writes the popularity, reuse distance and working set plots next to `path`.
+pub fn plot<T>(stats: &TraceStats<T>, path: &Path) {
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+
+    {
+        let file = path.with_file_name(format!("{}_popularity.png", stem));
+        let root = BitMapBackend::new(&file, (600, 400)).into_drawing_area();
+        root.fill(&WHITE).unwrap();
+
+        let counts = &stats.rank_frequency;
+        let max_x = (counts.len().max(2) as f64).log10();
+        let max_y = (counts.first().copied().unwrap_or(1).max(2) as f64).log10();
+        let mut chart = ChartBuilder::on(&root)
+            .x_label_area_size(35)
+            .y_label_area_size(40)
+            .caption(format!("Popularity (Zipf alpha {:.3})", stats.zipf_alpha), ("sans-serif", 30.0))
+            .build_cartesian_2d(0f64..max_x, 0f64..max_y)
+            .unwrap();
+
+        chart.configure_mesh()
+            .x_desc("log10(Rank)")
+            .y_desc("log10(Requests)")
+            .draw().unwrap();
+
+        chart.draw_series(LineSeries::new(
+            counts.iter().enumerate().map(|(rank, count)| (((rank + 1) as f64).log10(), (*count as f64).log10())),
+            &RED
+        )).unwrap();
+
+        root.present().unwrap();
+    }
+
+    {
+        let file = path.with_file_name(format!("{}_reuse.png", stem));
+        let root = BitMapBackend::new(&file, (600, 400)).into_drawing_area();
+        root.fill(&WHITE).unwrap();
+
+        let buckets = stats.reuse_distances.len() as u32;
+        let max_count = stats.reuse_distances.iter().map(|b| b.count).max().unwrap_or(1);
+        let mut chart = ChartBuilder::on(&root)
+            .x_label_area_size(35)
+            .y_label_area_size(60)
+            .caption("Reuse Distances", ("sans-serif", 30.0))
+            .build_cartesian_2d(0u32..buckets.max(1), 0u64..max_count)
+            .unwrap();
+
+        chart.configure_mesh()
+            .x_label_formatter(&|x| {
+                stats.reuse_distances.get(*x as usize).map(|b| b.upper_bound.to_string()).unwrap_or_default()
+            })
+            .x_desc("Distinct Objects Between Requests (up to)")
+            .y_desc("Requests")
+            .draw().unwrap();
+
+        chart.draw_series(stats.reuse_distances.iter().enumerate().map(|(i, b)| {
+            Rectangle::new([(i as u32, 0), (i as u32 + 1, b.count)], BLUE.mix(0.7).filled())
+        })).unwrap();
+
+        root.present().unwrap();
+    }
+
+    {
+        let file = path.with_file_name(format!("{}_working_set.png", stem));
+        let root = BitMapBackend::new(&file, (600, 400)).into_drawing_area();
+        root.fill(&WHITE).unwrap();
+
+        let end = stats.working_set.last().map(|w| w.start + w.requests).unwrap_or(1);
+        let max_bytes = stats.working_set.iter().map(|w| w.bytes).max().unwrap_or(1);
+        let mut chart = ChartBuilder::on(&root)
+            .x_label_area_size(35)
+            .y_label_area_size(40)
+            .caption("Working Set Size", ("sans-serif", 30.0))
+            .build_cartesian_2d(0u64..end, 0u64..max_bytes)
+            .unwrap();
+
+        chart.configure_mesh()
+            .y_label_formatter(&|y| format!("{} MB", y / (1024 * 1024)))
+            .x_desc("Request")
+            .y_desc("Bytes Touched per Window")
+            .draw().unwrap();
+
+        chart.draw_series(LineSeries::new(stats.working_set.iter().map(|w| (w.start, w.bytes)), &GREEN)).unwrap();
+
+        root.present().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(labels: &[i32]) -> Vec<FileRecord<i32>> {
+        labels.iter().map(|&label| FileRecord { label, size: 10 * label as i64, ..Default::default() }).collect()
+    }
+
+    #[test]
+    fn reuse_distances_count_the_objects_in_between() {
+        // 1 is requested again right away, 2 after two other objects and 3 after three
+        let stats = analyze(&trace(&[1, 1, 2, 3, 4, 2, 5, 3]), 100, 10);
+        assert_eq!(stats.cold_requests, 5);
+        let buckets: Vec<(u64, u64)> = stats.reuse_distances.iter().map(|b| (b.upper_bound, b.count)).collect();
+        assert_eq!(buckets, vec![(1, 1), (2, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn ties_in_popularity_are_ordered_by_label() {
+        let stats = analyze(&trace(&[5, 3, 9, 3, 1, 5, 7, 9]), 100, 10);
+        assert_eq!(stats.most_popular, vec![(3, 2), (5, 2), (9, 2), (1, 1), (7, 1)]);
+        assert_eq!(stats.rank_frequency, vec![2, 2, 2, 1, 1]);
+        assert!((stats.one_hit_wonder_ratio - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn counts_operations_footprint_and_windows() {
+        let mut data = trace(&[1, 2, 1, 3]);
+        data[2].op = Operation::Update;
+        data[2].size = 15;
+        data.push(FileRecord { label: 2, size: 20, op: Operation::Delete, ..Default::default() });
+        let stats = analyze(&data, 2, 10);
+        assert_eq!((stats.operations.gets, stats.operations.updates, stats.operations.deletes), (3, 1, 1));
+        assert_eq!(stats.requested_bytes, 10 + 20 + 15 + 30 + 20);
+        // The last size seen of every object, deletes leave it alone
+        assert_eq!(stats.footprint_bytes, 15 + 20 + 30);
+        let windows: Vec<(u64, u64, u64)> = stats.working_set.iter().map(|w| (w.start, w.objects, w.bytes)).collect();
+        assert_eq!(windows, vec![(0, 2, 30), (2, 2, 45), (4, 1, 20)]);
+    }
+}
@@ -0,0 +1,96 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Requests at the start of a run that fill the cache and are left out of its statistics.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Warmup {
+    Requests(usize),
+    Fraction(f64), // share of the trace
+    Full, // until the policy first has to evict
+}
+
+impl FromStr for Warmup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "full" {
+            return Ok(Warmup::Full);
+        }
+        if s.contains('.') {
+            return match f64::from_str(s) {
+                Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(Warmup::Fraction(fraction)),
+                _ => Err(format!("Warmup fraction must be between 0 and 1: {}", s))
+            };
+        }
+        usize::from_str(s).map(Warmup::Requests).map_err(|_| format!("Unknown warmup: {}", s))
+    }
+}
+
+impl Warmup {
+    /// Request the warmup ends at for a trace of `requests`, `None` when it depends on the policy.
+    pub fn end(&self, requests: usize) -> Option<usize> {
+        match self {
+            Warmup::Requests(count) => Some((*count).min(requests)),
+            Warmup::Fraction(fraction) => Some((requests as f64 * fraction) as usize),
+            Warmup::Full => None,
+        }
+    }
+}
+
+/// Statistics of a finished warmup, before the policy's were reset.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct WarmupStats {
+    pub requests: usize,
+    pub reads: i32,
+    pub hits: i32,
+    pub expired_misses: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use algorithm::Params;
+    use lru::LRU;
+    use simulator_shared_types::FileRecord;
+    use crate::cost::Costs;
+    use crate::native_modules::NativePolicyModule;
+    use crate::policy::PolicyModule;
+    use crate::trace::SizeChange;
+    use crate::{end_warmup, replay, RunState};
+    use super::*;
+
+    #[test]
+    fn parses_counts_fractions_and_full() {
+        assert_eq!(Warmup::from_str("full"), Ok(Warmup::Full));
+        assert_eq!(Warmup::from_str("100"), Ok(Warmup::Requests(100)));
+        assert_eq!(Warmup::from_str("0.25"), Ok(Warmup::Fraction(0.25)));
+        assert!(Warmup::from_str("1.5").is_err());
+        assert!(Warmup::from_str("some").is_err());
+    }
+
+    #[test]
+    fn ends_within_the_trace() {
+        assert_eq!(Warmup::Requests(100).end(40), Some(40));
+        assert_eq!(Warmup::Requests(10).end(40), Some(10));
+        assert_eq!(Warmup::Fraction(0.25).end(40), Some(10));
+        assert_eq!(Warmup::Full.end(40), None);
+    }
+
+    #[test]
+    fn warmup_requests_are_left_out_of_the_run() {
+        let data: Vec<FileRecord<i32>> = [1, 2, 1, 2, 3, 1].iter()
+            .map(|&label| FileRecord { label, size: 10, ..Default::default() })
+            .collect();
+        let changed = vec![false; data.len()];
+        let mut policy = NativePolicyModule::<LRU<i32>, i32>::new();
+        policy.initialize(100, &Params::new());
+        let mut run = RunState { expiry: None, events: None, series: None, costs: Costs::default() };
+
+        replay(&mut policy, &data, 0..4, &changed, SizeChange::Last, &mut run);
+        let warmup = end_warmup(&mut policy, 4, &mut run);
+        replay(&mut policy, &data, 4..data.len(), &changed, SizeChange::Last, &mut run);
+
+        assert_eq!((warmup.requests, warmup.reads, warmup.hits), (4, 4, 2));
+        // Only 3 and the last 1 count, the cached objects stay warm
+        assert_eq!(policy.stats(), (2, 1));
+    }
+}
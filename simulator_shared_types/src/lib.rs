@@ -1,15 +1,149 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
 use bytemuck::{Pod, Zeroable};
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash, Default)]
 #[repr(C)]
 pub struct FileRecord<T> {
     pub label: T,
-    pub size : i64
+    pub size : i64,
+    pub op : Operation,
+    pub time : u64, // trace timestamp, or the request's position when the trace has none
+    pub ttl : u64, // lifetime from the time the object is written, 0 never expires
+    pub cost : u64 // of fetching the object from the backend on a miss, 0 when unknown
+}
+
+/// What a request does to the object. Only reads count towards hit statistics.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+#[repr(i32)]
+pub enum Operation {
+    #[default]
+    Get = 0, // read, inserting the object on a miss
+    Set = 1, // write that places the object in the cache
+    Delete = 2, // drops the object from the cache
+    Update = 3, // write that changes the object in place if it is cached
+}
+
+impl TryFrom<i32> for Operation {
+    type Error = String;
+
+    fn try_from(op: i32) -> Result<Self, Self::Error> {
+        match op {
+            0 => Ok(Operation::Get),
+            1 => Ok(Operation::Set),
+            2 => Ok(Operation::Delete),
+            3 => Ok(Operation::Update),
+            _ => Err(format!("Unknown operation: {}", op))
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "GET" | "READ" => Ok(Operation::Get),
+            "SET" | "WRITE" | "PUT" => Ok(Operation::Set),
+            "DELETE" | "DEL" => Ok(Operation::Delete),
+            "UPDATE" => Ok(Operation::Update),
+            _ => Err(format!("Unknown operation: {}", s))
+        }
+    }
+}
+
+/// Something a policy did to its cache while handling a request.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum EventKind {
+    Insert, // object placed in the cache
+    Hit, // read of a cached object
+    Evict, // object dropped to make room, invalidations by the host are not events
+    Bypass, // object the policy declined to cache
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
+pub struct Event<T> {
+    pub kind : EventKind,
+    pub label : T,
+    pub size : i64,
+    pub time : u64 // time of the request that caused the event
+}
+
+impl<T : Copy> Copy for FileRecord<T> {}
+
+/// Keys that can be widened into the label of a `WireRecord`.
+pub trait WireKey : Copy {
+    fn to_wire(self) -> u64;
+    fn from_wire(label: u64) -> Self;
+}
+
+impl WireKey for i32 {
+    fn to_wire(self) -> u64 {
+        self as u32 as u64
+    }
+
+    fn from_wire(label: u64) -> Self {
+        label as u32 as i32
+    }
 }
 
-unsafe impl Zeroable for FileRecord<i32> {}
+impl WireKey for u64 {
+    fn to_wire(self) -> u64 {
+        self
+    }
 
-impl Copy for FileRecord<i32> {}
+    fn from_wire(label: u64) -> Self {
+        label
+    }
+}
 
-unsafe impl Pod for FileRecord<i32> {}
\ No newline at end of file
+/// A `FileRecord` as raw bytes for the bytemuck encodings.
+///
+/// The layout is the same for every key type and has no implicit padding, and the operation is a
+/// plain integer that is only checked when the record is read back with `FileRecord::try_from`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct WireRecord {
+    pub label : u64,
+    pub size : i64,
+    pub time : u64,
+    pub ttl : u64,
+    pub cost : u64,
+    pub op : i32,
+    pub padding : u32, // always 0
+}
+
+// Every field is plain integers and the padding is spelled out, so any bit pattern is a valid record
+unsafe impl Zeroable for WireRecord {}
+
+unsafe impl Pod for WireRecord {}
+
+impl<T : WireKey> From<FileRecord<T>> for WireRecord {
+    fn from(file: FileRecord<T>) -> Self {
+        WireRecord {
+            label: file.label.to_wire(),
+            size: file.size,
+            time: file.time,
+            ttl: file.ttl,
+            cost: file.cost,
+            op: file.op as i32,
+            padding: 0,
+        }
+    }
+}
+
+impl<T : WireKey> TryFrom<WireRecord> for FileRecord<T> {
+    type Error = String;
+
+    fn try_from(wire: WireRecord) -> Result<Self, Self::Error> {
+        Ok(FileRecord {
+            label: T::from_wire(wire.label),
+            size: wire.size,
+            op: Operation::try_from(wire.op)?,
+            time: wire.time,
+            ttl: wire.ttl,
+            cost: wire.cost,
+        })
+    }
+}